@@ -0,0 +1,180 @@
+//! The error envelope every handler answers with:
+//! `{"error": {"code", "message", "details", "requestId"}}`.
+//!
+//! `code` is stable and meant for programs, `message` for people, `details`
+//! carries structured context (validation problems, queue capacity) or
+//! `null`. `requestId` matches the `x-request-id` response header, so a
+//! client report can be found in the logs.
+
+use axum::{
+    Json,
+    extract::{
+        Request,
+        rejection::{BytesRejection, JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{HeaderValue, StatusCode, header::HeaderName},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::service::job_queue::QueueError;
+use crate::service::skill_repository::SkillError;
+use crate::service::task_service::TaskError;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self { status, code, message: message.into(), details: None }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, code, message)
+    }
+
+    /// Logs the cause and keeps it out of the response.
+    fn internal(cause: impl fmt::Display) -> Self {
+        tracing::error!("request failed: {}", cause);
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Internal server error")
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.status.as_u16(), self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        // Outside a request (tests, background tasks) there is nothing to correlate with
+        let request_id = REQUEST_ID.try_with(Clone::clone).unwrap_or_else(|_| Uuid::new_v4().to_string());
+        let body = json!({
+            "error": {
+                "code": self.code,
+                "message": self.message,
+                "details": self.details,
+                "requestId": request_id,
+            }
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Gives each request an id, taken from `x-request-id` when the client sent a
+/// usable one, and echoes it on the response.
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+// Service errors
+
+impl From<TaskError> for ApiError {
+    fn from(e: TaskError) -> Self {
+        match e {
+            TaskError::NotFound => ApiError::not_found("task_not_found", e.to_string()),
+            TaskError::IllegalTransition(ref transition) => ApiError::conflict("illegal_transition", e.to_string())
+                .with_details(json!({ "from": transition.from, "to": transition.to })),
+            TaskError::Storage(_) => ApiError::internal(e),
+        }
+    }
+}
+
+impl From<SkillError> for ApiError {
+    fn from(e: SkillError) -> Self {
+        match e {
+            SkillError::NotFound => ApiError::not_found("skill_not_found", e.to_string()),
+            SkillError::RevisionNotFound(_) => ApiError::not_found("revision_not_found", e.to_string()),
+            SkillError::Invalid(ref errors) => {
+                let details = json!({ "errors": errors });
+                ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_skill", e.to_string()).with_details(details)
+            }
+            SkillError::Bundle(_) => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_bundle", e.to_string()),
+            SkillError::Storage(_) => ApiError::internal(e),
+        }
+    }
+}
+
+impl From<QueueError> for ApiError {
+    fn from(e: QueueError) -> Self {
+        match e {
+            QueueError::Full { track, capacity } => ApiError::new(StatusCode::TOO_MANY_REQUESTS, "queue_full", e.to_string())
+                .with_details(json!({ "track": track, "capacity": capacity })),
+            QueueError::ShuttingDown => ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "shutting_down", e.to_string()),
+            QueueError::Task(e) => e.into(),
+        }
+    }
+}
+
+// Extractor rejections
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = match rejection {
+            JsonRejection::JsonDataError(_) => "invalid_body",
+            JsonRejection::JsonSyntaxError(_) => "malformed_json",
+            JsonRejection::MissingJsonContentType(_) => "unsupported_media_type",
+            _ => "unreadable_body",
+        };
+        ApiError::new(rejection.status(), code, rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(rejection.status(), "invalid_query", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(rejection.status(), "invalid_path", rejection.body_text())
+    }
+}
+
+impl From<BytesRejection> for ApiError {
+    fn from(rejection: BytesRejection) -> Self {
+        ApiError::new(rejection.status(), "unreadable_body", rejection.body_text())
+    }
+}
@@ -0,0 +1,369 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::service::llm::{DEFAULT_CHAT_BASE_URL, StageModels};
+use crate::service::retry::RetryConfig;
+use crate::service::transcribe::{DEFAULT_TRANSCRIBE_BASE_URL, DEFAULT_TRANSCRIBE_MODEL};
+
+/// Config file read when `SKILLFLOW_CONFIG` is not set; optional.
+pub const DEFAULT_CONFIG_PATH: &str = "skillflow.toml";
+
+/// A credential that never shows up in `Debug` output.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"<redacted>\"")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { listen: "0.0.0.0:64808".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBackend {
+    Memory,
+    Sqlite,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StoreBackend,
+    /// Database file for the `sqlite` backend.
+    pub sqlite_path: String,
+    /// Skill library database file for the `sqlite` backend.
+    pub library_path: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StoreBackend::Memory,
+            sqlite_path: "skillflow.db".to_string(),
+            library_path: "skillflow-library.db".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    pub base_url: String,
+    pub api_key: Option<Secret>,
+    pub models: StageModels,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_CHAT_BASE_URL.to_string(),
+            api_key: None,
+            models: StageModels::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscribeConfig {
+    pub base_url: String,
+    pub api_key: Option<Secret>,
+    pub model: String,
+    /// Sent as `response_format`, e.g. `verbose_json` for segment timestamps.
+    pub response_format: Option<String>,
+}
+
+impl Default for TranscribeConfig {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_TRANSCRIBE_BASE_URL.to_string(),
+            api_key: None,
+            model: DEFAULT_TRANSCRIBE_MODEL.to_string(),
+            response_format: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfig {
+    /// Formatter calls per video, including repairs of rejected output.
+    pub format_attempts: u32,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self { format_attempts: 3 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    /// Parse jobs run concurrently per track.
+    pub audio_workers: usize,
+    pub video_workers: usize,
+    /// Jobs waiting per track before parse requests are turned away.
+    pub max_queued: usize,
+    /// How long shutdown waits for running jobs before interrupting them.
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self { audio_workers: 2, video_workers: 1, max_queued: 32, drain_timeout_secs: 30 }
+    }
+}
+
+/// Service configuration: defaults, then the TOML file, then environment
+/// variables.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub chat: ChatConfig,
+    pub transcribe: TranscribeConfig,
+    pub pipeline: PipelineConfig,
+    pub queue: QueueConfig,
+    pub retry: RetryConfig,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, message: String },
+    Parse { path: PathBuf, message: String },
+    Env { name: &'static str, message: String },
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => write!(f, "cannot read {}: {}", path.display(), message),
+            ConfigError::Parse { path, message } => write!(f, "invalid {}: {}", path.display(), message),
+            ConfigError::Env { name, message } => write!(f, "invalid ${}: {}", name, message),
+            ConfigError::Invalid(problems) => write!(f, "invalid configuration: {}", problems.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads `$SKILLFLOW_CONFIG` (required to exist when set) or
+    /// `skillflow.toml` (if present), applies environment overrides and
+    /// validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var("SKILLFLOW_CONFIG") {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?,
+            Err(_) => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Read { path: path.to_path_buf(), message: e.to_string() })?;
+        toml::from_str(&text).map_err(|e| ConfigError::Parse { path: path.to_path_buf(), message: e.to_string() })
+    }
+
+    /// Overrides fields from environment variables, looked up through `var`.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let set = |target: &mut String, name: &str| {
+            if let Some(value) = var(name) {
+                *target = value;
+            }
+        };
+        set(&mut self.server.listen, "SKILLFLOW_LISTEN");
+        set(&mut self.storage.sqlite_path, "TASK_DB_PATH");
+        set(&mut self.storage.library_path, "LIBRARY_DB_PATH");
+        set(&mut self.chat.base_url, "CHAT_BASE_URL");
+        set(&mut self.chat.models.video_analysis, "VIDEO_ANALYSIS_MODEL");
+        set(&mut self.chat.models.skill_format, "SKILL_FORMAT_MODEL");
+        set(&mut self.transcribe.base_url, "TRANSCRIBE_BASE_URL");
+        set(&mut self.transcribe.model, "TRANSCRIBE_MODEL");
+
+        if let Some(backend) = var("TASK_STORE") {
+            self.storage.backend = match backend.as_str() {
+                "memory" => StoreBackend::Memory,
+                "sqlite" => StoreBackend::Sqlite,
+                other => {
+                    return Err(ConfigError::Env {
+                        name: "TASK_STORE",
+                        message: format!("expected `memory` or `sqlite`, got `{}`", other),
+                    });
+                }
+            };
+        }
+        if let Some(key) = var("OPENROUTER_API_KEY") {
+            self.chat.api_key = Some(Secret::new(key));
+        }
+        if let Some(key) = var("SILICONFLOW_API_KEY") {
+            self.transcribe.api_key = Some(Secret::new(key));
+        }
+        if let Some(format) = var("TRANSCRIBE_RESPONSE_FORMAT") {
+            self.transcribe.response_format = Some(format);
+        }
+        parse_env(&var, "FORMAT_ATTEMPTS", &mut self.pipeline.format_attempts)?;
+        parse_env(&var, "AUDIO_WORKERS", &mut self.queue.audio_workers)?;
+        parse_env(&var, "VIDEO_WORKERS", &mut self.queue.video_workers)?;
+        parse_env(&var, "MAX_QUEUED_JOBS", &mut self.queue.max_queued)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.server.listen.parse::<SocketAddr>().is_err() {
+            problems.push(format!("server.listen `{}` is not a socket address", self.server.listen));
+        }
+        if self.storage.backend == StoreBackend::Sqlite {
+            for (field, path) in [("storage.sqlite_path", &self.storage.sqlite_path), ("storage.library_path", &self.storage.library_path)] {
+                if path.trim().is_empty() {
+                    problems.push(format!("{} is required for the sqlite backend", field));
+                }
+            }
+        }
+        for (field, url) in [("chat.base_url", &self.chat.base_url), ("transcribe.base_url", &self.transcribe.base_url)] {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                problems.push(format!("{} `{}` must be an http(s) URL", field, url));
+            }
+        }
+        for (field, model) in [
+            ("chat.models.video_analysis", &self.chat.models.video_analysis),
+            ("chat.models.skill_format", &self.chat.models.skill_format),
+            ("transcribe.model", &self.transcribe.model),
+        ] {
+            if model.trim().is_empty() {
+                problems.push(format!("{} must not be empty", field));
+            }
+        }
+
+        if !(1..=10).contains(&self.pipeline.format_attempts) {
+            problems.push(format!("pipeline.format_attempts must be 1..=10, got {}", self.pipeline.format_attempts));
+        }
+
+        for (field, value) in [
+            ("queue.audio_workers", self.queue.audio_workers),
+            ("queue.video_workers", self.queue.video_workers),
+            ("queue.max_queued", self.queue.max_queued),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be at least 1", field));
+            }
+        }
+        problems.extend(self.retry.problems());
+
+        if problems.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(problems)) }
+    }
+
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.server.listen.parse().expect("validated at startup")
+    }
+}
+
+/// Parses `$name` into `target` when it is set.
+fn parse_env<T: std::str::FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &'static str,
+    target: &mut T,
+) -> Result<(), ConfigError> {
+    if let Some(value) = var(name) {
+        *target = value.parse().map_err(|_| ConfigError::Env {
+            name,
+            message: format!("expected a positive integer, got `{}`", value),
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_file_then_env_overrides() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [server]
+            listen = "127.0.0.1:9000"
+
+            [chat]
+            api_key = "file-key"
+            models = { skill_format = "formatter" }
+            "#,
+        )
+        .unwrap();
+        let env: HashMap<&str, &str> =
+            HashMap::from([("TASK_STORE", "sqlite"), ("OPENROUTER_API_KEY", "env-key"), ("VIDEO_WORKERS", "4")]);
+        config.apply_env(|name| env.get(name).map(|v| v.to_string())).unwrap();
+
+        assert_eq!(config.listen_addr().port(), 9000);
+        assert_eq!(config.storage.backend, StoreBackend::Sqlite);
+        assert_eq!(config.chat.api_key.as_ref().map(Secret::expose), Some("env-key"));
+        assert_eq!(config.chat.models.skill_format, "formatter");
+        assert_eq!(config.queue.video_workers, 4);
+        assert_eq!(config.chat.models.video_analysis, StageModels::default().video_analysis);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let mut config = Config::default();
+        config.server.listen = "nowhere".to_string();
+        config.transcribe.base_url = "ftp://asr".to_string();
+        config.chat.models.video_analysis = String::new();
+        config.retry.transcribe.max_attempts = 0;
+
+        match config.validate() {
+            Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 4),
+            other => panic!("expected validation errors, got {:?}", other),
+        }
+        assert!(toml::from_str::<Config>("[server]\nport = 1").is_err());
+    }
+
+    #[test]
+    fn test_example_file_matches_defaults() {
+        let example: Config = toml::from_str(include_str!("../skillflow.example.toml")).unwrap();
+        assert_eq!(example, Config::default());
+    }
+
+    #[test]
+    fn test_debug_output_redacts_secrets() {
+        let mut config = Config::default();
+        config.transcribe.api_key = Some(Secret::new("sk-live-123"));
+        let printed = format!("{:?}", config);
+        assert!(!printed.contains("sk-live-123"));
+        assert!(printed.contains("<redacted>"));
+    }
+}
@@ -0,0 +1,390 @@
+//! `.skillflow` bundles: a package together with the template images its
+//! selectors point at.
+//!
+//! A bundle is an uncompressed tar archive:
+//!
+//! ```text
+//! manifest.json    BundleManifest
+//! package.json     the package document
+//! assets/...       template images, at the path the selectors use
+//! ```
+//!
+//! The manifest lists every other file with its sha256, so a truncated or
+//! edited archive is rejected on import.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{Cursor, Read};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::domain::package::Package;
+
+pub const BUNDLE_EXTENSION: &str = "skillflow";
+pub const MANIFEST_PATH: &str = "manifest.json";
+pub const PACKAGE_PATH: &str = "package.json";
+/// Bundle layout version, independent of the package spec version.
+pub const BUNDLE_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: u32,
+    /// `version` of the bundled package.
+    #[serde(rename = "specVersion")]
+    pub spec_version: String,
+    pub name: String,
+    /// Every file in the archive except the manifest itself.
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BundleError {
+    /// Not a readable tar archive, or a file could not be read.
+    Archive(String),
+    MissingFile(String),
+    /// A path that is absolute, contains `..` or is not a regular file.
+    UnsafePath(String),
+    /// The same path appears more than once in the archive.
+    DuplicateEntry(String),
+    HashMismatch(String),
+    /// Present in the archive but not listed in the manifest, or the reverse.
+    ManifestMismatch(String),
+    UnsupportedFormat(u32),
+    SpecMismatch { manifest: String, package: String },
+    InvalidPackage(String),
+    Assets(Vec<AssetProblem>),
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Archive(msg) => write!(f, "unreadable bundle: {}", msg),
+            BundleError::MissingFile(path) => write!(f, "bundle has no {}", path),
+            BundleError::UnsafePath(path) => write!(f, "bundle entry `{}` is not allowed", path),
+            BundleError::DuplicateEntry(path) => write!(f, "bundle contains {} more than once", path),
+            BundleError::HashMismatch(path) => write!(f, "{} does not match its manifest hash", path),
+            BundleError::ManifestMismatch(path) => write!(f, "{} is not both in the archive and the manifest", path),
+            BundleError::UnsupportedFormat(format) => write!(f, "unsupported bundle format {}", format),
+            BundleError::SpecMismatch { manifest, package } => {
+                write!(f, "manifest spec version {} does not match package version {}", manifest, package)
+            }
+            BundleError::InvalidPackage(msg) => write!(f, "invalid package.json: {}", msg),
+            BundleError::Assets(problems) => {
+                let parts: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                write!(f, "{}", parts.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "path", rename_all = "snake_case")]
+pub enum AssetProblem {
+    /// A template selector names a file the bundle does not contain.
+    MissingTemplate(String),
+    /// A bundled file no selector refers to.
+    UnreferencedAsset(String),
+}
+
+impl fmt::Display for AssetProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetProblem::MissingTemplate(path) => write!(f, "template {} is not bundled", path),
+            AssetProblem::UnreferencedAsset(path) => write!(f, "asset {} is not referenced", path),
+        }
+    }
+}
+
+/// Every `template` path used by a template selector, in the selector map or
+/// inline in steps, anchors and candidates.
+pub fn template_paths(package: &Package) -> BTreeSet<String> {
+    fn walk(value: &Value, paths: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                if map.get("strategy").and_then(Value::as_str) == Some("template")
+                    && let Some(path) = map.get("template").and_then(Value::as_str)
+                {
+                    paths.insert(normalize(path));
+                }
+                map.values().for_each(|v| walk(v, paths));
+            }
+            Value::Array(items) => items.iter().for_each(|v| walk(v, paths)),
+            _ => {}
+        }
+    }
+    let mut paths = BTreeSet::new();
+    walk(&serde_json::to_value(package).unwrap_or(Value::Null), &mut paths);
+    paths
+}
+
+/// Checks that every referenced template is among `assets` and every asset is
+/// referenced. Empty when the two sets match.
+pub fn verify_assets<'a>(package: &Package, assets: impl IntoIterator<Item = &'a String>) -> Vec<AssetProblem> {
+    let referenced = template_paths(package);
+    let bundled: BTreeSet<String> = assets.into_iter().map(|p| normalize(p)).collect();
+    let missing = referenced.difference(&bundled).cloned().map(AssetProblem::MissingTemplate);
+    let unreferenced = bundled.difference(&referenced).cloned().map(AssetProblem::UnreferencedAsset);
+    missing.chain(unreferenced).collect()
+}
+
+/// Template paths are written relative to the bundle root; `./` is tolerated.
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./").to_string()
+}
+
+fn is_safe(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && !path.contains('\\')
+        && path.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// File contents keyed by bundle path, e.g. `assets/templates/export_icon.png`.
+pub type Assets = BTreeMap<String, Vec<u8>>;
+
+/// A package and its assets.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    pub package: Package,
+    pub assets: Assets,
+}
+
+impl Bundle {
+    pub fn new(package: Package, assets: Assets) -> Self {
+        Self { package, assets }
+    }
+
+    pub fn verify(&self) -> Result<(), BundleError> {
+        if let Some(path) = self.assets.keys().find(|p| !is_safe(p) || *p == MANIFEST_PATH || *p == PACKAGE_PATH) {
+            return Err(BundleError::UnsafePath(path.clone()));
+        }
+        let problems = verify_assets(&self.package, self.assets.keys());
+        if problems.is_empty() { Ok(()) } else { Err(BundleError::Assets(problems)) }
+    }
+
+    pub fn manifest(&self, package_json: &[u8]) -> BundleManifest {
+        let file = |path: &str, bytes: &[u8]| ManifestFile {
+            path: path.to_string(),
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+        };
+        let mut files = vec![file(PACKAGE_PATH, package_json)];
+        files.extend(self.assets.iter().map(|(path, bytes)| file(path, bytes)));
+        BundleManifest {
+            format: BUNDLE_FORMAT,
+            spec_version: self.package.version.clone(),
+            name: self.package.package.name.clone(),
+            files,
+        }
+    }
+
+    /// Verifies the bundle and writes it as a tar archive.
+    pub fn to_archive(&self) -> Result<Vec<u8>, BundleError> {
+        self.verify()?;
+        let package_json =
+            serde_json::to_vec_pretty(&self.package).map_err(|e| BundleError::InvalidPackage(e.to_string()))?;
+        let manifest = serde_json::to_vec_pretty(&self.manifest(&package_json))
+            .map_err(|e| BundleError::Archive(e.to_string()))?;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let entries = [(MANIFEST_PATH, manifest.as_slice()), (PACKAGE_PATH, package_json.as_slice())]
+            .into_iter()
+            .chain(self.assets.iter().map(|(path, bytes)| (path.as_str(), bytes.as_slice())));
+        for (path, bytes) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, bytes).map_err(|e| BundleError::Archive(e.to_string()))?;
+        }
+        builder.into_inner().map_err(|e| BundleError::Archive(e.to_string()))
+    }
+
+    /// Reads an archive written by [`Bundle::to_archive`], checking paths,
+    /// manifest hashes, the spec version and the assets.
+    pub fn from_archive(bytes: &[u8]) -> Result<Self, BundleError> {
+        let archive_error = |e: std::io::Error| BundleError::Archive(e.to_string());
+        let mut files = Assets::new();
+        let mut archive = tar::Archive::new(Cursor::new(bytes));
+        for entry in archive.entries().map_err(archive_error)? {
+            let mut entry = entry.map_err(archive_error)?;
+            let path = entry.path().map_err(archive_error)?.to_string_lossy().trim_start_matches("./").to_string();
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => continue,
+                tar::EntryType::Regular if is_safe(&path) => {}
+                _ => return Err(BundleError::UnsafePath(path)),
+            }
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(archive_error)?;
+            if files.contains_key(&path) {
+                return Err(BundleError::DuplicateEntry(path));
+            }
+            files.insert(path, data);
+        }
+
+        let manifest = files.remove(MANIFEST_PATH).ok_or_else(|| BundleError::MissingFile(MANIFEST_PATH.to_string()))?;
+        let manifest: BundleManifest = serde_json::from_slice(&manifest)
+            .map_err(|e| BundleError::Archive(format!("{}: {}", MANIFEST_PATH, e)))?;
+        if manifest.format != BUNDLE_FORMAT {
+            return Err(BundleError::UnsupportedFormat(manifest.format));
+        }
+        for listed in &manifest.files {
+            let data = files.get(&listed.path).ok_or_else(|| BundleError::ManifestMismatch(listed.path.clone()))?;
+            if sha256_hex(data) != listed.sha256 || data.len() as u64 != listed.size {
+                return Err(BundleError::HashMismatch(listed.path.clone()));
+            }
+        }
+        if let Some(extra) = files.keys().find(|path| !manifest.files.iter().any(|f| &f.path == *path)) {
+            return Err(BundleError::ManifestMismatch(extra.clone()));
+        }
+
+        let package_json = files.remove(PACKAGE_PATH).ok_or_else(|| BundleError::MissingFile(PACKAGE_PATH.to_string()))?;
+        let package: Package =
+            serde_json::from_slice(&package_json).map_err(|e| BundleError::InvalidPackage(e.to_string()))?;
+        if package.version != manifest.spec_version {
+            return Err(BundleError::SpecMismatch { manifest: manifest.spec_version, package: package.version });
+        }
+
+        let bundle = Bundle::new(package, files);
+        bundle.verify()?;
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use serde_json::json;
+
+    fn package() -> Package {
+        fixtures::package()
+            .selectors(json!({
+                "export_icon": { "strategy": "template", "template": "assets/templates/export_icon.png" }
+            }))
+            .steps(json!([
+                { "id": "s1", "op": "click", "target": { "$ref": "#/selectors/export_icon" } },
+                { "id": "s2", "op": "click", "target": {
+                    "strategy": "relative",
+                    "anchor": { "strategy": "template", "template": "./assets/templates/dialog.png" },
+                    "relation": { "type": "below" },
+                    "target": { "strategy": "ocr", "text": "PNG" }
+                } }
+            ]))
+            .build()
+    }
+
+    fn assets() -> Assets {
+        BTreeMap::from([
+            ("assets/templates/export_icon.png".to_string(), vec![0x89, b'P', b'N', b'G', 1]),
+            ("assets/templates/dialog.png".to_string(), vec![0x89, b'P', b'N', b'G', 2]),
+        ])
+    }
+
+    fn rewrite(archive: &[u8], edit: impl Fn(&str, Vec<u8>) -> Option<Vec<u8>>) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for entry in tar::Archive::new(Cursor::new(archive)).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if let Some(data) = edit(&path, data) {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_cksum();
+                builder.append_data(&mut header, &path, data.as_slice()).unwrap();
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_and_manifest() {
+        let archive = Bundle::new(package(), assets()).to_archive().unwrap();
+        let bundle = Bundle::from_archive(&archive).unwrap();
+        assert_eq!(bundle.assets, assets());
+        assert_eq!(bundle.package.steps.len(), 2);
+
+        let manifest = bundle.manifest(&serde_json::to_vec_pretty(&bundle.package).unwrap());
+        assert_eq!(manifest.spec_version, "0.1");
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, [PACKAGE_PATH, "assets/templates/dialog.png", "assets/templates/export_icon.png"]);
+    }
+
+    #[test]
+    fn test_asset_verification() {
+        let mut partial = assets();
+        partial.remove("assets/templates/dialog.png");
+        partial.insert("assets/unused.png".to_string(), vec![0]);
+        assert_eq!(
+            verify_assets(&package(), partial.keys()),
+            [
+                AssetProblem::MissingTemplate("assets/templates/dialog.png".to_string()),
+                AssetProblem::UnreferencedAsset("assets/unused.png".to_string()),
+            ]
+        );
+
+        let mut escaping = assets();
+        escaping.insert("../etc/passwd".to_string(), vec![0]);
+        assert_eq!(Bundle::new(package(), escaping).verify(), Err(BundleError::UnsafePath("../etc/passwd".to_string())));
+    }
+
+    #[test]
+    fn test_tampered_archives_are_rejected() {
+        let archive = Bundle::new(package(), assets()).to_archive().unwrap();
+
+        let edited = rewrite(&archive, |path, data| {
+            Some(if path == "assets/templates/dialog.png" { vec![0] } else { data })
+        });
+        assert_eq!(
+            Bundle::from_archive(&edited).unwrap_err(),
+            BundleError::HashMismatch("assets/templates/dialog.png".to_string())
+        );
+
+        // A second copy of a listed file would otherwise replace the hashed one
+        let mut builder = tar::Builder::new(Vec::new());
+        for entry in tar::Archive::new(Cursor::new(&archive)).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let header = entry.header().clone();
+            builder.append(&header, &mut entry).unwrap();
+        }
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_cksum();
+        builder.append_data(&mut header, "./assets/templates/dialog.png", [0u8].as_slice()).unwrap();
+        let duplicated = builder.into_inner().unwrap();
+        assert_eq!(
+            Bundle::from_archive(&duplicated).unwrap_err(),
+            BundleError::DuplicateEntry("assets/templates/dialog.png".to_string())
+        );
+
+        let dropped = rewrite(&archive, |path, data| (path != MANIFEST_PATH).then_some(data));
+        assert_eq!(Bundle::from_archive(&dropped).unwrap_err(), BundleError::MissingFile(MANIFEST_PATH.to_string()));
+
+        let respecced = rewrite(&archive, |path, data| {
+            if path != MANIFEST_PATH {
+                return Some(data);
+            }
+            let mut manifest: BundleManifest = serde_json::from_slice(&data).unwrap();
+            manifest.spec_version = "9.9".to_string();
+            Some(serde_json::to_vec(&manifest).unwrap())
+        });
+        assert!(matches!(Bundle::from_archive(&respecced), Err(BundleError::SpecMismatch { .. })));
+        assert!(matches!(Bundle::from_archive(b"not a tar"), Err(BundleError::Archive(_) | BundleError::MissingFile(_))));
+    }
+}
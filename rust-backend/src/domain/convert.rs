@@ -0,0 +1,753 @@
+//! Conversions between AIPDL packages and the flat [`Skill`] model.
+//!
+//! A package becomes a skill as follows:
+//!
+//! * each step's primary selector (click/type/scroll target, drag origin,
+//!   wait/assert expectation) becomes its [`Target`]; `$ref` targets are named
+//!   after their registry key, inline ones are unnamed;
+//! * the selector becomes locators: OCR is a `text` locator, a template a
+//!   `visual` one, relative and multi selectors `position` ones, each carrying
+//!   the selector JSON as its value; multi candidates keep their order as
+//!   priorities (equal priorities for `bestConfidence`), and the selector's
+//!   scope is a trailing `position` locator holding the scope JSON, so a rect
+//!   scope reads as a screen region;
+//! * everything else on the step (typed text, hotkeys, drag vectors, params,
+//!   retry, on_fail, ...) is kept verbatim in `parameters`, and retry timing
+//!   is summarised in `wait_after`;
+//! * package settings without a skill field go to [`PackageExtras`].
+//!
+//! Converting such a skill back yields the same package, up to equivalent
+//! spellings (a `bestConfidence` pick is the default, a one-candidate multi is
+//! its candidate, timestamps are UTC). Manually authored skills convert as long
+//! as every target has a text or visual locator and the parameters fit the
+//! step's operation.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use crate::domain::package::{
+    AppSpec, MultiSelector, OCRSelector, Package, PackageMeta, PickPolicy, Retry, Scope, Selector,
+    SelectorOrRef, SelectorRef, Step as PackageStep, StepOperation, TemplateSelector,
+};
+use crate::domain::resolve::{selector_key, selector_ref};
+use crate::domain::skill::{
+    ActionType, Locator, LocatorMethod, PackageExtras, Skill, SourceType, Step as SkillStep, Target, TargetType,
+};
+
+/// Spec version written for skills that do not record one.
+pub const SPEC_VERSION: &str = "0.1";
+
+/// Rough time a step takes besides its waits, for `estimated_duration`.
+const SECONDS_PER_STEP: f32 = 3.0;
+
+/// Poll interval of wait steps built from a skill's `wait_after`.
+const WAIT_POLL_MS: u32 = 250;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConvertError {
+    /// The step needs a target but none of its locators maps to a selector.
+    NoSelector { step_id: u32 },
+    /// Two steps name the same target but locate it differently.
+    ConflictingTarget { name: String },
+    /// The action and parameters do not form a valid package step.
+    InvalidStep { step_id: u32, message: String },
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::NoSelector { step_id } => {
+                write!(f, "step {}: no text, visual or selector locator for the target", step_id)
+            }
+            ConvertError::ConflictingTarget { name } => {
+                write!(f, "target '{}' is located differently by different steps", name)
+            }
+            ConvertError::InvalidStep { step_id, message } => write!(f, "step {}: {}", step_id, message),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+// Package -> Skill
+
+impl From<&Package> for Skill {
+    fn from(package: &Package) -> Self {
+        let steps: Vec<SkillStep> =
+            package.steps.iter().enumerate().map(|(i, step)| skill_step(package, i, step)).collect();
+
+        let targeted: HashSet<&str> = steps.iter().map(|s| s.target.name.as_str()).collect();
+        let selectors = package
+            .selectors
+            .iter()
+            .filter(|(key, _)| !targeted.contains(key.as_str()))
+            .map(|(key, selector)| (key.clone(), selector.clone()))
+            .collect();
+
+        let waits: f32 = steps.iter().map(|s| s.wait_after).sum();
+        let meta = &package.package;
+        Skill {
+            skill_id: skill_id(package).to_string(),
+            name: meta.name.clone(),
+            software: package.app.name.clone(),
+            version: version_range(&package.app),
+            description: meta.description.clone().unwrap_or_default(),
+            total_steps: steps.len() as u32,
+            estimated_duration: (steps.len() as f32 * SECONDS_PER_STEP + waits).ceil() as u32,
+            steps,
+            tags: meta.tags.clone(),
+            created_at: DateTime::parse_from_rfc3339(&meta.created_at).ok().map(|t| t.with_timezone(&Utc)),
+            source_type: SourceType::VideoAnalysis,
+            package: Some(PackageExtras {
+                spec_version: (package.version != SPEC_VERSION).then(|| package.version.clone()),
+                author: meta.author.clone(),
+                env: package.env.clone(),
+                vars: package.vars.clone(),
+                selectors,
+            }),
+        }
+    }
+}
+
+impl From<Package> for Skill {
+    fn from(package: Package) -> Self {
+        Skill::from(&package)
+    }
+}
+
+/// Stable across conversions of the same package.
+fn skill_id(package: &Package) -> Uuid {
+    let name = format!("skillflow:{}:{}:{}", package.app.name, package.package.name, package.package.created_at);
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes())
+}
+
+fn version_range(app: &AppSpec) -> String {
+    match (&app.min_version, &app.max_version) {
+        (None, None) => "any".to_string(),
+        (Some(min), None) => format!(">={}", min),
+        (None, Some(max)) => format!("<={}", max),
+        (Some(min), Some(max)) => format!(">={}, <={}", min, max),
+    }
+}
+
+fn default_step_id(step_id: u32) -> String {
+    format!("s{}", step_id)
+}
+
+/// The action a step performs and the field holding its target, if any.
+fn primary_target(op: &StepOperation) -> (ActionType, Option<(&'static str, &SelectorOrRef)>) {
+    match op {
+        StepOperation::Click(c) => (ActionType::Click, Some(("target", &c.target))),
+        StepOperation::Type(t) => (ActionType::Input, t.target.as_ref().map(|s| ("target", s))),
+        StepOperation::Drag(d) => (ActionType::Drag, Some(("from", &d.from))),
+        StepOperation::Scroll(s) => (ActionType::Scroll, s.target.as_ref().map(|s| ("target", s))),
+        StepOperation::Hotkey(_) => (ActionType::Shortcut, None),
+        StepOperation::Wait(w) => (ActionType::Wait, Some(("until", &w.until))),
+        StepOperation::Assert(a) => (ActionType::Assert, Some(("expect", &a.expect))),
+    }
+}
+
+fn skill_step(package: &Package, index: usize, step: &PackageStep) -> SkillStep {
+    let step_id = index as u32 + 1;
+    let (action_type, primary) = primary_target(&step.op);
+    let target = match primary {
+        Some((_, selector)) => skill_target(package, selector, &step.op),
+        None => Target { target_type: TargetType::Button, name: String::new(), locators: Vec::new() },
+    };
+
+    let mut parameters = match serde_json::to_value(step) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    parameters.retain(|_, v| !v.is_null());
+    parameters.remove("op");
+    parameters.remove("name");
+    if let Some((field, _)) = primary {
+        parameters.remove(field);
+    }
+    if step.id == default_step_id(step_id) {
+        parameters.remove("id");
+    }
+
+    SkillStep {
+        step_id,
+        action_type,
+        instruction: step.name.clone().unwrap_or_else(|| default_instruction(&step.op, &target)),
+        target,
+        wait_after: wait_after(step),
+        parameters: Value::Object(parameters),
+        confidence: 1.0,
+    }
+}
+
+fn skill_target(package: &Package, selector: &SelectorOrRef, op: &StepOperation) -> Target {
+    let (name, selector) = match selector {
+        SelectorOrRef::Ref(r) => {
+            let key = selector_key(&r.reference).unwrap_or_else(|| r.reference.clone());
+            let selector = package.selectors.get(&key);
+            (key, selector)
+        }
+        SelectorOrRef::Inline(s) => (String::new(), Some(s.as_ref())),
+    };
+
+    let target_type = match (op, selector) {
+        (StepOperation::Type(_), _) => TargetType::InputField,
+        (_, Some(Selector::Template(_))) => TargetType::Icon,
+        (_, Some(Selector::OCR(s))) if matches!(s.scope, Some(Scope::ActiveMenu(_))) => TargetType::MenuItem,
+        _ => TargetType::Button,
+    };
+    Target { target_type, name, locators: selector.map(|s| locators(package, s)).unwrap_or_default() }
+}
+
+fn scope_slot(selector: &mut Selector) -> &mut Option<Scope> {
+    match selector {
+        Selector::OCR(s) => &mut s.scope,
+        Selector::Template(s) => &mut s.scope,
+        Selector::Relative(s) => &mut s.scope,
+        Selector::Multi(s) => &mut s.scope,
+    }
+}
+
+fn locators(package: &Package, selector: &Selector) -> Vec<Locator> {
+    let (candidates, first_match, scope) = match selector {
+        Selector::Multi(m) => {
+            let first_match = m.pick.as_ref().is_some_and(|p| p.policy == "firstMatch");
+            (m.candidates.clone(), first_match, m.scope.clone())
+        }
+        single => {
+            let mut single = single.clone();
+            let scope = scope_slot(&mut single).take();
+            (vec![SelectorOrRef::Inline(Box::new(single))], false, scope)
+        }
+    };
+
+    let mut locators: Vec<Locator> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| Locator {
+            method: candidate_method(package, candidate),
+            value: serde_json::to_value(candidate).unwrap_or(Value::Null),
+            priority: if first_match { i as u32 + 1 } else { 1 },
+        })
+        .collect();
+    if let Some(scope) = scope {
+        let priority = locators.iter().map(|l| l.priority).max().unwrap_or(0) + 1;
+        locators.push(Locator {
+            method: LocatorMethod::Position,
+            value: serde_json::to_value(scope).unwrap_or(Value::Null),
+            priority,
+        });
+    }
+    locators
+}
+
+fn candidate_method(package: &Package, candidate: &SelectorOrRef) -> LocatorMethod {
+    let selector = match candidate {
+        SelectorOrRef::Inline(s) => Some(s.as_ref()),
+        SelectorOrRef::Ref(r) => selector_key(&r.reference).and_then(|key| package.selectors.get(&key)),
+    };
+    match selector {
+        Some(Selector::OCR(_)) | None => LocatorMethod::Text,
+        Some(Selector::Template(_)) => LocatorMethod::Visual,
+        Some(Selector::Relative(_) | Selector::Multi(_)) => LocatorMethod::Position,
+    }
+}
+
+/// Seconds the step may spend before the next one starts: the whole budget
+/// for waits, the retry interval for everything else.
+fn wait_after(step: &PackageStep) -> f32 {
+    let Some(retry) = &step.retry else {
+        return 0.0;
+    };
+    let ms = match step.op {
+        StepOperation::Wait(_) => retry.timeout_ms,
+        _ => retry.interval_ms,
+    };
+    ms as f32 / 1000.0
+}
+
+/// The instruction shown for unnamed steps; a skill whose instruction still
+/// reads like this converts back to an unnamed step.
+fn default_instruction(op: &StepOperation, target: &Target) -> String {
+    let label = target_label(target);
+    match op {
+        StepOperation::Click(_) => format!("Click {}", label),
+        StepOperation::Type(t) => format!("Type \"{}\"", t.text),
+        StepOperation::Drag(_) => format!("Drag {}", label),
+        StepOperation::Scroll(s) => format!("Scroll {} by {}", s.delta.direction, s.delta.amount),
+        StepOperation::Hotkey(h) => format!("Press {}", h.keys.join("+")),
+        StepOperation::Wait(_) => format!("Wait for {}", label),
+        StepOperation::Assert(_) => format!("Check {}", label),
+    }
+}
+
+fn target_label(target: &Target) -> String {
+    if !target.name.is_empty() {
+        return target.name.clone();
+    }
+    target
+        .locators
+        .iter()
+        .find_map(|l| match &l.value {
+            Value::String(text) => Some(text.clone()),
+            Value::Object(o) => o.get("text").or(o.get("template")).and_then(Value::as_str).map(str::to_string),
+            _ => None,
+        })
+        .unwrap_or_else(|| "target".to_string())
+}
+
+// Skill -> Package
+
+impl TryFrom<&Skill> for Package {
+    type Error = ConvertError;
+
+    fn try_from(skill: &Skill) -> Result<Self, ConvertError> {
+        let extras = skill.package.clone().unwrap_or_default();
+        let mut selectors = extras.selectors;
+        let steps = skill
+            .steps
+            .iter()
+            .map(|step| package_step(step, &mut selectors))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (min_version, max_version) = parse_version_range(&skill.version);
+        Ok(Package {
+            version: extras.spec_version.unwrap_or_else(|| SPEC_VERSION.to_string()),
+            package: PackageMeta {
+                name: skill.name.clone(),
+                created_at: skill.created_at.unwrap_or_else(Utc::now).to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                description: (!skill.description.is_empty()).then(|| skill.description.clone()),
+                author: extras.author,
+                tags: skill.tags.clone(),
+            },
+            app: AppSpec { name: skill.software.clone(), min_version, max_version },
+            env: extras.env,
+            vars: extras.vars,
+            selectors,
+            steps,
+        })
+    }
+}
+
+impl TryFrom<Skill> for Package {
+    type Error = ConvertError;
+
+    fn try_from(skill: Skill) -> Result<Self, ConvertError> {
+        Package::try_from(&skill)
+    }
+}
+
+/// Reads `any`, `>=min`, `<=max` and `>=min, <=max`; anything else is taken
+/// as the minimum version.
+fn parse_version_range(version: &str) -> (Option<String>, Option<String>) {
+    let version = version.trim();
+    if version.is_empty() || version == "any" || version == "*" {
+        return (None, None);
+    }
+    let (mut min, mut max) = (None, None);
+    for part in version.split(',').map(str::trim) {
+        if let Some(v) = part.strip_prefix(">=") {
+            min = Some(v.trim().to_string());
+        } else if let Some(v) = part.strip_prefix("<=") {
+            max = Some(v.trim().to_string());
+        } else {
+            return (Some(version.to_string()), None);
+        }
+    }
+    (min, max)
+}
+
+fn package_step(step: &SkillStep, selectors: &mut HashMap<String, Selector>) -> Result<PackageStep, ConvertError> {
+    let invalid = |message: String| ConvertError::InvalidStep { step_id: step.step_id, message };
+    let (op, primary, required) = match step.action_type {
+        ActionType::Click | ActionType::Menu => ("click", Some("target"), true),
+        ActionType::Input => ("type", Some("target"), false),
+        ActionType::Drag => ("drag", Some("from"), true),
+        ActionType::Scroll => ("scroll", Some("target"), false),
+        ActionType::Shortcut => ("hotkey", None, false),
+        ActionType::Wait => ("wait", Some("until"), true),
+        ActionType::Assert => ("assert", Some("expect"), true),
+    };
+
+    let mut fields = match &step.parameters {
+        Value::Object(fields) => fields.clone(),
+        Value::Null => Map::new(),
+        _ => return Err(invalid("parameters must be an object".to_string())),
+    };
+    fields.entry("id").or_insert_with(|| json!(default_step_id(step.step_id)));
+    fields.insert("op".to_string(), json!(op));
+    if let Some(field) = primary {
+        match target_selector(step, selectors)? {
+            Some(selector) => {
+                fields.insert(field.to_string(), serde_json::to_value(selector).unwrap_or(Value::Null));
+            }
+            None if required => return Err(ConvertError::NoSelector { step_id: step.step_id }),
+            None => {}
+        }
+    }
+
+    let mut converted: PackageStep =
+        serde_json::from_value(Value::Object(fields)).map_err(|e| invalid(e.to_string()))?;
+    if converted.retry.is_none() && step.wait_after > 0.0 {
+        converted.retry = Some(retry_for(&converted.op, step.wait_after));
+    }
+    let instruction = step.instruction.trim();
+    converted.name = (!instruction.is_empty() && instruction != default_instruction(&converted.op, &step.target))
+        .then(|| instruction.to_string());
+    Ok(converted)
+}
+
+/// Inverse of [`wait_after`] for skills without an explicit retry.
+fn retry_for(op: &StepOperation, wait_after: f32) -> Retry {
+    let ms = (wait_after * 1000.0).round() as u32;
+    match op {
+        StepOperation::Wait(_) => Retry { times: ms / WAIT_POLL_MS, interval_ms: WAIT_POLL_MS, timeout_ms: ms },
+        _ => Retry { times: 0, interval_ms: ms, timeout_ms: 0 },
+    }
+}
+
+/// Named targets go to the registry and are referenced; unnamed ones are
+/// inlined.
+fn target_selector(
+    step: &SkillStep,
+    selectors: &mut HashMap<String, Selector>,
+) -> Result<Option<SelectorOrRef>, ConvertError> {
+    let target = &step.target;
+    let decoded = selector_from_locators(&target.locators);
+    if target.name.is_empty() {
+        return Ok(decoded.map(|s| SelectorOrRef::Inline(Box::new(s))));
+    }
+
+    match (decoded, selectors.get(&target.name)) {
+        (Some(selector), Some(existing)) => {
+            if serde_json::to_value(&selector).ok() != serde_json::to_value(existing).ok() {
+                return Err(ConvertError::ConflictingTarget { name: target.name.clone() });
+            }
+        }
+        (Some(selector), None) => {
+            selectors.insert(target.name.clone(), selector);
+        }
+        (None, Some(_)) => {}
+        (None, None) => return Ok(None),
+    }
+    Ok(Some(SelectorOrRef::Ref(SelectorRef { reference: selector_ref(&target.name) })))
+}
+
+enum Decoded {
+    Candidate(SelectorOrRef),
+    Scope(Scope),
+}
+
+fn decode_locator(locator: &Locator) -> Option<Decoded> {
+    match (&locator.method, &locator.value) {
+        (_, Value::Object(o)) if o.contains_key("strategy") || o.contains_key("$ref") => {
+            serde_json::from_value(locator.value.clone()).ok().map(Decoded::Candidate)
+        }
+        (LocatorMethod::Position, Value::Object(o)) if o.contains_key("type") => {
+            serde_json::from_value(locator.value.clone()).ok().map(Decoded::Scope)
+        }
+        (LocatorMethod::Text, Value::String(text)) => Some(ocr(text)),
+        (LocatorMethod::Text, Value::Object(o)) => o.get("text").and_then(Value::as_str).map(ocr),
+        (LocatorMethod::Visual, Value::Object(o)) => o.get("template_image").and_then(Value::as_str).map(|path| {
+            Decoded::Candidate(SelectorOrRef::Inline(Box::new(Selector::Template(TemplateSelector {
+                template: path.to_string(),
+                match_options: None,
+                scope: None,
+            }))))
+        }),
+        _ => None,
+    }
+}
+
+fn ocr(text: &str) -> Decoded {
+    Decoded::Candidate(SelectorOrRef::Inline(Box::new(Selector::OCR(OCRSelector {
+        text: text.to_string(),
+        match_options: None,
+        scope: None,
+    }))))
+}
+
+/// Inverse of [`locators`]. Locators with nothing to match on (accessibility
+/// names, free-form position descriptions) are skipped.
+fn selector_from_locators(locators: &[Locator]) -> Option<Selector> {
+    let mut sorted: Vec<&Locator> = locators.iter().collect();
+    sorted.sort_by_key(|l| l.priority);
+
+    let mut candidates: Vec<(u32, SelectorOrRef)> = Vec::new();
+    let mut scope = None;
+    for locator in sorted {
+        match decode_locator(locator) {
+            Some(Decoded::Candidate(c)) => candidates.push((locator.priority, c)),
+            Some(Decoded::Scope(s)) if scope.is_none() => scope = Some(s),
+            _ => {}
+        }
+    }
+
+    if let [(_, SelectorOrRef::Inline(single))] = candidates.as_slice() {
+        let mut selector = single.as_ref().clone();
+        if scope.is_some() {
+            *scope_slot(&mut selector) = scope;
+        }
+        return Some(selector);
+    }
+    let first = candidates.first()?.0;
+    let first_match = candidates.iter().any(|(p, _)| *p != first);
+    Some(Selector::Multi(MultiSelector {
+        candidates: candidates.into_iter().map(|(_, c)| c).collect(),
+        pick: first_match.then(|| PickPolicy { policy: "firstMatch".to_string() }),
+        scope,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = r##"{
+        "version": "0.1",
+        "package": {
+            "name": "Export PNG",
+            "createdAt": "2026-01-01T00:00:00Z",
+            "description": "Export the document",
+            "author": "qa",
+            "tags": ["export"]
+        },
+        "app": { "name": "Photoshop", "minVersion": "2023" },
+        "env": { "os": ["Windows"], "localeHint": "zh-CN" },
+        "vars": { "FILE_NAME": { "type": "string", "default": "out" } },
+        "selectors": {
+            "menu_file": {
+                "strategy": "ocr",
+                "text": "文件",
+                "match": { "mode": "equals", "lang": "chi_sim", "caseSensitive": false },
+                "scope": { "type": "band", "edge": "top", "ratio": 0.18 }
+            },
+            "export_item": {
+                "strategy": "multi",
+                "candidates": [
+                    { "strategy": "ocr", "text": "导出" },
+                    { "strategy": "template", "template": "export.png" }
+                ],
+                "pick": { "policy": "firstMatch" }
+            },
+            "layer_panel": { "strategy": "ocr", "text": "图层" },
+            "unused": { "strategy": "template", "template": "spare.png" }
+        },
+        "steps": [
+            {
+                "id": "s1",
+                "op": "click",
+                "target": { "$ref": "#/selectors/menu_file" },
+                "retry": { "times": 2, "intervalMs": 400, "timeoutMs": 3000 },
+                "on_fail": { "action": "abort", "reason": "menu_file not found" }
+            },
+            {
+                "id": "open-export",
+                "name": "Open the export menu",
+                "op": "wait",
+                "until": { "$ref": "#/selectors/export_item" },
+                "params": { "mode": "appear" },
+                "retry": { "times": 6, "intervalMs": 250, "timeoutMs": 2500 }
+            },
+            {
+                "id": "s3",
+                "op": "click",
+                "target": {
+                    "strategy": "template",
+                    "template": "save.png",
+                    "match": { "threshold": 0.9 },
+                    "scope": { "type": "rect", "x": 0.5, "y": 0.5, "w": 0.5, "h": 0.5 }
+                },
+                "params": { "button": "left", "clickCount": 2 }
+            },
+            { "id": "s4", "op": "type", "text": "{{FILE_NAME}}", "params": { "clearFirst": true } },
+            {
+                "id": "s5",
+                "op": "drag",
+                "from": {
+                    "strategy": "relative",
+                    "anchor": { "$ref": "#/selectors/layer_panel" },
+                    "relation": { "type": "below", "maxDistancePx": 200 },
+                    "target": { "strategy": "ocr", "text": "背景" }
+                },
+                "to": { "$ref": "#/selectors/layer_panel" },
+                "vector": { "direction": "down", "distancePx": 120 }
+            },
+            { "id": "s6", "op": "scroll", "delta": { "direction": "down", "amount": 3 } },
+            { "id": "s7", "op": "hotkey", "keys": ["ctrl", "s"] },
+            { "id": "s8", "op": "assert", "expect": { "$ref": "#/selectors/menu_file" }, "params": { "negate": true } }
+        ]
+    }"##;
+
+    fn package() -> Package {
+        serde_json::from_str(PACKAGE).unwrap()
+    }
+
+    #[test]
+    fn test_package_round_trips_through_skill() {
+        let original = package();
+        let skill = Skill::from(&original);
+        let restored = Package::try_from(&skill).unwrap();
+        assert_eq!(serde_json::to_value(&restored).unwrap(), serde_json::to_value(&original).unwrap());
+
+        // And through the skill's JSON form, as stored or sent to clients
+        let reparsed: Skill = serde_json::from_value(serde_json::to_value(&skill).unwrap()).unwrap();
+        let restored = Package::try_from(reparsed).unwrap();
+        assert_eq!(serde_json::to_value(&restored).unwrap(), serde_json::to_value(&original).unwrap());
+    }
+
+    #[test]
+    fn test_skill_view_of_a_package() {
+        let skill = Skill::from(package());
+        assert_eq!(skill.skill_id, Skill::from(package()).skill_id);
+        assert_eq!(skill.version, ">=2023");
+        assert_eq!(skill.total_steps, 8);
+        let actions: Vec<&ActionType> = skill.steps.iter().map(|s| &s.action_type).collect();
+        assert_eq!(
+            actions,
+            [
+                &ActionType::Click,
+                &ActionType::Wait,
+                &ActionType::Click,
+                &ActionType::Input,
+                &ActionType::Drag,
+                &ActionType::Scroll,
+                &ActionType::Shortcut,
+                &ActionType::Assert
+            ]
+        );
+
+        // OCR selector with its band scope as a trailing position locator
+        let click = &skill.steps[0];
+        assert_eq!(click.target.name, "menu_file");
+        assert_eq!(click.target.locators[0].method, LocatorMethod::Text);
+        assert_eq!(click.target.locators[0].value["text"], "文件");
+        assert_eq!(click.target.locators[1].method, LocatorMethod::Position);
+        assert_eq!(click.target.locators[1].value["edge"], "top");
+        assert_eq!(click.instruction, "Click menu_file");
+        assert_eq!(click.wait_after, 0.4);
+        assert_eq!(click.parameters["on_fail"]["action"], "abort");
+
+        // firstMatch candidates in priority order
+        let wait = &skill.steps[1];
+        let priorities: Vec<u32> = wait.target.locators.iter().map(|l| l.priority).collect();
+        assert_eq!(priorities, [1, 2]);
+        assert_eq!(wait.target.locators[1].method, LocatorMethod::Visual);
+        assert_eq!(wait.instruction, "Open the export menu");
+        assert_eq!(wait.wait_after, 2.5);
+        assert_eq!(wait.parameters["id"], "open-export");
+
+        // Inline template with a rect scope
+        let save = &skill.steps[2];
+        assert_eq!(save.target.name, "");
+        assert_eq!(save.target.target_type, TargetType::Icon);
+        assert_eq!(save.target.locators[0].value["template"], "save.png");
+        assert_eq!(save.target.locators[1].value["type"], "rect");
+        assert_eq!(save.parameters["params"]["clickCount"], 2);
+
+        assert_eq!(skill.steps[3].parameters["text"], "{{FILE_NAME}}");
+        assert_eq!(skill.steps[4].parameters["vector"]["distancePx"], 120);
+        assert_eq!(skill.steps[6].parameters["keys"], json!(["ctrl", "s"]));
+        assert_eq!(skill.steps[6].instruction, "Press ctrl+s");
+
+        let extras = skill.package.as_ref().unwrap();
+        let mut leftover: Vec<&String> = extras.selectors.keys().collect();
+        leftover.sort();
+        assert_eq!(leftover, ["layer_panel", "unused"]);
+    }
+
+    #[test]
+    fn test_manual_skill_converts_to_a_valid_package() {
+        let skill: Skill = serde_json::from_value(json!({
+            "skill_id": "manual-1",
+            "name": "Save as",
+            "software": "Notepad",
+            "version": "any",
+            "description": "",
+            "steps": [
+                {
+                    "step_id": 1,
+                    "action_type": "menu",
+                    "target": {
+                        "type": "menu_item",
+                        "name": "File",
+                        "locators": [
+                            { "method": "accessibility", "value": "File", "priority": 1 },
+                            { "method": "text", "value": "File", "priority": 2 },
+                            { "method": "visual", "value": { "icon_description": "floppy", "template_image": "file.png" }, "priority": 3 },
+                            { "method": "position", "value": { "description": "top left", "region": "menu_bar" }, "priority": 4 }
+                        ]
+                    },
+                    "instruction": "Open the File menu",
+                    "wait_after": 0.5,
+                    "parameters": {},
+                    "confidence": 0.9
+                },
+                {
+                    "step_id": 2,
+                    "action_type": "input",
+                    "target": { "type": "input_field", "name": "", "locators": [] },
+                    "instruction": "Enter the name",
+                    "wait_after": 0.0,
+                    "parameters": { "text": "notes.txt" },
+                    "confidence": 0.9
+                },
+                {
+                    "step_id": 3,
+                    "action_type": "shortcut",
+                    "target": { "type": "button", "name": "", "locators": [] },
+                    "instruction": "",
+                    "wait_after": 0.0,
+                    "parameters": { "keys": ["enter"] },
+                    "confidence": 0.9
+                }
+            ],
+            "total_steps": 3,
+            "estimated_duration": 6,
+            "tags": [],
+            "created_at": null,
+            "source_type": "manual"
+        }))
+        .unwrap();
+
+        let package = Package::try_from(&skill).unwrap();
+        assert!(package.validate().iter().all(|d| !d.is_error()), "{:?}", package.validate());
+        assert_eq!(package.steps[0].name.as_deref(), Some("Open the File menu"));
+        assert_eq!(package.steps[0].retry.as_ref().map(|r| r.interval_ms), Some(500));
+        assert_eq!(package.steps[2].id, "s3");
+        match &package.selectors["File"] {
+            Selector::Multi(m) => {
+                assert_eq!(m.candidates.len(), 2);
+                assert_eq!(m.pick.as_ref().map(|p| p.policy.as_str()), Some("firstMatch"));
+            }
+            other => panic!("expected text then visual candidates, got {:?}", other),
+        }
+
+        // Once in canonical form, further conversions change nothing
+        let again = Package::try_from(&Skill::from(&package)).unwrap();
+        assert_eq!(serde_json::to_value(&again).unwrap(), serde_json::to_value(&package).unwrap());
+        assert_eq!(Skill::from(&package).steps[0].wait_after, 0.5);
+    }
+
+    #[test]
+    fn test_unconvertible_skills_are_rejected() {
+        let mut skill = Skill::from(package());
+        skill.steps[0].target.locators.retain(|l| l.method == LocatorMethod::Accessibility);
+        assert_eq!(Package::try_from(&skill).unwrap_err(), ConvertError::NoSelector { step_id: 1 });
+
+        let mut skill = Skill::from(package());
+        skill.steps[7].target.locators[0].value["text"] = json!("Datei");
+        assert_eq!(
+            Package::try_from(&skill).unwrap_err(),
+            ConvertError::ConflictingTarget { name: "menu_file".to_string() }
+        );
+
+        let mut skill = Skill::from(package());
+        skill.steps[6].parameters = json!({});
+        assert!(matches!(Package::try_from(&skill), Err(ConvertError::InvalidStep { step_id: 7, .. })));
+    }
+}
@@ -0,0 +1,275 @@
+//! Structural diff between two packages.
+//!
+//! Steps are matched by `id` rather than by position, so inserting a step
+//! shows up as one added step instead of every following step changing.
+//! Selectors and vars are compared by key; everything else field by field.
+//! The result is an RFC 6902 JSON Patch that turns the old package document
+//! into the new one, plus one summary line per change for people.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::domain::package::Package;
+
+/// One RFC 6902 operation; `test` and `copy` are never produced.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PackageDiff {
+    pub patch: Vec<PatchOperation>,
+    pub summary: Vec<String>,
+}
+
+impl PackageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.patch.is_empty()
+    }
+}
+
+/// `sha256:<hex>` over the package document with object keys sorted, so the
+/// hash only changes when the content does.
+pub fn content_hash(package: &Package) -> String {
+    let document = serde_json::to_value(package).unwrap_or(Value::Null);
+    let mut canonical = String::new();
+    write_canonical(&document, &mut canonical);
+    let digest = Sha256::digest(canonical.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+pub fn diff_packages(old: &Package, new: &Package) -> PackageDiff {
+    let old = serde_json::to_value(old).unwrap_or(Value::Null);
+    let new = serde_json::to_value(new).unwrap_or(Value::Null);
+    let mut diff = PackageDiff::default();
+    let empty = Map::new();
+    let old_fields = old.as_object().unwrap_or(&empty);
+    let new_fields = new.as_object().unwrap_or(&empty);
+
+    for key in union_keys(old_fields, new_fields) {
+        let path = format!("/{}", escape(&key));
+        match (old_fields.get(&key), new_fields.get(&key)) {
+            (Some(Value::Array(a)), Some(Value::Array(b))) if key == "steps" => diff_steps(a, b, &mut diff),
+            (Some(Value::Object(a)), Some(Value::Object(b))) if key == "selectors" || key == "vars" => {
+                let noun = if key == "selectors" { "selector" } else { "var" };
+                diff_keyed(noun, &path, a, b, &mut diff)
+            }
+            (old_value, new_value) => {
+                let before = diff.patch.len();
+                diff_value(&path, old_value, new_value, &mut diff.patch);
+                if diff.patch.len() > before {
+                    diff.summary.push(format!("Changed {}", key));
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Selectors and vars: one summary line per entry.
+fn diff_keyed(noun: &str, path: &str, old: &Map<String, Value>, new: &Map<String, Value>, diff: &mut PackageDiff) {
+    for key in union_keys(old, new) {
+        let entry_path = format!("{}/{}", path, escape(&key));
+        let (a, b) = (old.get(&key), new.get(&key));
+        let before = diff.patch.len();
+        diff_value(&entry_path, a, b, &mut diff.patch);
+        if diff.patch.len() == before {
+            continue;
+        }
+        let verb = match (a, b) {
+            (None, _) => "Added",
+            (_, None) => "Removed",
+            _ => "Changed",
+        };
+        diff.summary.push(format!("{} {} {}", verb, noun, key));
+    }
+}
+
+fn diff_steps(old: &[Value], new: &[Value], diff: &mut PackageDiff) {
+    let id = |step: &Value| step.get("id").and_then(Value::as_str).unwrap_or_default().to_string();
+    let label = |step: &Value| {
+        let op = step.get("op").and_then(Value::as_str).unwrap_or("?");
+        format!("{} ({})", id(step), op)
+    };
+    let new_ids: Vec<String> = new.iter().map(id).collect();
+    let old_ids: Vec<String> = old.iter().map(id).collect();
+
+    // Removals from the back, so the indices of earlier steps stay valid.
+    for (index, step) in old.iter().enumerate().rev() {
+        if !new_ids.contains(&id(step)) {
+            diff.patch.push(PatchOperation::Remove { path: format!("/steps/{}", index) });
+        }
+    }
+    for step in old.iter().filter(|s| !new_ids.contains(&id(s))) {
+        diff.summary.push(format!("Removed step {}", label(step)));
+    }
+
+    // Walk the new order; `working` mirrors the array the patch has built so far.
+    let mut working: Vec<&Value> = old.iter().filter(|s| new_ids.contains(&id(s))).collect();
+    for (index, step) in new.iter().enumerate() {
+        let step_id = id(step);
+        let Some(current) = working.iter().position(|s| id(s) == step_id) else {
+            diff.patch.push(PatchOperation::Add { path: format!("/steps/{}", index), value: step.clone() });
+            diff.summary.push(format!("Added step {} at position {}", label(step), index + 1));
+            working.insert(index, step);
+            continue;
+        };
+        if current != index {
+            let moved = working.remove(current);
+            working.insert(index, moved);
+            diff.patch.push(PatchOperation::Move { from: format!("/steps/{}", current), path: format!("/steps/{}", index) });
+            let old_position = old_ids.iter().position(|i| *i == step_id).unwrap_or(current);
+            diff.summary.push(format!("Moved step {} from position {} to {}", step_id, old_position + 1, index + 1));
+        }
+
+        let before = diff.patch.len();
+        diff_value(&format!("/steps/{}", index), Some(working[index]), Some(step), &mut diff.patch);
+        if diff.patch.len() > before {
+            let fields: Vec<String> = changed_fields(working[index], step);
+            diff.summary.push(format!("Changed step {}: {}", step_id, fields.join(", ")));
+        }
+        working[index] = step;
+    }
+}
+
+fn changed_fields(old: &Value, new: &Value) -> Vec<String> {
+    match (old.as_object(), new.as_object()) {
+        (Some(a), Some(b)) => union_keys(a, b).into_iter().filter(|k| a.get(k) != b.get(k)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Objects recurse key by key; anything else is replaced whole.
+fn diff_value(path: &str, old: Option<&Value>, new: Option<&Value>, patch: &mut Vec<PatchOperation>) {
+    match (old, new) {
+        (None, None) => {}
+        (None, Some(value)) => patch.push(PatchOperation::Add { path: path.to_string(), value: value.clone() }),
+        (Some(_), None) => patch.push(PatchOperation::Remove { path: path.to_string() }),
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            for key in union_keys(a, b) {
+                diff_value(&format!("{}/{}", path, escape(&key)), a.get(&key), b.get(&key), patch);
+            }
+        }
+        (Some(a), Some(b)) if a != b => patch.push(PatchOperation::Replace { path: path.to_string(), value: b.clone() }),
+        _ => {}
+    }
+}
+
+fn union_keys(a: &Map<String, Value>, b: &Map<String, Value>) -> Vec<String> {
+    let mut keys: Vec<String> = a.keys().cloned().collect();
+    keys.extend(b.keys().filter(|k| !a.contains_key(*k)).cloned());
+    keys
+}
+
+/// JSON Pointer escaping (RFC 6901).
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use serde_json::json;
+
+    fn package(steps: Value, selectors: Value) -> Package {
+        fixtures::package()
+            .vars(json!({ "FILE_NAME": { "type": "string", "default": "out" } }))
+            .selectors(selectors)
+            .steps(steps)
+            .build()
+    }
+
+    #[test]
+    fn test_diff_matches_steps_by_id_and_patch_applies() {
+        let old = package(
+            json!([
+                { "id": "s1", "op": "hotkey", "keys": ["ctrl", "o"] },
+                { "id": "s2", "op": "click", "target": { "$ref": "#/selectors/menu_file" } },
+                { "id": "s3", "op": "hotkey", "keys": ["enter"] },
+                { "id": "s4", "op": "hotkey", "keys": ["ctrl", "s"] }
+            ]),
+            json!({ "menu_file": { "strategy": "ocr", "text": "File" }, "old/key": { "strategy": "ocr", "text": "x" } }),
+        );
+        let mut new = package(
+            json!([
+                { "id": "s4", "op": "hotkey", "keys": ["ctrl", "shift", "s"] },
+                { "id": "s1", "op": "hotkey", "keys": ["ctrl", "o"] },
+                { "id": "s5", "op": "hotkey", "keys": ["tab"] },
+                { "id": "s2", "op": "click", "target": { "$ref": "#/selectors/menu_file" } }
+            ]),
+            json!({ "menu_file": { "strategy": "ocr", "text": "文件" } }),
+        );
+        new.package.name = "Export PNG v2".to_string();
+
+        let diff = diff_packages(&old, &new);
+        let mut document = serde_json::to_value(&old).unwrap();
+        let patch: json_patch::Patch = serde_json::from_value(serde_json::to_value(&diff.patch).unwrap()).unwrap();
+        json_patch::patch(&mut document, &patch).unwrap();
+        assert_eq!(document, serde_json::to_value(&new).unwrap());
+
+        assert_eq!(
+            diff.summary,
+            [
+                "Changed package",
+                "Changed selector menu_file",
+                "Removed selector old/key",
+                "Removed step s3 (hotkey)",
+                "Moved step s4 from position 4 to 1",
+                "Changed step s4: keys",
+                "Added step s5 (hotkey) at position 3",
+            ]
+        );
+        assert!(diff.patch.contains(&PatchOperation::Remove { path: "/selectors/old~1key".to_string() }));
+        assert!(diff_packages(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_content_hash_ignores_key_order_only() {
+        let a = package(json!([{ "id": "s1", "op": "hotkey", "keys": ["f5"] }]), json!({ "a": { "strategy": "ocr", "text": "A" } }));
+        let b: Package = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert_eq!(content_hash(&a), content_hash(&b));
+        assert!(content_hash(&a).starts_with("sha256:"));
+
+        let mut c = a.clone();
+        c.steps[0].id = "s2".to_string();
+        assert_ne!(content_hash(&a), content_hash(&c));
+    }
+}
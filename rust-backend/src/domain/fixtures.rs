@@ -0,0 +1,82 @@
+//! Test packages. [`package`] starts from a minimal valid package (one OCR
+//! selector and a click on it); tests override only the parts they look at.
+
+use serde_json::{Value, json};
+
+use crate::domain::package::Package;
+
+pub(crate) struct PackageBuilder {
+    document: Value,
+}
+
+pub(crate) fn package() -> PackageBuilder {
+    PackageBuilder {
+        document: json!({
+            "version": "0.1",
+            "package": { "name": "Export PNG", "createdAt": "2026-01-01T00:00:00Z" },
+            "app": { "name": "Photoshop" },
+            "selectors": { "menu_file": { "strategy": "ocr", "text": "File" } },
+            "steps": [{ "id": "s1", "op": "click", "target": { "$ref": "#/selectors/menu_file" } }]
+        }),
+    }
+}
+
+impl PackageBuilder {
+    pub(crate) fn name(mut self, name: &str) -> Self {
+        self.document["package"]["name"] = json!(name);
+        self
+    }
+
+    pub(crate) fn description(mut self, description: &str) -> Self {
+        self.document["package"]["description"] = json!(description);
+        self
+    }
+
+    pub(crate) fn tags(mut self, tags: &[&str]) -> Self {
+        self.document["package"]["tags"] = json!(tags);
+        self
+    }
+
+    pub(crate) fn app(mut self, name: &str) -> Self {
+        self.document["app"]["name"] = json!(name);
+        self
+    }
+
+    pub(crate) fn app_versions(mut self, min: Option<&str>, max: Option<&str>) -> Self {
+        for (field, version) in [("minVersion", min), ("maxVersion", max)] {
+            if let Some(version) = version {
+                self.document["app"][field] = json!(version);
+            }
+        }
+        self
+    }
+
+    pub(crate) fn vars(mut self, vars: Value) -> Self {
+        self.document["vars"] = vars;
+        self
+    }
+
+    /// Replaces all selectors, including the default `menu_file`.
+    pub(crate) fn selectors(mut self, selectors: Value) -> Self {
+        self.document["selectors"] = selectors;
+        self
+    }
+
+    pub(crate) fn selector(mut self, key: &str, selector: Value) -> Self {
+        self.document["selectors"][key] = selector;
+        self
+    }
+
+    pub(crate) fn steps(mut self, steps: Value) -> Self {
+        self.document["steps"] = steps;
+        self
+    }
+
+    pub(crate) fn json(self) -> Value {
+        self.document
+    }
+
+    pub(crate) fn build(self) -> Package {
+        serde_json::from_value(self.document).expect("fixture should parse")
+    }
+}
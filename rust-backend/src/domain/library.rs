@@ -0,0 +1,207 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+use crate::domain::diff::content_hash;
+use crate::domain::package::Package;
+use crate::domain::skill::{Skill, SourceType};
+
+/// A skill in the library: the validated package and its Skill view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillEntry {
+    pub id: String,
+    pub package: Package,
+    /// As submitted for manually authored skills, otherwise derived from the
+    /// package. `skill_id` always equals `id`.
+    pub skill: Skill,
+    /// Task the package was published from.
+    #[serde(rename = "sourceTask", default, skip_serializing_if = "Option::is_none")]
+    pub source_task: Option<String>,
+    /// Number of the latest revision, starting at 1.
+    pub revision: u32,
+    /// Content hash of `package`, see [`content_hash`].
+    pub hash: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An immutable snapshot written on every save of a library entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillRevision {
+    #[serde(rename = "skillId")]
+    pub skill_id: String,
+    pub number: u32,
+    pub hash: String,
+    pub package: Package,
+    pub skill: Skill,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl SkillEntry {
+    pub fn new(id: String, package: Package, skill: Skill, source_task: Option<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            skill: Skill { skill_id: id.clone(), ..skill },
+            hash: content_hash(&package),
+            id,
+            package,
+            source_task,
+            revision: 1,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Derives the Skill view from the package.
+    pub fn from_package(id: String, package: Package, source_type: SourceType, source_task: Option<String>) -> Self {
+        let skill = Skill { source_type, ..Skill::from(&package) };
+        Self::new(id, package, skill, source_task)
+    }
+
+    /// Replaces the content and starts the next revision.
+    pub fn revise(&mut self, package: Package, skill: Skill) {
+        self.hash = content_hash(&package);
+        self.package = package;
+        self.skill = Skill { skill_id: self.id.clone(), ..skill };
+        self.revision += 1;
+        self.updated_at = Utc::now();
+    }
+
+    /// The snapshot of the current content.
+    pub fn current_revision(&self) -> SkillRevision {
+        SkillRevision {
+            skill_id: self.id.clone(),
+            number: self.revision,
+            hash: self.hash.clone(),
+            package: self.package.clone(),
+            skill: self.skill.clone(),
+            created_at: self.updated_at,
+        }
+    }
+
+    /// Relevance for `query`, or `None` if the entry is filtered out. Every
+    /// search term has to appear in the name, a tag, the app name or the
+    /// description; name hits weigh most.
+    pub fn score(&self, query: &SkillQuery) -> Option<u32> {
+        let app = &self.package.app;
+        if let Some(wanted) = &query.app
+            && !app.name.eq_ignore_ascii_case(wanted.trim())
+        {
+            return None;
+        }
+        if let Some(version) = &query.version {
+            let too_old = app.min_version.as_deref().is_some_and(|min| compare_versions(version, min).is_lt());
+            let too_new = app.max_version.as_deref().is_some_and(|max| compare_versions(version, max).is_gt());
+            if too_old || too_new {
+                return None;
+            }
+        }
+
+        let meta = &self.package.package;
+        let name = meta.name.to_lowercase();
+        let description = meta.description.as_deref().unwrap_or_default().to_lowercase();
+        let app_name = app.name.to_lowercase();
+        let tags: Vec<String> = meta.tags.iter().map(|t| t.to_lowercase()).collect();
+
+        let mut score = 0;
+        for term in query.terms() {
+            let hits = [
+                (name.contains(&term), 3),
+                (tags.iter().any(|t| t.contains(&term)), 2),
+                (app_name.contains(&term), 2),
+                (description.contains(&term), 1),
+            ];
+            let term_score: u32 = hits.iter().filter(|(hit, _)| *hit).map(|(_, weight)| weight).sum();
+            if term_score == 0 {
+                return None;
+            }
+            score += term_score;
+        }
+        Some(score)
+    }
+}
+
+/// Library search: free text plus app and app-version filters, all optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SkillQuery {
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub app: Option<String>,
+    /// Only skills whose `minVersion..=maxVersion` contains this version.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl SkillQuery {
+    fn terms(&self) -> Vec<String> {
+        self.q.as_deref().unwrap_or_default().split_whitespace().map(str::to_lowercase).collect()
+    }
+}
+
+/// Orders app versions like `2023`, `24.1.3` or `CC 2019`: numeric parts
+/// compare as numbers, others case-insensitively, missing parts as zero.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<String> {
+        v.split(|c: char| !c.is_alphanumeric()).filter(|p| !p.is_empty()).map(str::to_lowercase).collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).map(String::as_str).unwrap_or("0");
+        let y = b.get(i).map(String::as_str).unwrap_or("0");
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+
+    fn entry(name: &str, app: &str, min: Option<&str>, max: Option<&str>, tags: &[&str]) -> SkillEntry {
+        let package =
+            fixtures::package().name(name).description("Batch export").tags(tags).app(app).app_versions(min, max).build();
+        SkillEntry::from_package("e1".to_string(), package, SourceType::Manual, None)
+    }
+
+    #[test]
+    fn test_search_terms_and_filters() {
+        let e = entry("证件照换底色", "Photoshop", Some("2023"), Some("2025.1"), &["photo", "背景"]);
+        assert_eq!(e.skill.skill_id, "e1");
+        let query = |q: &str| SkillQuery { q: Some(q.to_string()), ..Default::default() };
+
+        assert_eq!(e.score(&SkillQuery::default()), Some(0));
+        assert_eq!(e.score(&query("换底色")), Some(3));
+        assert_eq!(e.score(&query("PHOTO")), Some(4)); // tag and app name
+        assert_eq!(e.score(&query("photo export")), Some(5));
+        assert_eq!(e.score(&query("photo gimp")), None);
+
+        let filtered = |app: &str, version: &str| SkillQuery {
+            app: Some(app.to_string()),
+            version: Some(version.to_string()),
+            ..Default::default()
+        };
+        assert!(e.score(&filtered("photoshop", "2024")).is_some());
+        assert!(e.score(&filtered("photoshop", "2025.1.0")).is_some());
+        assert!(e.score(&filtered("photoshop", "2025.2")).is_none());
+        assert!(e.score(&filtered("photoshop", "2022")).is_none());
+        assert!(e.score(&filtered("GIMP", "2024")).is_none());
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("10.0", "9.9"), Ordering::Greater);
+        assert_eq!(compare_versions("2023", "2023.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("CC 2019", "cc 2020"), Ordering::Less);
+    }
+}
@@ -1,3 +1,15 @@
 pub mod task;
 pub mod skill;
 pub mod package;
+pub mod validate;
+pub mod resolve;
+pub mod vars;
+pub mod transcript;
+pub mod schema;
+pub mod convert;
+pub mod library;
+pub mod diff;
+pub mod bundle;
+pub mod pipeline_error;
+#[cfg(test)]
+pub(crate) mod fixtures;
@@ -6,7 +6,7 @@ pub struct Package {
     pub version: String,
     pub package: PackageMeta,
     pub app: AppSpec,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub env: Option<EnvSpec>,
     #[serde(default)]
     pub vars: HashMap<String, VarDef>,
@@ -19,9 +19,9 @@ pub struct PackageMeta {
     pub name: String,
     #[serde(rename = "createdAt")]
     pub created_at: String, // ISO 8601 string
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub author: Option<String>,
     #[serde(default)]
     pub tags: Vec<String>,
@@ -30,9 +30,9 @@ pub struct PackageMeta {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSpec {
     pub name: String,
-    #[serde(rename = "minVersion", default)]
+    #[serde(rename = "minVersion", default, skip_serializing_if = "Option::is_none")]
     pub min_version: Option<String>,
-    #[serde(rename = "maxVersion", default)]
+    #[serde(rename = "maxVersion", default, skip_serializing_if = "Option::is_none")]
     pub max_version: Option<String>,
 }
 
@@ -40,9 +40,9 @@ pub struct AppSpec {
 pub struct EnvSpec {
     #[serde(default)]
     pub os: Vec<String>,
-    #[serde(rename = "resolutionHint", default)]
+    #[serde(rename = "resolutionHint", default, skip_serializing_if = "Option::is_none")]
     pub resolution_hint: Option<String>,
-    #[serde(rename = "localeHint", default)]
+    #[serde(rename = "localeHint", default, skip_serializing_if = "Option::is_none")]
     pub locale_hint: Option<String>,
 }
 
@@ -50,9 +50,9 @@ pub struct EnvSpec {
 pub struct VarDef {
     #[serde(rename = "type")]
     pub var_type: String, // "string", "number", "boolean", "path"
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub default: Option<serde_json::Value>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 }
 
@@ -73,6 +73,7 @@ pub struct SelectorRef {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "strategy")]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Selector {
     #[serde(rename = "ocr")]
     OCR(OCRSelector),
@@ -87,9 +88,9 @@ pub enum Selector {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OCRSelector {
     pub text: String,
-    #[serde(default)]
+    #[serde(rename = "match", default, skip_serializing_if = "Option::is_none")]
     pub match_options: Option<OCRMatch>, // 'match' is a keyword in Rust
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scope: Option<Scope>,
 }
 
@@ -106,7 +107,7 @@ pub struct OCRMatch {
     pub lang: String,
     #[serde(rename = "caseSensitive", default)]
     pub case_sensitive: bool,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub regex: Option<String>,
 }
 
@@ -116,9 +117,9 @@ fn default_lang() -> String { "chi_sim".to_string() }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemplateSelector {
     pub template: String,
-    #[serde(rename = "match", default)]
+    #[serde(rename = "match", default, skip_serializing_if = "Option::is_none")]
     pub match_options: Option<TemplateMatch>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scope: Option<Scope>,
 }
 
@@ -135,7 +136,7 @@ pub struct RelativeSelector {
     pub anchor: SelectorOrRef,
     pub relation: Relation,
     pub target: SelectorOrRef,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scope: Option<Scope>,
 }
 
@@ -152,9 +153,9 @@ fn default_max_distance() -> u32 { 400 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultiSelector {
     pub candidates: Vec<SelectorOrRef>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub pick: Option<PickPolicy>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scope: Option<Scope>,
 }
 
@@ -214,7 +215,7 @@ pub struct ScopeBand {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScopeWindow {
     pub mode: String, // "active", "main", "byTitle"
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     #[serde(rename = "match", default = "default_match_mode")]
     pub match_mode: String,
@@ -223,7 +224,7 @@ pub struct ScopeWindow {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScopeDialog {
     pub role: String, // "topmost", "modal", "byTitle"
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     #[serde(rename = "match", default = "default_match_mode")]
     pub match_mode: String,
@@ -273,13 +274,13 @@ pub struct ScopeExclude {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Step {
     pub id: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scope: Option<Scope>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub retry: Option<Retry>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub on_fail: Option<OnFail>,
     
     #[serde(flatten)]
@@ -318,9 +319,9 @@ pub struct Retry {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OnFail {
     pub action: String, // "abort", "skip", "fallback_step_id"
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
-    #[serde(rename = "stepId", default)]
+    #[serde(rename = "stepId", default, skip_serializing_if = "Option::is_none")]
     pub step_id: Option<String>,
 }
 
@@ -341,9 +342,9 @@ pub struct FallbackPoint {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepClick {
     pub target: SelectorOrRef,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<ClickParams>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub fallback: Option<Fallback>,
 }
 
@@ -353,7 +354,7 @@ pub struct ClickParams {
     pub button: String,
     #[serde(rename = "clickCount", default = "default_click_count")]
     pub click_count: u32,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub offset: Option<Offset>,
 }
 
@@ -369,13 +370,13 @@ pub struct Offset {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepDrag {
     pub from: SelectorOrRef,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub to: Option<SelectorOrRef>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub vector: Option<DragVector>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<DragParams>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub fallback: Option<Fallback>,
 }
 
@@ -397,9 +398,9 @@ fn default_drag_duration() -> u32 { 250 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepType {
     pub text: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target: Option<SelectorOrRef>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<TypeParams>,
 }
 
@@ -414,9 +415,9 @@ pub struct TypeParams {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepScroll {
     pub delta: ScrollDelta,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target: Option<SelectorOrRef>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<ScrollParams>,
 }
 
@@ -442,7 +443,7 @@ pub struct StepHotkey {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepWait {
     pub until: SelectorOrRef,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<WaitParams>,
 }
 
@@ -460,7 +461,7 @@ fn default_wait_confidence() -> f64 { 0.6 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepAssert {
     pub expect: SelectorOrRef,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<AssertParams>,
 }
 
@@ -0,0 +1,217 @@
+//! Why a pipeline run failed, in terms clients can act on.
+//!
+//! Every failure has a stable machine code, the stage it happened in and
+//! whether submitting the parse again may help. Messages describe the problem
+//! without echoing upstream response bodies, which go to the log instead.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::domain::schema::SchemaViolation;
+
+/// Pipeline stages, each calling out to one provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Download,
+    Transcribe,
+    VideoAnalysis,
+    SkillFormat,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Download => "download",
+            Stage::Transcribe => "transcribe",
+            Stage::VideoAnalysis => "video_analysis",
+            Stage::SkillFormat => "skill_format",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineError {
+    /// The source media could not be fetched; `status` is absent when no
+    /// HTTP response arrived.
+    DownloadFailed { status: Option<u16>, reason: String },
+    /// The media is not in a format the pipeline or its provider accepts.
+    UnsupportedMedia { stage: Stage, reason: String },
+    /// The provider refused the configured credentials.
+    ProviderAuth { stage: Stage, status: u16 },
+    /// The provider kept throttling until the retry budget ran out.
+    RateLimited { stage: Stage, retry_after: Option<Duration> },
+    /// The provider failed, rejected the request or answered with something
+    /// that is not a usable result.
+    BadResponse { stage: Stage, status: Option<u16>, reason: String },
+    /// The formatter's output is not JSON or does not match the package schema.
+    SchemaInvalid { violations: Vec<SchemaViolation> },
+    /// The output matches the schema but fails the semantic package checks.
+    PackageInvalid { violations: Vec<SchemaViolation> },
+    Cancelled { stage: Stage },
+    /// The service shut down while the job was waiting (`started` is false)
+    /// or running.
+    Interrupted { stage: Stage, started: bool },
+}
+
+impl PipelineError {
+    /// Stable identifier clients can switch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PipelineError::DownloadFailed { .. } => "download_failed",
+            PipelineError::UnsupportedMedia { .. } => "unsupported_media",
+            PipelineError::ProviderAuth { .. } => "provider_auth",
+            PipelineError::RateLimited { .. } => "provider_rate_limited",
+            PipelineError::BadResponse { .. } => "provider_bad_response",
+            PipelineError::SchemaInvalid { .. } => "schema_invalid",
+            PipelineError::PackageInvalid { .. } => "package_invalid",
+            PipelineError::Cancelled { .. } => "cancelled",
+            PipelineError::Interrupted { .. } => "interrupted",
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        match self {
+            PipelineError::DownloadFailed { .. } => Stage::Download,
+            PipelineError::UnsupportedMedia { stage, .. }
+            | PipelineError::ProviderAuth { stage, .. }
+            | PipelineError::RateLimited { stage, .. }
+            | PipelineError::BadResponse { stage, .. }
+            | PipelineError::Cancelled { stage }
+            | PipelineError::Interrupted { stage, .. } => *stage,
+            PipelineError::SchemaInvalid { .. } | PipelineError::PackageInvalid { .. } => Stage::SkillFormat,
+        }
+    }
+
+    /// Whether submitting the same parse again may succeed. Client errors
+    /// (4xx) and invalid input will fail the same way every time.
+    pub fn retryable(&self) -> bool {
+        let transient = |status: &Option<u16>| status.is_none_or(|s| s == 408 || s == 429 || s >= 500);
+        match self {
+            PipelineError::DownloadFailed { status, .. } | PipelineError::BadResponse { status, .. } => transient(status),
+            PipelineError::RateLimited { .. } | PipelineError::Interrupted { .. } => true,
+            PipelineError::UnsupportedMedia { .. }
+            | PipelineError::ProviderAuth { .. }
+            | PipelineError::SchemaInvalid { .. }
+            | PipelineError::PackageInvalid { .. }
+            | PipelineError::Cancelled { .. } => false,
+        }
+    }
+
+    /// Schema or semantic problems of a rejected package.
+    pub fn violations(&self) -> &[SchemaViolation] {
+        match self {
+            PipelineError::SchemaInvalid { violations } | PipelineError::PackageInvalid { violations } => violations,
+            _ => &[],
+        }
+    }
+
+    pub fn failure(&self) -> TaskFailure {
+        let (status, retry_after) = match self {
+            PipelineError::DownloadFailed { status, .. } | PipelineError::BadResponse { status, .. } => (*status, None),
+            PipelineError::ProviderAuth { status, .. } => (Some(*status), None),
+            PipelineError::RateLimited { retry_after, .. } => (Some(429), *retry_after),
+            _ => (None, None),
+        };
+        TaskFailure {
+            code: self.code().to_string(),
+            stage: self.stage(),
+            retryable: self.retryable(),
+            message: self.to_string(),
+            status,
+            retry_after_secs: retry_after.map(|d| d.as_secs()),
+        }
+    }
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::DownloadFailed { status: Some(status), .. } => {
+                write!(f, "Could not download the media: the server answered HTTP {}", status)
+            }
+            PipelineError::DownloadFailed { status: None, reason } => write!(f, "Could not download the media: {}", reason),
+            PipelineError::UnsupportedMedia { reason, .. } => write!(f, "Unsupported media: {}", reason),
+            PipelineError::ProviderAuth { stage, status } => {
+                write!(f, "The {} provider rejected the API key (HTTP {}); check its credentials", stage, status)
+            }
+            PipelineError::RateLimited { stage, retry_after: Some(after) } => {
+                write!(f, "The {} provider is rate limiting requests; try again in {}s", stage, after.as_secs())
+            }
+            PipelineError::RateLimited { stage, retry_after: None } => {
+                write!(f, "The {} provider is rate limiting requests; try again later", stage)
+            }
+            PipelineError::BadResponse { stage, status: Some(status), .. } => {
+                write!(f, "The {} provider failed with HTTP {}", stage, status)
+            }
+            PipelineError::BadResponse { stage, status: None, reason } => {
+                write!(f, "The {} provider returned no usable result: {}", stage, reason)
+            }
+            PipelineError::SchemaInvalid { violations } => write!(f, "Generated package does not match the schema: {}", join(violations)),
+            PipelineError::PackageInvalid { violations } => write!(f, "Generated package failed validation: {}", join(violations)),
+            PipelineError::Cancelled { stage } => write!(f, "Cancelled during {}", stage),
+            PipelineError::Interrupted { stage, started: false } => {
+                write!(f, "The service shut down before {} started; submit the parse again", stage)
+            }
+            PipelineError::Interrupted { stage, started: true } => {
+                write!(f, "Interrupted during {} by a service shutdown; submit the parse again", stage)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+fn join(violations: &[SchemaViolation]) -> String {
+    violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; ")
+}
+
+/// A [`PipelineError`] as stored on the task and shown to clients.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskFailure {
+    pub code: String,
+    pub stage: Stage,
+    pub retryable: bool,
+    pub message: String,
+    /// HTTP status of the upstream response, if one caused the failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(rename = "retryAfterSecs", default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_record_carries_code_stage_and_retryable() {
+        let throttled = PipelineError::RateLimited { stage: Stage::VideoAnalysis, retry_after: Some(Duration::from_secs(30)) };
+        let failure = throttled.failure();
+        assert_eq!((failure.code.as_str(), failure.stage, failure.retryable), ("provider_rate_limited", Stage::VideoAnalysis, true));
+        assert_eq!(failure.retry_after_secs, Some(30));
+        assert_eq!(
+            serde_json::to_value(&failure).unwrap()["stage"],
+            serde_json::json!("video_analysis")
+        );
+
+        let auth = PipelineError::ProviderAuth { stage: Stage::Transcribe, status: 401 };
+        assert!(!auth.retryable());
+        assert!(auth.to_string().contains("transcribe provider rejected the API key"));
+
+        let missing = PipelineError::DownloadFailed { status: Some(404), reason: String::new() };
+        let unreachable = PipelineError::DownloadFailed { status: None, reason: "connection refused".to_string() };
+        assert!(!missing.retryable() && unreachable.retryable());
+
+        // Upstream bodies never reach the message
+        let failed = PipelineError::BadResponse { stage: Stage::SkillFormat, status: Some(502), reason: "<html>nginx</html>".to_string() };
+        assert_eq!(failed.to_string(), "The skill_format provider failed with HTTP 502");
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+
+use crate::domain::package::{Package, Scope, Selector, SelectorOrRef, Step, StepOperation};
+use crate::domain::validate::escape_pointer_token;
+
+const SELECTOR_REF_PREFIX: &str = "#/selectors/";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// The reference is not a `#/selectors/<key>` pointer.
+    Malformed { path: String, reference: String },
+    /// The pointer names a selector that is not in the registry.
+    Unresolved { path: String, key: String },
+    /// Expanding the reference leads back to a selector that is still being expanded.
+    Cycle { path: String, chain: Vec<String> },
+}
+
+impl ResolveError {
+    pub fn path(&self) -> &str {
+        match self {
+            ResolveError::Malformed { path, .. }
+            | ResolveError::Unresolved { path, .. }
+            | ResolveError::Cycle { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::Malformed { path, reference } => {
+                write!(f, "{}: '{}' is not a {}<id> reference", path, reference, SELECTOR_REF_PREFIX)
+            }
+            ResolveError::Unresolved { path, key } => write!(f, "{}: selector '{}' is not defined", path, key),
+            ResolveError::Cycle { path, chain } => write!(f, "{}: reference cycle {}", path, chain.join(" -> ")),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Decodes `#/selectors/<key>` into the registry key, undoing JSON pointer
+/// escaping (`~1` is `/`, `~0` is `~`).
+pub fn selector_key(reference: &str) -> Option<String> {
+    let token = reference.strip_prefix(SELECTOR_REF_PREFIX)?;
+    if token.is_empty() || token.contains('/') {
+        return None;
+    }
+    let mut key = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => key.push('~'),
+                Some('1') => key.push('/'),
+                _ => return None,
+            }
+        } else {
+            key.push(c);
+        }
+    }
+    Some(key)
+}
+
+/// Encodes a registry key as a `#/selectors/<key>` reference.
+pub fn selector_ref(key: &str) -> String {
+    format!("{}{}", SELECTOR_REF_PREFIX, escape_pointer_token(key))
+}
+
+/// A package in which every `SelectorOrRef` is `Inline`, in the registry as
+/// well as in steps, relative/multi selectors and `around` scopes.
+#[derive(Debug, Clone)]
+pub struct ResolvedPackage(Package);
+
+impl Deref for ResolvedPackage {
+    type Target = Package;
+
+    fn deref(&self) -> &Package {
+        &self.0
+    }
+}
+
+impl Package {
+    /// Inlines every `$ref`. All unresolved, malformed and cyclic references
+    /// are reported together with the JSON pointer of the `$ref` field.
+    pub fn resolve(&self) -> Result<ResolvedPackage, Vec<ResolveError>> {
+        let mut resolver = Resolver {
+            pkg: self,
+            done: HashMap::new(),
+            stack: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        let mut keys: Vec<&String> = self.selectors.keys().collect();
+        keys.sort();
+        for key in keys {
+            resolver.resolve_key(key);
+        }
+
+        let steps: Vec<Step> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| resolver.inline_step(step, &format!("/steps/{}", i)))
+            .collect();
+
+        if !resolver.errors.is_empty() {
+            return Err(resolver.errors);
+        }
+
+        let mut resolved = self.clone();
+        resolved.selectors = resolver.done;
+        resolved.steps = steps;
+        Ok(ResolvedPackage(resolved))
+    }
+}
+
+struct Resolver<'a> {
+    pkg: &'a Package,
+    /// Registry selectors that have been expanded (possibly with errors left in place).
+    done: HashMap<String, Selector>,
+    /// Registry keys currently being expanded, outermost first.
+    stack: Vec<String>,
+    errors: Vec<ResolveError>,
+}
+
+impl Resolver<'_> {
+    /// Expands a registry entry, memoised. Returns `None` when the key is not defined.
+    fn resolve_key(&mut self, key: &str) -> Option<Selector> {
+        if let Some(selector) = self.done.get(key) {
+            return Some(selector.clone());
+        }
+        let definition = self.pkg.selectors.get(key)?;
+
+        self.stack.push(key.to_string());
+        let path = format!("/selectors/{}", escape_pointer_token(key));
+        let selector = self.inline_selector(definition, &path);
+        self.stack.pop();
+
+        self.done.insert(key.to_string(), selector.clone());
+        Some(selector)
+    }
+
+    fn inline_sor(&mut self, sor: &SelectorOrRef, path: &str) -> SelectorOrRef {
+        let r = match sor {
+            SelectorOrRef::Inline(selector) => {
+                return SelectorOrRef::Inline(Box::new(self.inline_selector(selector, path)));
+            }
+            SelectorOrRef::Ref(r) => r,
+        };
+
+        let ref_path = format!("{}/$ref", path);
+        let Some(key) = selector_key(&r.reference) else {
+            self.errors.push(ResolveError::Malformed { path: ref_path, reference: r.reference.clone() });
+            return sor.clone();
+        };
+        if let Some(start) = self.stack.iter().position(|k| *k == key) {
+            let mut chain = self.stack[start..].to_vec();
+            chain.push(key);
+            self.errors.push(ResolveError::Cycle { path: ref_path, chain });
+            return sor.clone();
+        }
+        match self.resolve_key(&key) {
+            Some(selector) => SelectorOrRef::Inline(Box::new(selector)),
+            None => {
+                self.errors.push(ResolveError::Unresolved { path: ref_path, key });
+                sor.clone()
+            }
+        }
+    }
+
+    fn inline_selector(&mut self, selector: &Selector, path: &str) -> Selector {
+        let mut selector = selector.clone();
+        match &mut selector {
+            Selector::OCR(s) => self.inline_scope_opt(&mut s.scope, path),
+            Selector::Template(s) => self.inline_scope_opt(&mut s.scope, path),
+            Selector::Relative(s) => {
+                s.anchor = self.inline_sor(&s.anchor, &format!("{}/anchor", path));
+                s.target = self.inline_sor(&s.target, &format!("{}/target", path));
+                self.inline_scope_opt(&mut s.scope, path);
+            }
+            Selector::Multi(s) => {
+                for (i, candidate) in s.candidates.iter_mut().enumerate() {
+                    *candidate = self.inline_sor(candidate, &format!("{}/candidates/{}", path, i));
+                }
+                self.inline_scope_opt(&mut s.scope, path);
+            }
+        }
+        selector
+    }
+
+    fn inline_scope_opt(&mut self, scope: &mut Option<Scope>, parent: &str) {
+        if let Some(scope) = scope {
+            self.inline_scope(scope, &format!("{}/scope", parent));
+        }
+    }
+
+    fn inline_scope(&mut self, scope: &mut Scope, path: &str) {
+        match scope {
+            Scope::Around(around) => {
+                around.anchor = self.inline_sor(&around.anchor, &format!("{}/anchor", path));
+            }
+            Scope::Union(u) => {
+                for (i, s) in u.scopes.iter_mut().enumerate() {
+                    self.inline_scope(s, &format!("{}/scopes/{}", path, i));
+                }
+            }
+            Scope::Intersect(x) => {
+                for (i, s) in x.scopes.iter_mut().enumerate() {
+                    self.inline_scope(s, &format!("{}/scopes/{}", path, i));
+                }
+            }
+            Scope::Exclude(e) => {
+                self.inline_scope(&mut e.base, &format!("{}/base", path));
+                self.inline_scope(&mut e.exclude, &format!("{}/exclude", path));
+            }
+            _ => {}
+        }
+    }
+
+    fn inline_step(&mut self, step: &Step, path: &str) -> Step {
+        let mut step = step.clone();
+        self.inline_scope_opt(&mut step.scope, path);
+        match &mut step.op {
+            StepOperation::Click(c) => c.target = self.inline_sor(&c.target, &format!("{}/target", path)),
+            StepOperation::Drag(d) => {
+                d.from = self.inline_sor(&d.from, &format!("{}/from", path));
+                if let Some(to) = &d.to {
+                    d.to = Some(self.inline_sor(to, &format!("{}/to", path)));
+                }
+            }
+            StepOperation::Type(t) => {
+                if let Some(target) = &t.target {
+                    t.target = Some(self.inline_sor(target, &format!("{}/target", path)));
+                }
+            }
+            StepOperation::Scroll(s) => {
+                if let Some(target) = &s.target {
+                    s.target = Some(self.inline_sor(target, &format!("{}/target", path)));
+                }
+            }
+            StepOperation::Hotkey(_) => {}
+            StepOperation::Wait(w) => w.until = self.inline_sor(&w.until, &format!("{}/until", path)),
+            StepOperation::Assert(a) => a.expect = self.inline_sor(&a.expect, &format!("{}/expect", path)),
+        }
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use serde_json::{json, Value};
+
+    fn package(selectors: Value, steps: Value) -> Package {
+        fixtures::package().selectors(selectors).steps(steps).build()
+    }
+
+    fn has_ref(sor: &SelectorOrRef) -> bool {
+        match sor {
+            SelectorOrRef::Ref(_) => true,
+            SelectorOrRef::Inline(s) => match s.as_ref() {
+                Selector::Relative(r) => has_ref(&r.anchor) || has_ref(&r.target),
+                Selector::Multi(m) => m.candidates.iter().any(has_ref),
+                _ => false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_selector_key_escaping() {
+        assert_eq!(selector_key("#/selectors/menu_file").as_deref(), Some("menu_file"));
+        assert_eq!(selector_key("#/selectors/a~1b~0c").as_deref(), Some("a/b~c"));
+        assert_eq!(selector_key("#/selectors/a/b"), None);
+        assert_eq!(selector_key("#/selectors/bad~2"), None);
+        assert_eq!(selector_key("#/steps/s1"), None);
+        assert_eq!(selector_key(&selector_ref("a/b~c")).as_deref(), Some("a/b~c"));
+    }
+
+    #[test]
+    fn test_inlines_nested_refs() {
+        let pkg = package(
+            json!({
+                "title": { "strategy": "ocr", "text": "图层" },
+                "a/b": { "strategy": "template", "template": "assets/templates/x.png" },
+                "layer": {
+                    "strategy": "relative",
+                    "anchor": { "$ref": "#/selectors/title" },
+                    "relation": { "type": "below" },
+                    "target": {
+                        "strategy": "multi",
+                        "candidates": [{ "$ref": "#/selectors/a~1b" }, { "strategy": "ocr", "text": "图层 1" }]
+                    },
+                    "scope": { "type": "around", "anchor": { "$ref": "#/selectors/title" }, "radiusPx": 200 }
+                }
+            }),
+            json!([{ "id": "s1", "op": "click", "target": { "$ref": "#/selectors/layer" } }]),
+        );
+
+        let resolved = pkg.resolve().expect("should resolve");
+        let StepOperation::Click(click) = &resolved.steps[0].op else { panic!("expected click") };
+        assert!(!has_ref(&click.target));
+        let SelectorOrRef::Inline(selector) = &click.target else { panic!("expected inline") };
+        let Selector::Relative(relative) = selector.as_ref() else { panic!("expected relative") };
+        let Some(Scope::Around(around)) = &relative.scope else { panic!("expected around scope") };
+        assert!(matches!(&around.anchor, SelectorOrRef::Inline(s) if matches!(s.as_ref(), Selector::OCR(o) if o.text == "图层")));
+        assert!(resolved.selectors.values().all(|s| !has_ref(&SelectorOrRef::Inline(Box::new(s.clone())))));
+    }
+
+    #[test]
+    fn test_reports_unresolved_and_malformed_with_location() {
+        let pkg = package(
+            json!({
+                "multi": { "strategy": "multi", "candidates": [{ "$ref": "#/selectors/missing" }] }
+            }),
+            json!([
+                { "id": "s1", "op": "click", "target": { "$ref": "#/selectors/multi" } },
+                { "id": "s2", "op": "wait", "until": { "$ref": "selectors/multi" } }
+            ]),
+        );
+
+        let errors = pkg.resolve().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ResolveError::Unresolved { path: "/selectors/multi/candidates/0/$ref".to_string(), key: "missing".to_string() },
+                ResolveError::Malformed { path: "/steps/1/until/$ref".to_string(), reference: "selectors/multi".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detects_cycle_through_multi() {
+        let pkg = package(
+            json!({
+                "rel": {
+                    "strategy": "relative",
+                    "anchor": { "$ref": "#/selectors/pick" },
+                    "relation": { "type": "near" },
+                    "target": { "strategy": "ocr", "text": "OK" }
+                },
+                "pick": {
+                    "strategy": "multi",
+                    "candidates": [{ "strategy": "ocr", "text": "OK" }, { "$ref": "#/selectors/rel" }]
+                }
+            }),
+            json!([{ "id": "s1", "op": "click", "target": { "$ref": "#/selectors/rel" } }]),
+        );
+
+        let errors = pkg.resolve().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![ResolveError::Cycle {
+                path: "/selectors/rel/anchor/$ref".to_string(),
+                chain: vec!["pick".to_string(), "rel".to_string(), "pick".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::domain::validate::Diagnostic;
+
+/// The AIPDL package JSON Schema (draft 2020-12), bundled at compile time.
+/// Kept identical to the repository-root `schema.json` the clients use.
+pub const PACKAGE_SCHEMA: &str = include_str!("../../schema.json");
+
+/// Why a generated package was rejected, located by JSON pointer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaViolation {
+    /// Pointer into the rejected document.
+    pub path: String,
+    /// Pointer to the schema keyword that failed; absent for semantic checks
+    /// that run after the schema (see [`crate::domain::validate`]).
+    #[serde(rename = "schemaPath", default, skip_serializing_if = "Option::is_none")]
+    pub schema_path: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+impl From<&Diagnostic> for SchemaViolation {
+    fn from(d: &Diagnostic) -> Self {
+        SchemaViolation { path: d.path.clone(), schema_path: None, message: d.message.clone() }
+    }
+}
+
+pub fn package_schema() -> &'static Value {
+    static SCHEMA: OnceLock<Value> = OnceLock::new();
+    SCHEMA.get_or_init(|| serde_json::from_str(PACKAGE_SCHEMA).expect("bundled schema.json is valid JSON"))
+}
+
+fn validator() -> &'static jsonschema::Validator {
+    static VALIDATOR: OnceLock<jsonschema::Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        jsonschema::draft202012::new(package_schema()).expect("bundled schema.json is a valid JSON Schema")
+    })
+}
+
+/// Checks a raw document against the package schema, reporting every violation.
+pub fn validate_package_json(document: &Value) -> Result<(), Vec<SchemaViolation>> {
+    let violations: Vec<SchemaViolation> = validator()
+        .iter_errors(document)
+        .map(|e| SchemaViolation {
+            path: e.instance_path().to_string(),
+            schema_path: Some(e.schema_path().to_string()),
+            message: e.to_string(),
+        })
+        .collect();
+    if violations.is_empty() { Ok(()) } else { Err(violations) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::package::Package;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "version": "0.1",
+            "package": { "name": "Export", "createdAt": "2026-01-01T00:00:00Z", "tags": ["export"] },
+            "app": { "name": "Photoshop", "minVersion": "2023" },
+            "vars": { "FILE_NAME": { "type": "string", "default": "out" } },
+            "selectors": {
+                "menu_file": {
+                    "strategy": "ocr",
+                    "text": "文件",
+                    "match": { "mode": "equals", "lang": "chi_sim", "caseSensitive": false },
+                    "scope": { "type": "band", "edge": "top", "ratio": 0.18 }
+                }
+            },
+            "steps": [
+                {
+                    "id": "s1",
+                    "op": "click",
+                    "target": { "$ref": "#/selectors/menu_file" },
+                    "retry": { "times": 2, "intervalMs": 400, "timeoutMs": 3000 },
+                    "on_fail": { "action": "abort", "reason": "menu_file not found" }
+                },
+                { "id": "s2", "op": "type", "text": "{{FILE_NAME}}" },
+                { "id": "s3", "op": "hotkey", "keys": ["ctrl", "s"] }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_valid_package_passes_schema_and_serde() {
+        let doc = document();
+        assert_eq!(validate_package_json(&doc), Ok(()));
+        let package: Package = serde_json::from_value(doc).unwrap();
+
+        // What the service stores and serves must validate too
+        assert_eq!(validate_package_json(&serde_json::to_value(&package).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_violations_carry_pointers() {
+        let mut doc = document();
+        doc["steps"][0]["colour"] = json!("red");
+        doc["app"].as_object_mut().unwrap().remove("name");
+
+        let violations = validate_package_json(&doc).unwrap_err();
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert!(paths.contains(&"/app"), "{:?}", violations);
+        assert!(paths.iter().any(|p| p.starts_with("/steps/0")), "{:?}", violations);
+        assert!(violations.iter().all(|v| v.schema_path.is_some()));
+    }
+}
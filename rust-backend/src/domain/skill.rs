@@ -1,6 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::domain::package::{EnvSpec, Selector, VarDef};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -17,6 +20,9 @@ pub enum ActionType {
     Drag,
     Shortcut,
     Menu,
+    Scroll,
+    Wait,
+    Assert,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -80,6 +86,27 @@ pub struct Skill {
     pub tags: Vec<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub source_type: SourceType,
+    /// Package settings a skill has no field for; see [`crate::domain::convert`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<PackageExtras>,
+}
+
+/// What a [`Skill`] needs on top of its own fields to become a package again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageExtras {
+    /// AIPDL spec version; absent means the current one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spec_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<EnvSpec>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vars: HashMap<String, VarDef>,
+    /// Registry selectors that are not the target of any step, e.g. drag
+    /// destinations and relative anchors. Step targets live in their locators.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub selectors: HashMap<String, Selector>,
 }
 
 #[cfg(test)]
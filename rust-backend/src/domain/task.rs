@@ -1,5 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::domain::pipeline_error::TaskFailure;
+use crate::domain::schema::SchemaViolation;
+use crate::domain::transcript::{StepAlignment, TranscriptSegment};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -10,6 +15,99 @@ pub enum TaskStatus {
     VideoDone,
     Finished,
     Failed,
+    /// Stopped on request; results of runs still in flight are discarded.
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// The transition table. Audio and video run as independent tracks, so a
+    /// task may go back to `Processing` after either one completes.
+    pub fn can_transition_to(&self, to: &TaskStatus) -> bool {
+        use TaskStatus::*;
+        match self {
+            Created => matches!(to, Processing | Failed | Cancelled),
+            Processing => matches!(to, Processing | AudioDone | VideoDone | Finished | Failed | Cancelled),
+            AudioDone => matches!(to, Processing | VideoDone | Finished | Failed | Cancelled),
+            VideoDone => matches!(to, Processing | AudioDone | Finished | Failed | Cancelled),
+            Finished | Failed | Cancelled => false,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, TaskStatus::Finished | TaskStatus::Failed | TaskStatus::Cancelled)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Created => "created",
+            TaskStatus::Processing => "processing",
+            TaskStatus::AudioDone => "audio_done",
+            TaskStatus::VideoDone => "video_done",
+            TaskStatus::Finished => "finished",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A transition rejected by [`TaskStatus::can_transition_to`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionError {
+    pub from: TaskStatus,
+    pub to: TaskStatus,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "illegal status transition {} -> {}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+/// Artifact tracks that must all be stored before a task is finished.
+pub const REQUIRED_TRACKS: [&str; 3] = ["audio", "video", "steps"];
+
+/// One formatter call of the video track, kept so failed generations can be
+/// inspected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormatAttempt {
+    /// 1-based.
+    pub attempt: u32,
+    /// The model's reply, verbatim.
+    pub output: String,
+    /// Why the reply was rejected; `None` for the accepted attempt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// One provider call of the pipeline, with every retry it took.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderCall {
+    /// `download`, `transcribe`, `video_analysis` or `skill_format`.
+    pub stage: String,
+    /// Attempts made, the first included.
+    pub attempts: u32,
+    /// The last failure, if no attempt succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// One entry in a task's status history.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatusChange {
+    pub from: TaskStatus,
+    pub to: TaskStatus,
+    pub at: DateTime<Utc>,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,17 +117,41 @@ pub struct Task {
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transcript_text: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transcript_segments: Vec<TranscriptSegment>,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub video_analysis: Option<serde_json::Value>,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub steps_package: Option<serde_json::Value>,
+
+    /// Transcript span for each generated step, joined once audio and video are both done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_alignment: Option<Vec<StepAlignment>>,
     
     pub status: TaskStatus,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+
+    /// The structured form of `error` when a pipeline stage failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<TaskFailure>,
+
+    /// Why the generated package was rejected, if it was.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub validation_errors: Vec<SchemaViolation>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub format_attempts: Vec<FormatAttempt>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provider_calls: Vec<ProviderCall>,
+
+    #[serde(default)]
+    pub history: Vec<StatusChange>,
     
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -42,12 +164,108 @@ impl Task {
             entry_id,
             dir_location,
             transcript_text: None,
+            transcript_segments: Vec::new(),
             video_analysis: None,
             steps_package: None,
+            step_alignment: None,
             status: TaskStatus::Created,
             error: None,
+            failure: None,
+            validation_errors: Vec::new(),
+            format_attempts: Vec::new(),
+            provider_calls: Vec::new(),
+            history: Vec::new(),
             created_at: now,
             updated_at: now,
         }
     }
+
+    /// Moves the task to `to`, recording the change in its history.
+    pub fn transition(&mut self, to: TaskStatus, reason: &str) -> Result<(), TransitionError> {
+        if !self.status.can_transition_to(&to) {
+            return Err(TransitionError { from: self.status.clone(), to });
+        }
+        let now = Utc::now();
+        self.history.push(StatusChange {
+            from: self.status.clone(),
+            to: to.clone(),
+            at: now,
+            reason: reason.to_string(),
+        });
+        self.status = to;
+        self.updated_at = now;
+        Ok(())
+    }
+
+    pub fn has_artifact(&self, track: &str) -> bool {
+        match track {
+            "audio" => self.transcript_text.is_some(),
+            "video" => self.video_analysis.is_some(),
+            "steps" => self.steps_package.is_some(),
+            "alignment" => self.step_alignment.is_some(),
+            "attempts" => !self.format_attempts.is_empty(),
+            _ => false,
+        }
+    }
+
+    pub fn missing_tracks(&self) -> Vec<&'static str> {
+        REQUIRED_TRACKS.into_iter().filter(|track| !self.has_artifact(track)).collect()
+    }
+
+    /// The completion rule, applied after storing a track's artifacts: the
+    /// task is `Finished` once every required track is present and `partial`
+    /// until then.
+    pub fn artifacts_stored(&mut self, partial: TaskStatus, reason: &str) -> Result<(), TransitionError> {
+        if self.missing_tracks().is_empty() {
+            return self.transition(TaskStatus::Finished, &format!("{}, all tracks ready", reason));
+        }
+        if self.status == partial && !partial.is_terminal() {
+            self.updated_at = Utc::now();
+            return Ok(());
+        }
+        self.transition(partial, reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_states_are_final() {
+        let mut task = Task::new("t".to_string(), String::new());
+        task.transition(TaskStatus::Processing, "audio parse started").unwrap();
+        task.transition(TaskStatus::Failed, "download failed").unwrap();
+
+        let err = task.transition(TaskStatus::VideoDone, "video analysis ready").unwrap_err();
+        assert_eq!(err, TransitionError { from: TaskStatus::Failed, to: TaskStatus::VideoDone });
+        assert_eq!(task.status, TaskStatus::Failed);
+        assert_eq!(task.history.len(), 2);
+        assert_eq!(task.history[1].from, TaskStatus::Processing);
+        assert_eq!(task.history[1].reason, "download failed");
+    }
+
+    #[test]
+    fn test_finished_once_every_track_is_stored() {
+        let mut task = Task::new("t".to_string(), String::new());
+        task.transition(TaskStatus::Processing, "video parse started").unwrap();
+        task.video_analysis = Some(serde_json::json!({}));
+        task.steps_package = Some(serde_json::json!({}));
+        task.artifacts_stored(TaskStatus::VideoDone, "video analysis ready").unwrap();
+        assert_eq!(task.status, TaskStatus::VideoDone);
+        assert_eq!(task.missing_tracks(), ["audio"]);
+
+        task.transition(TaskStatus::Processing, "audio parse started").unwrap();
+        task.transcript_text = Some("hello".to_string());
+        task.artifacts_stored(TaskStatus::AudioDone, "audio transcript ready").unwrap();
+        assert_eq!(task.status, TaskStatus::Finished);
+        assert_eq!(task.history.last().unwrap().reason, "audio transcript ready, all tracks ready");
+    }
+
+    #[test]
+    fn test_created_must_start_processing() {
+        let mut task = Task::new("t".to_string(), String::new());
+        assert!(task.transition(TaskStatus::AudioDone, "audio transcript ready").is_err());
+        assert!(task.history.is_empty());
+    }
 }
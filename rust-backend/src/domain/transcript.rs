@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::domain::package::{Package, Step};
+use crate::domain::resolve::selector_key;
+
+/// A timed span of the transcript, in seconds from the start of the audio.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    /// Provider confidence in 0..1, when reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+/// The contiguous run of segments that narrates a step.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscriptSpan {
+    #[serde(rename = "firstSegment")]
+    pub first_segment: usize,
+    #[serde(rename = "lastSegment")]
+    pub last_segment: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepAlignment {
+    #[serde(rename = "stepId")]
+    pub step_id: String,
+    /// `None` when there is no transcript to align against.
+    pub span: Option<TranscriptSpan>,
+    /// Lexical similarity (0..1) between the step and its first segment; 0
+    /// means the span was placed by position alone.
+    pub score: f64,
+}
+
+/// Weight of the "steps and speech progress at the same pace" prior. Small
+/// enough that any shared word outweighs it.
+const POSITION_PRIOR: f64 = 0.05;
+
+/// Joins each step with the transcript span that narrates it.
+///
+/// Steps and segments are both in chronological order, so every step gets an
+/// anchor segment with anchors never moving backwards (a sentence may narrate
+/// several steps). Anchors maximise the summed word overlap between a step's
+/// text (name, OCR text, typed text, keys, selector names) and its segment. A
+/// step's span then runs from its anchor up to the segment before the next
+/// step's anchor.
+pub fn align_steps(package: &Package, segments: &[TranscriptSegment]) -> Vec<StepAlignment> {
+    if segments.is_empty() {
+        return package
+            .steps
+            .iter()
+            .map(|s| StepAlignment { step_id: s.id.clone(), span: None, score: 0.0 })
+            .collect();
+    }
+
+    // Resolved steps carry their selectors' OCR text inline
+    let steps: Vec<Step> = match package.resolve() {
+        Ok(resolved) => resolved.steps.clone(),
+        Err(_) => package.steps.clone(),
+    };
+    let step_tokens: Vec<HashSet<String>> = steps.iter().map(|s| tokenize(&step_text(s))).collect();
+    let segment_tokens: Vec<HashSet<String>> = segments.iter().map(|s| tokenize(&s.text)).collect();
+
+    let (n, m) = (steps.len(), segments.len());
+    let similarity = |i: usize, j: usize| dice(&step_tokens[i], &segment_tokens[j]);
+    let score = |i: usize, j: usize| {
+        let drift = ((i as f64 + 0.5) / n as f64 - (j as f64 + 0.5) / m as f64).abs();
+        similarity(i, j) + POSITION_PRIOR * (1.0 - drift)
+    };
+
+    // best[i][j]: best total with step i anchored at segment j; from[i][j]:
+    // step i-1's anchor on that path
+    let mut best = vec![vec![0.0; m]; n];
+    let mut from = vec![vec![0usize; m]; n];
+    for i in 0..n {
+        let (mut prefix_best, mut prefix_at) = (f64::NEG_INFINITY, 0);
+        for j in 0..m {
+            let previous = if i == 0 {
+                0.0
+            } else {
+                if best[i - 1][j] > prefix_best {
+                    prefix_best = best[i - 1][j];
+                    prefix_at = j;
+                }
+                prefix_best
+            };
+            best[i][j] = previous + score(i, j);
+            from[i][j] = prefix_at;
+        }
+    }
+
+    let mut anchors = vec![0usize; n];
+    if n > 0 {
+        let mut j = (0..m).fold(0, |acc, j| if best[n - 1][j] > best[n - 1][acc] { j } else { acc });
+        for i in (0..n).rev() {
+            anchors[i] = j;
+            j = from[i][j];
+        }
+    }
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let first = anchors[i];
+            let last = match anchors.get(i + 1) {
+                Some(&next) if next > first => next - 1,
+                _ => first,
+            };
+            StepAlignment {
+                step_id: step.id.clone(),
+                span: Some(span(segments, first, last)),
+                score: similarity(i, first),
+            }
+        })
+        .collect()
+}
+
+fn span(segments: &[TranscriptSegment], first: usize, last: usize) -> TranscriptSpan {
+    let covered = &segments[first..=last];
+    let confidences: Vec<f64> = covered.iter().filter_map(|s| s.confidence).collect();
+    TranscriptSpan {
+        first_segment: first,
+        last_segment: last,
+        start: covered[0].start,
+        end: covered[covered.len() - 1].end,
+        text: covered.iter().map(|s| s.text.trim()).collect::<Vec<_>>().join(" "),
+        confidence: if confidences.is_empty() {
+            None
+        } else {
+            Some(confidences.iter().sum::<f64>() / confidences.len() as f64)
+        },
+    }
+}
+
+/// Human-readable words describing a step, gathered from its JSON form.
+fn step_text(step: &Step) -> String {
+    fn collect(value: &Value, key: Option<&str>, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (k, v) in map {
+                    collect(v, Some(k), out);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    collect(item, key, out);
+                }
+            }
+            Value::String(s) => match key {
+                Some("name" | "text" | "keys" | "title") => out.push(s.clone()),
+                Some("$ref") => {
+                    if let Some(key) = selector_key(s) {
+                        out.push(key.replace(['_', '-'], " "));
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    let mut words = Vec::new();
+    if let Ok(value) = serde_json::to_value(step) {
+        collect(&value, None, &mut words);
+    }
+    words.join(" ")
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}')
+}
+
+/// Lowercased alphanumeric words plus CJK character bigrams (CJK text has no
+/// word boundaries; a lone CJK character counts as its own token).
+fn tokenize(text: &str) -> HashSet<String> {
+    let mut tokens = HashSet::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    let flush_cjk = |run: &mut Vec<char>, tokens: &mut HashSet<String>| {
+        match run.len() {
+            0 => {}
+            1 => {
+                tokens.insert(run[0].to_string());
+            }
+            _ => {
+                for pair in run.windows(2) {
+                    tokens.insert(pair.iter().collect());
+                }
+            }
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.insert(std::mem::take(&mut word));
+            }
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            if !word.is_empty() {
+                tokens.insert(std::mem::take(&mut word));
+            }
+        }
+    }
+    flush_cjk(&mut cjk_run, &mut tokens);
+    if !word.is_empty() {
+        tokens.insert(word);
+    }
+    tokens
+}
+
+fn dice(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use serde_json::json;
+
+    fn segment(start: f64, end: f64, text: &str) -> TranscriptSegment {
+        TranscriptSegment { start, end, text: text.to_string(), confidence: Some(0.9) }
+    }
+
+    fn package() -> Package {
+        fixtures::package()
+            .selectors(json!({
+                "file_menu": { "strategy": "ocr", "text": "文件" },
+                "export_item": { "strategy": "ocr", "text": "导出" }
+            }))
+            .steps(json!([
+                { "id": "open_file", "op": "click", "target": { "$ref": "#/selectors/file_menu" } },
+                { "id": "export", "op": "click", "target": { "$ref": "#/selectors/export_item" } },
+                { "id": "name", "op": "type", "text": "poster.png" },
+                { "id": "confirm", "op": "hotkey", "keys": ["enter"] }
+            ]))
+            .build()
+    }
+
+    #[test]
+    fn test_steps_follow_their_narration() {
+        let segments = vec![
+            segment(0.0, 2.0, "大家好，今天演示导出图片"),
+            segment(2.0, 4.0, "首先点击文件菜单"),
+            segment(4.0, 5.0, "然后往下看"),
+            segment(5.0, 7.0, "选择导出"),
+            segment(7.0, 9.0, "文件名输入 poster.png"),
+            segment(9.0, 10.0, "最后按 enter 确认"),
+        ];
+        let alignment = align_steps(&package(), &segments);
+        let firsts: Vec<usize> = alignment.iter().map(|a| a.span.as_ref().unwrap().first_segment).collect();
+        assert_eq!(firsts, vec![1, 3, 4, 5]);
+
+        // The filler sentence stays with the step it follows
+        let open = alignment[0].span.as_ref().unwrap();
+        assert_eq!((open.start, open.end), (2.0, 5.0));
+        assert_eq!(open.text, "首先点击文件菜单 然后往下看");
+        assert!(alignment[0].score > 0.0);
+    }
+
+    #[test]
+    fn test_without_transcript_every_step_is_unaligned() {
+        let alignment = align_steps(&package(), &[]);
+        assert_eq!(alignment.len(), 4);
+        assert!(alignment.iter().all(|a| a.span.is_none()));
+    }
+}
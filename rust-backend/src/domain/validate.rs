@@ -0,0 +1,688 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::domain::package::{
+    Fallback, OCRMatch, Package, Scope, Selector, SelectorOrRef, SelectorRef, Step, StepOperation,
+};
+use crate::domain::resolve::{selector_key, ResolveError};
+
+const REL_TYPES: &[&str] = &["below", "above", "leftOf", "rightOf", "near"];
+const BAND_EDGES: &[&str] = &["top", "bottom", "left", "right"];
+const OCR_MODES: &[&str] = &["equals", "contains", "regex"];
+const PICK_POLICIES: &[&str] = &["bestConfidence", "firstMatch"];
+const FALLBACK_POLICIES: &[&str] = &["last_retry_only", "always"];
+const ON_FAIL_ACTIONS: &[&str] = &["abort", "skip", "fallback_step_id"];
+const VAR_TYPES: &[&str] = &["string", "number", "boolean", "path"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticCode {
+    /// `$ref` does not point into `#/selectors/` or names a missing selector.
+    DanglingRef,
+    /// Following `$ref`s leads back to the selector being expanded.
+    RefCycle,
+    DuplicateStepId,
+    /// `on_fail.action = fallback_step_id` without a `stepId`.
+    MissingFallbackStep,
+    /// A step id that does not exist in `steps`.
+    UnknownStepRef,
+    /// `elementRef` pointing at the current or a later step.
+    ForwardStepRef,
+    InvalidEnum,
+    OutOfRange,
+    EmptyCandidates,
+    UnusedSelector,
+}
+
+/// A single finding from [`Package::validate`], located by JSON pointer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Diagnostic {
+    pub path: String,
+    pub severity: Severity,
+    pub code: DiagnosticCode,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = serde_json::to_value(self.code).ok();
+        let code = code.as_ref().and_then(|v| v.as_str()).unwrap_or("unknown");
+        write!(f, "{} [{}]: {}", self.path, code, self.message)
+    }
+}
+
+/// Escapes a single JSON pointer reference token (RFC 6901).
+pub fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+impl Package {
+    /// Semantic checks that serde cannot express: reference integrity,
+    /// step id uniqueness and ordering, enum-like strings and numeric ranges.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(self);
+        validator.run();
+        // Dangling refs are already reported by the walk above; only cycles are new here.
+        if let Err(errors) = self.resolve() {
+            for error in errors {
+                if let ResolveError::Cycle { chain, .. } = &error {
+                    validator.error(
+                        error.path().to_string(),
+                        DiagnosticCode::RefCycle,
+                        format!("reference cycle {}", chain.join(" -> ")),
+                    );
+                }
+            }
+        }
+        validator.diagnostics
+    }
+}
+
+struct Validator<'a> {
+    pkg: &'a Package,
+    /// First index of every step id.
+    step_index: HashMap<&'a str, usize>,
+    used_selectors: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(pkg: &'a Package) -> Self {
+        let mut step_index = HashMap::new();
+        for (i, step) in pkg.steps.iter().enumerate() {
+            step_index.entry(step.id.as_str()).or_insert(i);
+        }
+        Self {
+            pkg,
+            step_index,
+            used_selectors: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, path: String, severity: Severity, code: DiagnosticCode, message: String) {
+        self.diagnostics.push(Diagnostic { path, severity, code, message });
+    }
+
+    fn error(&mut self, path: String, code: DiagnosticCode, message: String) {
+        self.push(path, Severity::Error, code, message);
+    }
+
+    fn run(&mut self) {
+        let pkg = self.pkg;
+
+        let mut var_names: Vec<&String> = pkg.vars.keys().collect();
+        var_names.sort();
+        for name in var_names {
+            let path = format!("/vars/{}/type", escape_pointer_token(name));
+            self.check_enum(path, &pkg.vars[name].var_type, VAR_TYPES);
+        }
+
+        // Registry selectors are checked once here; step contexts only add ordering checks.
+        let mut selector_keys: Vec<&String> = pkg.selectors.keys().collect();
+        selector_keys.sort();
+        for key in &selector_keys {
+            let path = format!("/selectors/{}", escape_pointer_token(key));
+            self.check_selector(&pkg.selectors[*key], &path, None);
+        }
+
+        let mut seen = HashSet::new();
+        for (i, step) in pkg.steps.iter().enumerate() {
+            if !seen.insert(step.id.as_str()) {
+                self.error(
+                    format!("/steps/{}/id", i),
+                    DiagnosticCode::DuplicateStepId,
+                    format!("step id '{}' is already used by an earlier step", step.id),
+                );
+            }
+            self.check_step(step, i);
+        }
+
+        for key in selector_keys {
+            if !self.used_selectors.contains(key.as_str()) {
+                self.push(
+                    format!("/selectors/{}", escape_pointer_token(key)),
+                    Severity::Warning,
+                    DiagnosticCode::UnusedSelector,
+                    format!("selector '{}' is never referenced", key),
+                );
+            }
+        }
+    }
+
+    fn check_step(&mut self, step: &Step, index: usize) {
+        let base = format!("/steps/{}", index);
+        let ctx = Some(index);
+
+        if let Some(scope) = &step.scope {
+            self.check_scope(scope, &format!("{}/scope", base), ctx);
+        }
+
+        if let Some(retry) = &step.retry
+            && retry.timeout_ms > 0
+            && retry.interval_ms > retry.timeout_ms
+        {
+            self.push(
+                format!("{}/retry/intervalMs", base),
+                Severity::Warning,
+                DiagnosticCode::OutOfRange,
+                "retry interval is longer than the timeout".to_string(),
+            );
+        }
+
+        if let Some(on_fail) = &step.on_fail {
+            let path = format!("{}/on_fail", base);
+            self.check_enum(format!("{}/action", path), &on_fail.action, ON_FAIL_ACTIONS);
+            match (on_fail.action.as_str(), &on_fail.step_id) {
+                ("fallback_step_id", None) => self.error(
+                    format!("{}/stepId", path),
+                    DiagnosticCode::MissingFallbackStep,
+                    "on_fail action 'fallback_step_id' requires a stepId".to_string(),
+                ),
+                ("fallback_step_id", Some(target)) if !self.step_index.contains_key(target.as_str()) => self.error(
+                    format!("{}/stepId", path),
+                    DiagnosticCode::UnknownStepRef,
+                    format!("fallback step '{}' does not exist", target),
+                ),
+                ("fallback_step_id", Some(target)) if *target == step.id => self.error(
+                    format!("{}/stepId", path),
+                    DiagnosticCode::UnknownStepRef,
+                    format!("step '{}' falls back to itself", target),
+                ),
+                _ => {}
+            }
+        }
+
+        match &step.op {
+            StepOperation::Click(click) => {
+                self.check_selector_or_ref(&click.target, &format!("{}/target", base), ctx);
+                if let Some(fallback) = &click.fallback {
+                    self.check_fallback(fallback, &format!("{}/fallback", base));
+                }
+            }
+            StepOperation::Drag(drag) => {
+                self.check_selector_or_ref(&drag.from, &format!("{}/from", base), ctx);
+                if let Some(to) = &drag.to {
+                    self.check_selector_or_ref(to, &format!("{}/to", base), ctx);
+                }
+                if let Some(fallback) = &drag.fallback {
+                    self.check_fallback(fallback, &format!("{}/fallback", base));
+                }
+            }
+            StepOperation::Type(t) => {
+                if let Some(target) = &t.target {
+                    self.check_selector_or_ref(target, &format!("{}/target", base), ctx);
+                }
+            }
+            StepOperation::Scroll(scroll) => {
+                if let Some(target) = &scroll.target {
+                    self.check_selector_or_ref(target, &format!("{}/target", base), ctx);
+                }
+            }
+            StepOperation::Hotkey(_) => {}
+            StepOperation::Wait(wait) => {
+                self.check_selector_or_ref(&wait.until, &format!("{}/until", base), ctx);
+                if let Some(params) = &wait.params {
+                    self.check_unit_range(format!("{}/params/minConfidence", base), params.min_confidence);
+                }
+            }
+            StepOperation::Assert(assert) => {
+                self.check_selector_or_ref(&assert.expect, &format!("{}/expect", base), ctx);
+                if let Some(params) = &assert.params {
+                    self.check_unit_range(format!("{}/params/minConfidence", base), params.min_confidence);
+                }
+            }
+        }
+    }
+
+    /// `step` is the index of the step the selector is evaluated in, or `None`
+    /// for registry definitions that may be shared by several steps.
+    fn check_selector_or_ref(&mut self, sel: &SelectorOrRef, path: &str, step: Option<usize>) {
+        match sel {
+            SelectorOrRef::Ref(r) => self.check_ref(r, &format!("{}/$ref", path), step),
+            SelectorOrRef::Inline(selector) => self.check_selector(selector, path, step),
+        }
+    }
+
+    fn check_ref(&mut self, r: &SelectorRef, path: &str, step: Option<usize>) {
+        let Some(key) = selector_key(&r.reference) else {
+            self.error(
+                path.to_string(),
+                DiagnosticCode::DanglingRef,
+                format!("'{}' is not a #/selectors/<id> reference", r.reference),
+            );
+            return;
+        };
+        if !self.pkg.selectors.contains_key(&key) {
+            self.error(
+                path.to_string(),
+                DiagnosticCode::DanglingRef,
+                format!("selector '{}' is not defined", key),
+            );
+            return;
+        }
+        self.used_selectors.insert(key.clone());
+
+        // Registry selectors were validated on their own; only the ordering of
+        // elementRef scopes depends on which step pulls them in.
+        if let Some(index) = step {
+            let mut visited = HashSet::new();
+            let mut step_refs = Vec::new();
+            self.collect_registry_step_refs(&key, &mut visited, &mut step_refs);
+            for ref_step_id in step_refs {
+                if let Some(&target) = self.step_index.get(ref_step_id.as_str())
+                    && target >= index
+                {
+                    self.error(
+                        path.to_string(),
+                        DiagnosticCode::ForwardStepRef,
+                        format!(
+                            "selector '{}' scopes to step '{}' which has not run yet",
+                            key, ref_step_id
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Gathers `elementRef` step ids reachable from a registry selector,
+    /// following nested `$ref`s. Also marks those selectors as used.
+    fn collect_registry_step_refs(&mut self, key: &str, visited: &mut HashSet<String>, out: &mut Vec<String>) {
+        if !visited.insert(key.to_string()) {
+            return;
+        }
+        let Some(selector) = self.pkg.selectors.get(key) else {
+            return;
+        };
+        self.used_selectors.insert(key.to_string());
+
+        let mut nested = Vec::new();
+        collect_selector_step_refs(selector, out, &mut nested);
+        for next in nested {
+            self.collect_registry_step_refs(&next, visited, out);
+        }
+    }
+
+    fn check_selector(&mut self, selector: &Selector, path: &str, step: Option<usize>) {
+        match selector {
+            Selector::OCR(ocr) => {
+                if let Some(m) = &ocr.match_options {
+                    self.check_ocr_match(m, &format!("{}/match", path));
+                }
+                if let Some(scope) = &ocr.scope {
+                    self.check_scope(scope, &format!("{}/scope", path), step);
+                }
+            }
+            Selector::Template(template) => {
+                if let Some(m) = &template.match_options {
+                    self.check_unit_range(format!("{}/match/threshold", path), m.threshold);
+                }
+                if let Some(scope) = &template.scope {
+                    self.check_scope(scope, &format!("{}/scope", path), step);
+                }
+            }
+            Selector::Relative(relative) => {
+                self.check_selector_or_ref(&relative.anchor, &format!("{}/anchor", path), step);
+                self.check_enum(format!("{}/relation/type", path), &relative.relation.rel_type, REL_TYPES);
+                self.check_selector_or_ref(&relative.target, &format!("{}/target", path), step);
+                if let Some(scope) = &relative.scope {
+                    self.check_scope(scope, &format!("{}/scope", path), step);
+                }
+            }
+            Selector::Multi(multi) => {
+                if multi.candidates.is_empty() {
+                    self.error(
+                        format!("{}/candidates", path),
+                        DiagnosticCode::EmptyCandidates,
+                        "multi selector needs at least one candidate".to_string(),
+                    );
+                }
+                for (i, candidate) in multi.candidates.iter().enumerate() {
+                    self.check_selector_or_ref(candidate, &format!("{}/candidates/{}", path, i), step);
+                }
+                if let Some(pick) = &multi.pick {
+                    self.check_enum(format!("{}/pick/policy", path), &pick.policy, PICK_POLICIES);
+                }
+                if let Some(scope) = &multi.scope {
+                    self.check_scope(scope, &format!("{}/scope", path), step);
+                }
+            }
+        }
+    }
+
+    fn check_ocr_match(&mut self, m: &OCRMatch, path: &str) {
+        self.check_enum(format!("{}/mode", path), &m.mode, OCR_MODES);
+        if m.mode == "regex" && m.regex.is_none() {
+            self.error(
+                format!("{}/regex", path),
+                DiagnosticCode::InvalidEnum,
+                "match mode 'regex' requires a regex pattern".to_string(),
+            );
+        }
+    }
+
+    fn check_scope(&mut self, scope: &Scope, path: &str, step: Option<usize>) {
+        match scope {
+            Scope::Rect(rect) => {
+                if rect.normalized {
+                    for (field, value) in [("x", rect.x), ("y", rect.y), ("w", rect.w), ("h", rect.h)] {
+                        self.check_unit_range(format!("{}/{}", path, field), value);
+                    }
+                } else if rect.w < 0.0 || rect.h < 0.0 {
+                    self.error(
+                        path.to_string(),
+                        DiagnosticCode::OutOfRange,
+                        "rect width and height must not be negative".to_string(),
+                    );
+                }
+            }
+            Scope::Band(band) => {
+                self.check_enum(format!("{}/edge", path), &band.edge, BAND_EDGES);
+                self.check_unit_range(format!("{}/ratio", path), band.ratio);
+            }
+            Scope::ElementRef(element) => {
+                let field = format!("{}/refStepId", path);
+                match self.step_index.get(element.ref_step_id.as_str()) {
+                    None => self.error(
+                        field,
+                        DiagnosticCode::UnknownStepRef,
+                        format!("step '{}' does not exist", element.ref_step_id),
+                    ),
+                    Some(&target) => {
+                        if let Some(index) = step
+                            && target >= index
+                        {
+                            self.error(
+                                field,
+                                DiagnosticCode::ForwardStepRef,
+                                format!("step '{}' has not run yet at this point", element.ref_step_id),
+                            );
+                        }
+                    }
+                }
+            }
+            Scope::Around(around) => {
+                self.check_selector_or_ref(&around.anchor, &format!("{}/anchor", path), step);
+            }
+            Scope::Union(union) => {
+                for (i, s) in union.scopes.iter().enumerate() {
+                    self.check_scope(s, &format!("{}/scopes/{}", path, i), step);
+                }
+            }
+            Scope::Intersect(intersect) => {
+                for (i, s) in intersect.scopes.iter().enumerate() {
+                    self.check_scope(s, &format!("{}/scopes/{}", path, i), step);
+                }
+            }
+            Scope::Exclude(exclude) => {
+                self.check_scope(&exclude.base, &format!("{}/base", path), step);
+                self.check_scope(&exclude.exclude, &format!("{}/exclude", path), step);
+            }
+            Scope::Window(_) | Scope::Dialog(_) | Scope::ActiveMenu(_) | Scope::Nearest(_) => {}
+        }
+    }
+
+    fn check_fallback(&mut self, fallback: &Fallback, path: &str) {
+        self.check_enum(format!("{}/policy", path), &fallback.policy, FALLBACK_POLICIES);
+        if fallback.point.normalized {
+            self.check_unit_range(format!("{}/point/x", path), fallback.point.x);
+            self.check_unit_range(format!("{}/point/y", path), fallback.point.y);
+        }
+    }
+
+    fn check_enum(&mut self, path: String, value: &str, allowed: &[&str]) {
+        if !allowed.contains(&value) {
+            self.error(
+                path,
+                DiagnosticCode::InvalidEnum,
+                format!("'{}' is not one of {}", value, allowed.join(", ")),
+            );
+        }
+    }
+
+    fn check_unit_range(&mut self, path: String, value: f64) {
+        if !(0.0..=1.0).contains(&value) {
+            self.error(
+                path,
+                DiagnosticCode::OutOfRange,
+                format!("{} is outside 0..1", value),
+            );
+        }
+    }
+}
+
+/// Collects `elementRef` step ids in a selector tree and the registry keys it refers to.
+fn collect_selector_step_refs(selector: &Selector, step_refs: &mut Vec<String>, selector_refs: &mut Vec<String>) {
+    let visit_sor = |sor: &SelectorOrRef, step_refs: &mut Vec<String>, selector_refs: &mut Vec<String>| match sor {
+        SelectorOrRef::Ref(r) => {
+            if let Some(key) = selector_key(&r.reference) {
+                selector_refs.push(key);
+            }
+        }
+        SelectorOrRef::Inline(inner) => collect_selector_step_refs(inner, step_refs, selector_refs),
+    };
+
+    let scope = match selector {
+        Selector::OCR(s) => s.scope.as_ref(),
+        Selector::Template(s) => s.scope.as_ref(),
+        Selector::Relative(s) => {
+            visit_sor(&s.anchor, step_refs, selector_refs);
+            visit_sor(&s.target, step_refs, selector_refs);
+            s.scope.as_ref()
+        }
+        Selector::Multi(s) => {
+            for candidate in &s.candidates {
+                visit_sor(candidate, step_refs, selector_refs);
+            }
+            s.scope.as_ref()
+        }
+    };
+    if let Some(scope) = scope {
+        collect_scope_step_refs(scope, step_refs, selector_refs);
+    }
+}
+
+fn collect_scope_step_refs(scope: &Scope, step_refs: &mut Vec<String>, selector_refs: &mut Vec<String>) {
+    match scope {
+        Scope::ElementRef(e) => step_refs.push(e.ref_step_id.clone()),
+        Scope::Around(a) => match &a.anchor {
+            SelectorOrRef::Ref(r) => {
+                if let Some(key) = selector_key(&r.reference) {
+                    selector_refs.push(key);
+                }
+            }
+            SelectorOrRef::Inline(inner) => collect_selector_step_refs(inner, step_refs, selector_refs),
+        },
+        Scope::Union(u) => u.scopes.iter().for_each(|s| collect_scope_step_refs(s, step_refs, selector_refs)),
+        Scope::Intersect(i) => i.scopes.iter().for_each(|s| collect_scope_step_refs(s, step_refs, selector_refs)),
+        Scope::Exclude(e) => {
+            collect_scope_step_refs(&e.base, step_refs, selector_refs);
+            collect_scope_step_refs(&e.exclude, step_refs, selector_refs);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use serde_json::{json, Value};
+
+    fn package(selectors: Value, steps: Value) -> Package {
+        fixtures::package().selectors(selectors).steps(steps).build()
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(String, DiagnosticCode)> {
+        diagnostics.iter().map(|d| (d.path.clone(), d.code)).collect()
+    }
+
+    fn menu_file() -> Value {
+        json!({
+            "strategy": "ocr",
+            "text": "文件",
+            "match": { "mode": "equals", "lang": "chi_sim" },
+            "scope": { "type": "band", "edge": "top", "ratio": 0.18 }
+        })
+    }
+
+    #[test]
+    fn test_valid_package_has_no_diagnostics() {
+        let pkg = package(
+            json!({ "menu_file": menu_file() }),
+            json!([
+                { "id": "s1", "op": "click", "target": { "$ref": "#/selectors/menu_file" } },
+                {
+                    "id": "s2",
+                    "op": "click",
+                    "target": {
+                        "strategy": "ocr",
+                        "text": "导出",
+                        "scope": { "type": "elementRef", "refStepId": "s1", "paddingPx": 20 }
+                    },
+                    "on_fail": { "action": "fallback_step_id", "stepId": "s1" }
+                }
+            ]),
+        );
+        assert!(pkg.validate().is_empty());
+    }
+
+    #[test]
+    fn test_dangling_ref_and_duplicate_ids() {
+        let pkg = package(
+            json!({}),
+            json!([
+                { "id": "s1", "op": "click", "target": { "$ref": "#/selectors/missing" } },
+                { "id": "s1", "op": "hotkey", "keys": ["cmd", "s"] }
+            ]),
+        );
+        assert_eq!(
+            codes(&pkg.validate()),
+            vec![
+                ("/steps/0/target/$ref".to_string(), DiagnosticCode::DanglingRef),
+                ("/steps/1/id".to_string(), DiagnosticCode::DuplicateStepId),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_fail_fallback_rules() {
+        let pkg = package(
+            json!({}),
+            json!([
+                { "id": "s1", "op": "hotkey", "keys": ["esc"], "on_fail": { "action": "fallback_step_id" } },
+                { "id": "s2", "op": "hotkey", "keys": ["esc"], "on_fail": { "action": "fallback_step_id", "stepId": "nope" } },
+                { "id": "s3", "op": "hotkey", "keys": ["esc"], "on_fail": { "action": "retry" } }
+            ]),
+        );
+        assert_eq!(
+            codes(&pkg.validate()),
+            vec![
+                ("/steps/0/on_fail/stepId".to_string(), DiagnosticCode::MissingFallbackStep),
+                ("/steps/1/on_fail/stepId".to_string(), DiagnosticCode::UnknownStepRef),
+                ("/steps/2/on_fail/action".to_string(), DiagnosticCode::InvalidEnum),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_element_ref_through_registry() {
+        let pkg = package(
+            json!({
+                "near_s2": {
+                    "strategy": "ocr",
+                    "text": "确定",
+                    "scope": { "type": "elementRef", "refStepId": "s2" }
+                }
+            }),
+            json!([
+                { "id": "s1", "op": "click", "target": { "$ref": "#/selectors/near_s2" } },
+                { "id": "s2", "op": "hotkey", "keys": ["enter"] },
+                {
+                    "id": "s3",
+                    "op": "click",
+                    "target": { "strategy": "ocr", "text": "x", "scope": { "type": "elementRef", "refStepId": "s3" } }
+                }
+            ]),
+        );
+        assert_eq!(
+            codes(&pkg.validate()),
+            vec![
+                ("/steps/0/target/$ref".to_string(), DiagnosticCode::ForwardStepRef),
+                ("/steps/2/target/scope/refStepId".to_string(), DiagnosticCode::ForwardStepRef),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enums_and_ranges() {
+        let pkg = package(
+            json!({
+                "multi_bad": {
+                    "strategy": "multi",
+                    "candidates": [
+                        { "strategy": "ocr", "text": "x", "match": { "mode": "fuzzy" } },
+                        { "strategy": "template", "template": "t.png", "match": { "threshold": 1.5 } }
+                    ],
+                    "pick": { "policy": "random" },
+                    "scope": { "type": "band", "edge": "middle", "ratio": -0.1 }
+                },
+                "rel": {
+                    "strategy": "relative",
+                    "anchor": { "$ref": "#/selectors/multi_bad" },
+                    "relation": { "type": "under" },
+                    "target": { "strategy": "ocr", "text": "y" }
+                }
+            }),
+            json!([
+                {
+                    "id": "s1",
+                    "op": "click",
+                    "target": { "$ref": "#/selectors/rel" },
+                    "fallback": { "point": { "x": 1.2, "y": 0.5 }, "policy": "sometimes" }
+                }
+            ]),
+        );
+        let found = codes(&pkg.validate());
+        let expect = [
+            ("/selectors/multi_bad/candidates/0/match/mode", DiagnosticCode::InvalidEnum),
+            ("/selectors/multi_bad/candidates/1/match/threshold", DiagnosticCode::OutOfRange),
+            ("/selectors/multi_bad/pick/policy", DiagnosticCode::InvalidEnum),
+            ("/selectors/multi_bad/scope/edge", DiagnosticCode::InvalidEnum),
+            ("/selectors/multi_bad/scope/ratio", DiagnosticCode::OutOfRange),
+            ("/selectors/rel/relation/type", DiagnosticCode::InvalidEnum),
+            ("/steps/0/fallback/policy", DiagnosticCode::InvalidEnum),
+            ("/steps/0/fallback/point/x", DiagnosticCode::OutOfRange),
+        ];
+        for (path, code) in expect {
+            assert!(found.contains(&(path.to_string(), code)), "missing {} {:?} in {:?}", path, code, found);
+        }
+    }
+
+    #[test]
+    fn test_unused_selector_is_warning() {
+        let pkg = package(
+            json!({ "menu_file": menu_file() }),
+            json!([{ "id": "s1", "op": "hotkey", "keys": ["cmd", "n"] }]),
+        );
+        let diagnostics = pkg.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::UnusedSelector);
+        assert!(!diagnostics[0].is_error());
+    }
+}
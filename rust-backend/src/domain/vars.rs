@@ -0,0 +1,382 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::domain::package::{Package, Scope, Selector, SelectorOrRef, StepOperation, VarDef};
+use crate::domain::validate::escape_pointer_token;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindError {
+    /// A declared variable has no default and the caller did not supply it.
+    MissingVar { name: String },
+    /// The caller supplied a value for a variable the package does not declare.
+    UndeclaredVar { name: String },
+    /// A value (supplied or default) does not fit `VarDef.var_type`.
+    TypeMismatch { name: String, expected: String, found: Value },
+    /// A `{{NAME}}` in a template field that is not declared in `vars`.
+    UnknownPlaceholder { path: String, name: String },
+    /// `{{` without a matching `}}`.
+    Unterminated { path: String },
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindError::MissingVar { name } => write!(f, "variable '{}' is required", name),
+            BindError::UndeclaredVar { name } => write!(f, "variable '{}' is not declared by the package", name),
+            BindError::TypeMismatch { name, expected, found } => {
+                write!(f, "variable '{}' expects a {}, got {}", name, expected, found)
+            }
+            BindError::UnknownPlaceholder { path, name } => {
+                write!(f, "{}: placeholder '{{{{{}}}}}' has no matching variable", path, name)
+            }
+            BindError::Unterminated { path } => write!(f, "{}: unterminated '{{{{' placeholder", path),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+impl Package {
+    /// Substitutes `{{VAR}}` placeholders with caller values or `VarDef` defaults.
+    ///
+    /// Templated fields are step `type` text, OCR text and regex, template
+    /// paths and window/dialog titles. Values substituted into a field that is
+    /// matched as a regex are escaped. All problems are reported together.
+    pub fn bind(&self, values: &HashMap<String, Value>) -> Result<Package, Vec<BindError>> {
+        let mut errors = Vec::new();
+
+        let mut supplied: Vec<&String> = values.keys().filter(|k| !self.vars.contains_key(*k)).collect();
+        supplied.sort();
+        for name in supplied {
+            errors.push(BindError::UndeclaredVar { name: name.clone() });
+        }
+
+        let mut names: Vec<&String> = self.vars.keys().collect();
+        names.sort();
+        let mut bound = HashMap::new();
+        for name in names {
+            let def = &self.vars[name];
+            let value = match values.get(name) {
+                Some(v) => v,
+                None => match &def.default {
+                    Some(v) if !v.is_null() => v,
+                    _ => {
+                        errors.push(BindError::MissingVar { name: name.clone() });
+                        continue;
+                    }
+                },
+            };
+            match render_value(def, value) {
+                Some(text) => {
+                    bound.insert(name.clone(), text);
+                }
+                None => errors.push(BindError::TypeMismatch {
+                    name: name.clone(),
+                    expected: def.var_type.clone(),
+                    found: value.clone(),
+                }),
+            }
+        }
+
+        let mut pkg = self.clone();
+        let mut binder = Binder { pkg: self, bound: &bound, errors: &mut errors };
+
+        let mut keys: Vec<String> = pkg.selectors.keys().cloned().collect();
+        keys.sort();
+        for key in keys {
+            let path = format!("/selectors/{}", escape_pointer_token(&key));
+            if let Some(selector) = pkg.selectors.get_mut(&key) {
+                binder.selector(selector, &path);
+            }
+        }
+        for (i, step) in pkg.steps.iter_mut().enumerate() {
+            let path = format!("/steps/{}", i);
+            if let Some(scope) = &mut step.scope {
+                binder.scope(scope, &format!("{}/scope", path));
+            }
+            match &mut step.op {
+                StepOperation::Click(c) => binder.sor(&mut c.target, &format!("{}/target", path)),
+                StepOperation::Drag(d) => {
+                    binder.sor(&mut d.from, &format!("{}/from", path));
+                    if let Some(to) = &mut d.to {
+                        binder.sor(to, &format!("{}/to", path));
+                    }
+                }
+                StepOperation::Type(t) => {
+                    binder.text(&mut t.text, &format!("{}/text", path));
+                    if let Some(target) = &mut t.target {
+                        binder.sor(target, &format!("{}/target", path));
+                    }
+                }
+                StepOperation::Scroll(s) => {
+                    if let Some(target) = &mut s.target {
+                        binder.sor(target, &format!("{}/target", path));
+                    }
+                }
+                StepOperation::Hotkey(_) => {}
+                StepOperation::Wait(w) => binder.sor(&mut w.until, &format!("{}/until", path)),
+                StepOperation::Assert(a) => binder.sor(&mut a.expect, &format!("{}/expect", path)),
+            }
+        }
+
+        if errors.is_empty() { Ok(pkg) } else { Err(errors) }
+    }
+}
+
+/// Renders a value as template text, or `None` when it does not match the declared type.
+fn render_value(def: &VarDef, value: &Value) -> Option<String> {
+    match (def.var_type.as_str(), value) {
+        ("string" | "path", Value::String(s)) => Some(s.clone()),
+        ("number", Value::Number(n)) => Some(n.to_string()),
+        ("boolean", Value::Bool(b)) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+struct Binder<'a> {
+    pkg: &'a Package,
+    bound: &'a HashMap<String, String>,
+    errors: &'a mut Vec<BindError>,
+}
+
+impl Binder<'_> {
+    fn text(&mut self, text: &mut String, path: &str) {
+        self.substitute(text, path, false);
+    }
+
+    /// Like [`Binder::text`] for fields the engine compiles as a regex, so
+    /// bound values match literally.
+    fn pattern(&mut self, text: &mut String, path: &str) {
+        self.substitute(text, path, true);
+    }
+
+    fn substitute(&mut self, text: &mut String, path: &str, escape: bool) {
+        if !text.contains("{{") {
+            return;
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                self.errors.push(BindError::Unterminated { path: path.to_string() });
+                return;
+            };
+            let name = after[..end].trim();
+            match self.bound.get(name) {
+                Some(value) if escape => out.push_str(&regex::escape(value)),
+                Some(value) => out.push_str(value),
+                None => {
+                    // Declared but unbound vars were already reported as missing or mistyped.
+                    if !self.pkg.vars.contains_key(name) {
+                        self.errors.push(BindError::UnknownPlaceholder {
+                            path: path.to_string(),
+                            name: name.to_string(),
+                        });
+                    }
+                }
+            }
+            rest = &after[end + 2..];
+        }
+        out.push_str(rest);
+        *text = out;
+    }
+
+    fn sor(&mut self, sor: &mut SelectorOrRef, path: &str) {
+        if let SelectorOrRef::Inline(selector) = sor {
+            self.selector(selector, path);
+        }
+    }
+
+    fn selector(&mut self, selector: &mut Selector, path: &str) {
+        let scope = match selector {
+            Selector::OCR(s) => {
+                // In regex mode without a `regex` field the text itself is the pattern
+                let text_is_pattern = s.match_options.as_ref().is_some_and(|m| m.mode == "regex" && m.regex.is_none());
+                if text_is_pattern {
+                    self.pattern(&mut s.text, &format!("{}/text", path));
+                } else {
+                    self.text(&mut s.text, &format!("{}/text", path));
+                }
+                if let Some(regex) = s.match_options.as_mut().and_then(|m| m.regex.as_mut()) {
+                    self.pattern(regex, &format!("{}/match/regex", path));
+                }
+                &mut s.scope
+            }
+            Selector::Template(s) => {
+                self.text(&mut s.template, &format!("{}/template", path));
+                &mut s.scope
+            }
+            Selector::Relative(s) => {
+                self.sor(&mut s.anchor, &format!("{}/anchor", path));
+                self.sor(&mut s.target, &format!("{}/target", path));
+                &mut s.scope
+            }
+            Selector::Multi(s) => {
+                for (i, candidate) in s.candidates.iter_mut().enumerate() {
+                    self.sor(candidate, &format!("{}/candidates/{}", path, i));
+                }
+                &mut s.scope
+            }
+        };
+        if let Some(scope) = scope {
+            self.scope(scope, &format!("{}/scope", path));
+        }
+    }
+
+    fn scope(&mut self, scope: &mut Scope, path: &str) {
+        match scope {
+            Scope::Window(w) => {
+                if let Some(title) = &mut w.title {
+                    if w.match_mode == "regex" {
+                        self.pattern(title, &format!("{}/title", path));
+                    } else {
+                        self.text(title, &format!("{}/title", path));
+                    }
+                }
+            }
+            Scope::Dialog(d) => {
+                if let Some(title) = &mut d.title {
+                    if d.match_mode == "regex" {
+                        self.pattern(title, &format!("{}/title", path));
+                    } else {
+                        self.text(title, &format!("{}/title", path));
+                    }
+                }
+            }
+            Scope::Around(a) => self.sor(&mut a.anchor, &format!("{}/anchor", path)),
+            Scope::Union(u) => {
+                for (i, s) in u.scopes.iter_mut().enumerate() {
+                    self.scope(s, &format!("{}/scopes/{}", path, i));
+                }
+            }
+            Scope::Intersect(x) => {
+                for (i, s) in x.scopes.iter_mut().enumerate() {
+                    self.scope(s, &format!("{}/scopes/{}", path, i));
+                }
+            }
+            Scope::Exclude(e) => {
+                self.scope(&mut e.base, &format!("{}/base", path));
+                self.scope(&mut e.exclude, &format!("{}/exclude", path));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn package() -> Package {
+        serde_json::from_value(json!({
+            "version": "0.1",
+            "package": { "name": "Export", "createdAt": "2026-01-01T00:00:00+09:00" },
+            "app": { "name": "Photoshop" },
+            "vars": {
+                "FILE_NAME": { "type": "string", "default": "output" },
+                "EXPORT_DIR": { "type": "path" },
+                "QUALITY": { "type": "number", "default": 80 }
+            },
+            "selectors": {
+                "dialog_title": {
+                    "strategy": "ocr",
+                    "text": "导出到 {{ EXPORT_DIR }}",
+                    "scope": { "type": "dialog", "role": "byTitle", "title": "{{FILE_NAME}} - 导出" }
+                },
+                "icon": { "strategy": "template", "template": "assets/{{EXPORT_DIR}}/icon.png" }
+            },
+            "steps": [
+                { "id": "s1", "op": "wait", "until": { "$ref": "#/selectors/dialog_title" } },
+                { "id": "s2", "op": "type", "text": "{{FILE_NAME}}@{{QUALITY}}" },
+                { "id": "s3", "op": "click", "target": { "$ref": "#/selectors/icon" } }
+            ]
+        }))
+        .expect("fixture should parse")
+    }
+
+    #[test]
+    fn test_bind_fills_values_and_defaults() {
+        let values = HashMap::from([("EXPORT_DIR".to_string(), json!("/tmp/out"))]);
+        let pkg = package().bind(&values).expect("should bind");
+
+        let StepOperation::Type(t) = &pkg.steps[1].op else { panic!("expected type step") };
+        assert_eq!(t.text, "output@80");
+
+        let Selector::OCR(ocr) = &pkg.selectors["dialog_title"] else { panic!("expected ocr") };
+        assert_eq!(ocr.text, "导出到 /tmp/out");
+        let Some(Scope::Dialog(dialog)) = &ocr.scope else { panic!("expected dialog scope") };
+        assert_eq!(dialog.title.as_deref(), Some("output - 导出"));
+
+        let Selector::Template(template) = &pkg.selectors["icon"] else { panic!("expected template") };
+        assert_eq!(template.template, "assets//tmp/out/icon.png");
+    }
+
+    #[test]
+    fn test_bind_escapes_values_in_regex_fields() {
+        let mut pkg = package();
+        pkg.selectors.insert(
+            "saved".to_string(),
+            serde_json::from_value(json!({
+                "strategy": "ocr",
+                "text": "{{FILE_NAME}}",
+                "match": { "mode": "regex", "regex": "^{{FILE_NAME}}$" },
+                "scope": { "type": "window", "mode": "byTitle", "title": "{{FILE_NAME}} \\(\\d+\\)", "match": "regex" }
+            }))
+            .unwrap(),
+        );
+        let values = HashMap::from([
+            ("FILE_NAME".to_string(), json!("a.b (1)*")),
+            ("EXPORT_DIR".to_string(), json!("/tmp/out")),
+        ]);
+        let pkg = pkg.bind(&values).expect("should bind");
+
+        let Selector::OCR(ocr) = &pkg.selectors["saved"] else { panic!("expected ocr") };
+        assert_eq!(ocr.text, "a.b (1)*");
+        let regex = ocr.match_options.as_ref().and_then(|m| m.regex.as_deref()).unwrap();
+        assert_eq!(regex, "^a\\.b \\(1\\)\\*$");
+        assert!(regex::Regex::new(regex).unwrap().is_match("a.b (1)*"));
+        let Some(Scope::Window(window)) = &ocr.scope else { panic!("expected window scope") };
+        assert_eq!(window.title.as_deref(), Some("a\\.b \\(1\\)\\* \\(\\d+\\)"));
+    }
+
+    #[test]
+    fn test_bind_reports_missing_mistyped_and_undeclared() {
+        let values = HashMap::from([
+            ("QUALITY".to_string(), json!("high")),
+            ("COLOR".to_string(), json!("red")),
+        ]);
+        let errors = package().bind(&values).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                BindError::UndeclaredVar { name: "COLOR".to_string() },
+                BindError::MissingVar { name: "EXPORT_DIR".to_string() },
+                BindError::TypeMismatch { name: "QUALITY".to_string(), expected: "number".to_string(), found: json!("high") },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bind_rejects_unknown_placeholders() {
+        let mut pkg = package();
+        pkg.vars.clear();
+        let errors = pkg.bind(&HashMap::new()).unwrap_err();
+        assert!(errors.contains(&BindError::UnknownPlaceholder {
+            path: "/steps/1/text".to_string(),
+            name: "FILE_NAME".to_string(),
+        }));
+        assert!(errors.contains(&BindError::UnknownPlaceholder {
+            path: "/selectors/dialog_title/scope/title".to_string(),
+            name: "FILE_NAME".to_string(),
+        }));
+
+        let StepOperation::Type(t) = &mut pkg.steps[1].op else { panic!("expected type step") };
+        t.text = "{{oops".to_string();
+        let errors = pkg.bind(&HashMap::new()).unwrap_err();
+        assert!(errors.contains(&BindError::Unterminated { path: "/steps/1/text".to_string() }));
+    }
+}
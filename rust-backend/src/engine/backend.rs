@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::domain::package::{Scope, Selector};
+use crate::engine::geometry::{Point, Rect, Size};
+
+/// A located element on the current frame.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Match {
+    pub rect: Rect,
+    pub confidence: f64,
+}
+
+/// What the interpreter knows when it asks the screen for a selector.
+pub struct LocateContext<'a> {
+    /// Step-level scope, applied when the selector has none of its own.
+    pub step_scope: Option<&'a Scope>,
+    /// Elements matched by earlier steps, keyed by step id (for `elementRef` scopes).
+    pub elements: &'a HashMap<String, Rect>,
+}
+
+/// Read side of a desktop: observing frames and passing time.
+pub trait Screen {
+    fn size(&self) -> Size;
+
+    /// Finds the best match for a fully resolved selector on the current frame.
+    fn locate(&mut self, selector: &Selector, ctx: &LocateContext<'_>) -> Option<Match>;
+
+    /// Milliseconds since the run started.
+    fn now_ms(&self) -> u64;
+
+    fn sleep(&mut self, ms: u32);
+}
+
+/// Write side of a desktop: synthesised mouse and keyboard events.
+pub trait Input {
+    fn click(&mut self, at: Point, button: &str, count: u32) -> Result<(), String>;
+    fn drag(&mut self, from: Point, to: Point, duration_ms: u32) -> Result<(), String>;
+    fn type_text(&mut self, text: &str, clear_first: bool, delay_per_char_ms: u32) -> Result<(), String>;
+    fn scroll(&mut self, at: Point, direction: &str, amount: u32, steps: u32) -> Result<(), String>;
+    fn hotkey(&mut self, keys: &[String]) -> Result<(), String>;
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::package::Selector;
+use crate::engine::backend::{Input, LocateContext, Match, Screen};
+use crate::engine::geometry::{Point, Size};
+use crate::engine::matcher::{Frame, SelectorMatcher};
+
+/// A recorded screen session: one entry per observable frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub width: f64,
+    pub height: f64,
+    pub frames: Vec<Frame>,
+}
+
+/// An input event the fake received, in order.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedAction {
+    Click { at: Point, button: String, count: u32 },
+    Drag { from: Point, to: Point },
+    Type { text: String, clear_first: bool },
+    Scroll { at: Point, direction: String, amount: u32 },
+    Hotkey { keys: Vec<String> },
+}
+
+/// Deterministic desktop that replays a [`Fixture`].
+///
+/// Every input action and every `sleep` moves to the next frame (staying on
+/// the last one), so a fixture lists frames in the order the run observes them.
+pub struct FakeDesktop {
+    fixture: Fixture,
+    frame: usize,
+    clock_ms: u64,
+    pub actions: Vec<RecordedAction>,
+}
+
+impl FakeDesktop {
+    pub fn new(fixture: Fixture) -> Self {
+        Self { fixture, frame: 0, clock_ms: 0, actions: Vec::new() }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        Ok(Self::new(serde_json::from_str(json)?))
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    fn current(&self) -> Option<&Frame> {
+        self.fixture.frames.get(self.frame)
+    }
+
+    fn advance(&mut self) {
+        if self.frame + 1 < self.fixture.frames.len() {
+            self.frame += 1;
+        }
+    }
+
+    fn record(&mut self, action: RecordedAction) -> Result<(), String> {
+        self.actions.push(action);
+        self.advance();
+        Ok(())
+    }
+}
+
+impl Screen for FakeDesktop {
+    fn size(&self) -> Size {
+        Size { width: self.fixture.width, height: self.fixture.height }
+    }
+
+    fn locate(&mut self, selector: &Selector, ctx: &LocateContext<'_>) -> Option<Match> {
+        let frame = self.current()?;
+        let matcher = SelectorMatcher::new(frame, self.size(), ctx.elements);
+        matcher.find(selector, ctx.step_scope).ok()?.into_iter().next()
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.clock_ms
+    }
+
+    fn sleep(&mut self, ms: u32) {
+        self.clock_ms += u64::from(ms);
+        self.advance();
+    }
+}
+
+impl Input for FakeDesktop {
+    fn click(&mut self, at: Point, button: &str, count: u32) -> Result<(), String> {
+        self.record(RecordedAction::Click { at, button: button.to_string(), count })
+    }
+
+    fn drag(&mut self, from: Point, to: Point, _duration_ms: u32) -> Result<(), String> {
+        self.record(RecordedAction::Drag { from, to })
+    }
+
+    fn type_text(&mut self, text: &str, clear_first: bool, _delay_per_char_ms: u32) -> Result<(), String> {
+        self.record(RecordedAction::Type { text: text.to_string(), clear_first })
+    }
+
+    fn scroll(&mut self, at: Point, direction: &str, amount: u32, _steps: u32) -> Result<(), String> {
+        self.record(RecordedAction::Scroll { at, direction: direction.to_string(), amount })
+    }
+
+    fn hotkey(&mut self, keys: &[String]) -> Result<(), String> {
+        self.record(RecordedAction::Hotkey { keys: keys.to_vec() })
+    }
+}
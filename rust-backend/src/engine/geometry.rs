@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+
+/// A point in screen pixels.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// An axis-aligned rectangle in screen pixels, origin at the top-left.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, w: f64, h: f64) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn center(&self) -> Point {
+        Point { x: self.x + self.w / 2.0, y: self.y + self.h / 2.0 }
+    }
+
+    pub fn right(&self) -> f64 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.h
+    }
+
+    pub fn area(&self) -> f64 {
+        self.w.max(0.0) * self.h.max(0.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.w <= 0.0 || self.h <= 0.0
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.x >= self.x && p.x < self.right() && p.y >= self.y && p.y < self.bottom()
+    }
+
+    /// Grows the rect by `by` pixels on every side.
+    pub fn inflate(&self, by: f64) -> Rect {
+        Rect::new(self.x - by, self.y - by, self.w + 2.0 * by, self.h + 2.0 * by)
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let r = Rect::new(x, y, self.right().min(other.right()) - x, self.bottom().min(other.bottom()) - y);
+        if r.is_empty() { None } else { Some(r) }
+    }
+
+    /// `self` minus `other`, as up to four disjoint rects (top, bottom, left, right bands).
+    pub fn subtract(&self, other: &Rect) -> Vec<Rect> {
+        let Some(cut) = self.intersect(other) else {
+            return if self.is_empty() { Vec::new() } else { vec![*self] };
+        };
+        let pieces = [
+            Rect::new(self.x, self.y, self.w, cut.y - self.y),
+            Rect::new(self.x, cut.bottom(), self.w, self.bottom() - cut.bottom()),
+            Rect::new(self.x, cut.y, cut.x - self.x, cut.h),
+            Rect::new(cut.right(), cut.y, self.right() - cut.right(), cut.h),
+        ];
+        pieces.into_iter().filter(|r| !r.is_empty()).collect()
+    }
+}
+
+/// A set of pixels described by pairwise-disjoint rectangles.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Region {
+    rects: Vec<Rect>,
+}
+
+impl Region {
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rect(rect: Rect) -> Self {
+        if rect.is_empty() { Self::empty() } else { Self { rects: vec![rect] } }
+    }
+
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn area(&self) -> f64 {
+        self.rects.iter().map(Rect::area).sum()
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.rects.iter().any(|r| r.contains(p))
+    }
+
+    /// Smallest rect covering the whole region.
+    pub fn bounds(&self) -> Option<Rect> {
+        let first = self.rects.first()?;
+        let (mut x0, mut y0, mut x1, mut y1) = (first.x, first.y, first.right(), first.bottom());
+        for r in &self.rects[1..] {
+            x0 = x0.min(r.x);
+            y0 = y0.min(r.y);
+            x1 = x1.max(r.right());
+            y1 = y1.max(r.bottom());
+        }
+        Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+    }
+
+    pub fn union(&self, other: &Region) -> Region {
+        // Keep `self` as is and add only the parts of `other` it does not cover.
+        let mut rects = self.rects.clone();
+        rects.extend(other.subtract(self).rects);
+        Region { rects }
+    }
+
+    pub fn intersect(&self, other: &Region) -> Region {
+        let rects = self
+            .rects
+            .iter()
+            .flat_map(|a| other.rects.iter().filter_map(move |b| a.intersect(b)))
+            .collect();
+        Region { rects }
+    }
+
+    pub fn subtract(&self, other: &Region) -> Region {
+        let mut rects = self.rects.clone();
+        for cut in &other.rects {
+            rects = rects.iter().flat_map(|r| r.subtract(cut)).collect();
+        }
+        Region { rects }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn rect() -> impl Strategy<Value = Rect> {
+        (0..50i32, 0..50i32, 0..30i32, 0..30i32)
+            .prop_map(|(x, y, w, h)| Rect::new(f64::from(x), f64::from(y), f64::from(w), f64::from(h)))
+    }
+
+    fn region() -> impl Strategy<Value = Region> {
+        prop::collection::vec(rect(), 0..4)
+            .prop_map(|rects| rects.into_iter().fold(Region::empty(), |acc, r| acc.union(&Region::from_rect(r))))
+    }
+
+    fn disjoint(region: &Region) -> bool {
+        let rects = region.rects();
+        rects.iter().enumerate().all(|(i, a)| rects[i + 1..].iter().all(|b| a.intersect(b).is_none()))
+    }
+
+    /// Integer sample points covering the generated coordinate space.
+    fn samples() -> impl Iterator<Item = Point> {
+        (0..80).flat_map(|x| (0..80).map(move |y| Point { x: f64::from(x) + 0.5, y: f64::from(y) + 0.5 }))
+    }
+
+    #[test]
+    fn test_subtract_center_hole() {
+        let outer = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let pieces = outer.subtract(&Rect::new(4.0, 4.0, 2.0, 2.0));
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces.iter().map(Rect::area).sum::<f64>(), 96.0);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_results_stay_disjoint(a in region(), b in region()) {
+            prop_assert!(disjoint(&a));
+            prop_assert!(disjoint(&a.union(&b)));
+            prop_assert!(disjoint(&a.intersect(&b)));
+            prop_assert!(disjoint(&a.subtract(&b)));
+        }
+
+        #[test]
+        fn prop_membership_matches_set_semantics(a in region(), b in region()) {
+            let (union, inter, diff) = (a.union(&b), a.intersect(&b), a.subtract(&b));
+            for p in samples() {
+                let (in_a, in_b) = (a.contains(p), b.contains(p));
+                prop_assert_eq!(union.contains(p), in_a || in_b);
+                prop_assert_eq!(inter.contains(p), in_a && in_b);
+                prop_assert_eq!(diff.contains(p), in_a && !in_b);
+            }
+        }
+
+        #[test]
+        fn prop_inclusion_exclusion(a in region(), b in region()) {
+            let lhs = a.union(&b).area();
+            let rhs = a.area() + b.area() - a.intersect(&b).area();
+            prop_assert!((lhs - rhs).abs() < 1e-6);
+            prop_assert!((a.subtract(&b).area() + a.intersect(&b).area() - a.area()).abs() < 1e-6);
+        }
+
+        #[test]
+        fn prop_commutative_areas(a in region(), b in region()) {
+            prop_assert!((a.union(&b).area() - b.union(&a).area()).abs() < 1e-6);
+            prop_assert!((a.intersect(&b).area() - b.intersect(&a).area()).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::domain::package::{Fallback, Scope, Selector, SelectorOrRef, Step, StepOperation};
+use crate::domain::resolve::ResolvedPackage;
+use crate::engine::backend::{Input, LocateContext, Match, Screen};
+use crate::engine::geometry::{Point, Rect};
+
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Upper bound on step executions, so `fallback_step_id` loops terminate.
+    pub max_step_runs: usize,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self { max_step_runs: 200 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StepOutcome {
+    Passed,
+    /// Failed with `on_fail.action = skip`.
+    Skipped,
+    /// Failed and jumped to `on_fail.stepId`.
+    Jumped { to: String },
+    Aborted,
+}
+
+/// One execution of one step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepTrace {
+    pub step_id: String,
+    pub attempts: u32,
+    pub outcome: StepOutcome,
+    /// The element the step acted on, if it located one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element: Option<Match>,
+    /// True when the step acted on `fallback.point` instead of a located element.
+    pub used_fallback_point: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RunStatus {
+    Completed,
+    Aborted { step_id: String, reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub status: RunStatus,
+    pub trace: Vec<StepTrace>,
+}
+
+/// Result of a single attempt at a step.
+struct Acted {
+    element: Option<Match>,
+    used_fallback_point: bool,
+}
+
+/// Walks `pkg.steps` against a desktop, honouring `retry`, `on_fail` and `fallback`.
+///
+/// The package must already be bound (see `Package::bind`) and resolved.
+pub fn run<B: Screen + Input>(pkg: &ResolvedPackage, backend: &mut B, options: &RunOptions) -> RunReport {
+    Interpreter { backend, elements: HashMap::new() }.run(&pkg.steps, options)
+}
+
+struct Interpreter<'b, B> {
+    backend: &'b mut B,
+    elements: HashMap<String, Rect>,
+}
+
+impl<B: Screen + Input> Interpreter<'_, B> {
+    fn run(mut self, steps: &[Step], options: &RunOptions) -> RunReport {
+        let mut trace = Vec::new();
+        let mut index = 0;
+
+        while index < steps.len() {
+            let step = &steps[index];
+            if trace.len() >= options.max_step_runs {
+                return RunReport {
+                    status: RunStatus::Aborted {
+                        step_id: step.id.clone(),
+                        reason: format!("exceeded {} step executions", options.max_step_runs),
+                    },
+                    trace,
+                };
+            }
+
+            let started_at_ms = self.backend.now_ms();
+            let (attempts, result) = self.execute_with_retry(step);
+            let mut entry = StepTrace {
+                step_id: step.id.clone(),
+                attempts,
+                outcome: StepOutcome::Passed,
+                element: None,
+                used_fallback_point: false,
+                error: None,
+                started_at_ms,
+                finished_at_ms: self.backend.now_ms(),
+            };
+
+            match result {
+                Ok(acted) => {
+                    if let Some(element) = acted.element {
+                        self.elements.insert(step.id.clone(), element.rect);
+                    }
+                    entry.element = acted.element;
+                    entry.used_fallback_point = acted.used_fallback_point;
+                    trace.push(entry);
+                    index += 1;
+                }
+                Err(error) => {
+                    entry.error = Some(error.clone());
+                    let action = step.on_fail.as_ref().map(|f| f.action.as_str()).unwrap_or("abort");
+                    let jump = step.on_fail.as_ref().and_then(|f| f.step_id.as_ref());
+                    match (action, jump) {
+                        ("skip", _) => {
+                            entry.outcome = StepOutcome::Skipped;
+                            trace.push(entry);
+                            index += 1;
+                        }
+                        ("fallback_step_id", Some(to)) if steps.iter().any(|s| s.id == *to) => {
+                            entry.outcome = StepOutcome::Jumped { to: to.clone() };
+                            trace.push(entry);
+                            index = steps.iter().position(|s| s.id == *to).unwrap_or(index);
+                        }
+                        _ => {
+                            entry.outcome = StepOutcome::Aborted;
+                            trace.push(entry);
+                            let reason = step
+                                .on_fail
+                                .as_ref()
+                                .and_then(|f| f.reason.clone())
+                                .unwrap_or(error);
+                            return RunReport {
+                                status: RunStatus::Aborted { step_id: step.id.clone(), reason },
+                                trace,
+                            };
+                        }
+                    }
+                }
+            }
+        }
+
+        RunReport { status: RunStatus::Completed, trace }
+    }
+
+    /// Runs up to `retry.times + 1` attempts, sleeping `intervalMs` between them
+    /// and giving up early once another attempt would start past `timeoutMs`.
+    fn execute_with_retry(&mut self, step: &Step) -> (u32, Result<Acted, String>) {
+        let (times, interval_ms, timeout_ms) = step
+            .retry
+            .as_ref()
+            .map(|r| (r.times, r.interval_ms, r.timeout_ms))
+            .unwrap_or((0, 0, 0));
+        let started = self.backend.now_ms();
+        let max_attempts = times + 1;
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let elapsed = self.backend.now_ms() - started;
+            let out_of_time = timeout_ms > 0 && elapsed + u64::from(interval_ms) >= u64::from(timeout_ms);
+            let last = attempt >= max_attempts || out_of_time;
+
+            match self.attempt(step, last) {
+                Ok(acted) => return (attempt, Ok(acted)),
+                Err(error) if last => return (attempt, Err(error)),
+                Err(_) => self.backend.sleep(interval_ms),
+            }
+        }
+    }
+
+    fn attempt(&mut self, step: &Step, last: bool) -> Result<Acted, String> {
+        let scope = step.scope.as_ref();
+        match &step.op {
+            StepOperation::Click(click) => {
+                let (at, acted) = self.target_point(&click.target, scope, click.fallback.as_ref(), last)?;
+                let params = click.params.as_ref();
+                let button = params.map(|p| p.button.as_str()).unwrap_or("left");
+                let count = params.map(|p| p.click_count).unwrap_or(1);
+                let at = match params.and_then(|p| p.offset.as_ref()) {
+                    Some(offset) => Point { x: at.x + f64::from(offset.dx), y: at.y + f64::from(offset.dy) },
+                    None => at,
+                };
+                self.backend.click(at, button, count)?;
+                Ok(acted)
+            }
+            StepOperation::Drag(drag) => {
+                let (from, acted) = self.target_point(&drag.from, scope, drag.fallback.as_ref(), last)?;
+                let to = if let Some(to) = &drag.to {
+                    self.locate(to, scope).ok_or("drag destination not found")?.rect.center()
+                } else if let Some(vector) = &drag.vector {
+                    let d = f64::from(vector.distance_px);
+                    match vector.direction.as_str() {
+                        "up" => Point { x: from.x, y: from.y - d },
+                        "down" => Point { x: from.x, y: from.y + d },
+                        "left" => Point { x: from.x - d, y: from.y },
+                        "right" => Point { x: from.x + d, y: from.y },
+                        other => return Err(format!("unknown drag direction '{}'", other)),
+                    }
+                } else {
+                    return Err("drag needs either 'to' or 'vector'".to_string());
+                };
+                let duration = drag.params.as_ref().map(|p| p.duration_ms).unwrap_or(250);
+                self.backend.drag(from, to, duration)?;
+                Ok(acted)
+            }
+            StepOperation::Type(t) => {
+                let mut acted = Acted { element: None, used_fallback_point: false };
+                if let Some(target) = &t.target {
+                    let (at, focused) = self.target_point(target, scope, None, last)?;
+                    self.backend.click(at, "left", 1)?;
+                    acted = focused;
+                }
+                let (clear_first, delay) = t
+                    .params
+                    .as_ref()
+                    .map(|p| (p.clear_first, p.delay_per_char_ms))
+                    .unwrap_or((false, 0));
+                self.backend.type_text(&t.text, clear_first, delay)?;
+                Ok(acted)
+            }
+            StepOperation::Scroll(scroll) => {
+                let (at, acted) = match &scroll.target {
+                    Some(target) => self.target_point(target, scope, None, last)?,
+                    None => {
+                        let size = self.backend.size();
+                        let center = Point { x: size.width / 2.0, y: size.height / 2.0 };
+                        (center, Acted { element: None, used_fallback_point: false })
+                    }
+                };
+                let steps = scroll.params.as_ref().map(|p| p.steps).unwrap_or(1);
+                self.backend.scroll(at, &scroll.delta.direction, scroll.delta.amount, steps)?;
+                Ok(acted)
+            }
+            StepOperation::Hotkey(hotkey) => {
+                self.backend.hotkey(&hotkey.keys)?;
+                Ok(Acted { element: None, used_fallback_point: false })
+            }
+            StepOperation::Wait(wait) => {
+                let (mode, min_confidence) = wait
+                    .params
+                    .as_ref()
+                    .map(|p| (p.mode.as_str(), p.min_confidence))
+                    .unwrap_or(("appear", 0.6));
+                let found = self.locate(&wait.until, scope).filter(|m| m.confidence >= min_confidence);
+                match (mode, found) {
+                    ("disappear", None) => Ok(Acted { element: None, used_fallback_point: false }),
+                    ("disappear", Some(_)) => Err("element is still visible".to_string()),
+                    (_, Some(m)) => Ok(Acted { element: Some(m), used_fallback_point: false }),
+                    (_, None) => Err("element did not appear".to_string()),
+                }
+            }
+            StepOperation::Assert(assert) => {
+                let (min_confidence, negate) = assert
+                    .params
+                    .as_ref()
+                    .map(|p| (p.min_confidence, p.negate))
+                    .unwrap_or((0.65, false));
+                let found = self.locate(&assert.expect, scope).filter(|m| m.confidence >= min_confidence);
+                match (negate, found) {
+                    (false, Some(m)) => Ok(Acted { element: Some(m), used_fallback_point: false }),
+                    (false, None) => Err("expected element not found".to_string()),
+                    (true, None) => Ok(Acted { element: None, used_fallback_point: false }),
+                    (true, Some(_)) => Err("element present but asserted absent".to_string()),
+                }
+            }
+        }
+    }
+
+    /// Locates a target, falling back to `fallback.point` when the policy allows it.
+    fn target_point(
+        &mut self,
+        target: &SelectorOrRef,
+        scope: Option<&Scope>,
+        fallback: Option<&Fallback>,
+        last: bool,
+    ) -> Result<(Point, Acted), String> {
+        if let Some(m) = self.locate(target, scope) {
+            return Ok((m.rect.center(), Acted { element: Some(m), used_fallback_point: false }));
+        }
+        match fallback {
+            Some(f) if f.policy == "always" || (f.policy == "last_retry_only" && last) => {
+                let point = if f.point.normalized {
+                    let size = self.backend.size();
+                    Point { x: f.point.x * size.width, y: f.point.y * size.height }
+                } else {
+                    Point { x: f.point.x, y: f.point.y }
+                };
+                Ok((point, Acted { element: None, used_fallback_point: true }))
+            }
+            _ => Err("target not found".to_string()),
+        }
+    }
+
+    fn locate(&mut self, sor: &SelectorOrRef, step_scope: Option<&Scope>) -> Option<Match> {
+        let selector: &Selector = match sor {
+            SelectorOrRef::Inline(selector) => selector,
+            // A resolved package has no refs left.
+            SelectorOrRef::Ref(_) => return None,
+        };
+        let ctx = LocateContext { step_scope, elements: &self.elements };
+        self.backend.locate(selector, &ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use crate::engine::fake::{FakeDesktop, RecordedAction};
+    use serde_json::{json, Value};
+
+    fn resolved(steps: Value) -> ResolvedPackage {
+        let pkg = fixtures::package()
+            .selectors(json!({
+                "menu_file": { "strategy": "ocr", "text": "文件", "match": { "mode": "equals" } },
+                "dialog": { "strategy": "ocr", "text": "导出" },
+                "export_icon": { "strategy": "template", "template": "assets/templates/export_icon.png" }
+            }))
+            .steps(steps)
+            .build();
+        pkg.resolve().expect("fixture should resolve")
+    }
+
+    fn desktop(frames: Value) -> FakeDesktop {
+        FakeDesktop::from_json(&json!({ "width": 1000.0, "height": 800.0, "frames": frames }).to_string())
+            .expect("fixture should parse")
+    }
+
+    fn ocr(text: &str, x: f64, y: f64) -> Value {
+        json!({ "text": text, "rect": { "x": x, "y": y, "w": 40.0, "h": 20.0 }, "confidence": 0.9 })
+    }
+
+    #[test]
+    fn test_runs_steps_in_order() {
+        let pkg = resolved(json!([
+            { "id": "s1", "op": "click", "target": { "$ref": "#/selectors/menu_file" } },
+            { "id": "s2", "op": "wait", "until": { "$ref": "#/selectors/dialog" } },
+            { "id": "s3", "op": "type", "text": "output", "params": { "clearFirst": true } },
+            { "id": "s4", "op": "hotkey", "keys": ["enter"] }
+        ]));
+        let mut fake = desktop(json!([
+            { "ocr": [ocr("文件", 10.0, 0.0)] },
+            { "ocr": [ocr("导出为", 300.0, 200.0)] }
+        ]));
+
+        let report = run(&pkg, &mut fake, &RunOptions::default());
+        assert_eq!(report.status, RunStatus::Completed);
+        assert_eq!(report.trace.len(), 4);
+        assert!(report.trace.iter().all(|t| t.outcome == StepOutcome::Passed && t.attempts == 1));
+        assert_eq!(
+            fake.actions,
+            vec![
+                RecordedAction::Click { at: Point { x: 30.0, y: 10.0 }, button: "left".to_string(), count: 1 },
+                RecordedAction::Type { text: "output".to_string(), clear_first: true },
+                RecordedAction::Hotkey { keys: vec!["enter".to_string()] },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retries_until_element_appears() {
+        let pkg = resolved(json!([
+            {
+                "id": "s1",
+                "op": "wait",
+                "until": { "$ref": "#/selectors/dialog" },
+                "retry": { "times": 5, "intervalMs": 100 }
+            }
+        ]));
+        let mut fake = desktop(json!([{}, {}, { "ocr": [ocr("导出", 0.0, 0.0)] }]));
+
+        let report = run(&pkg, &mut fake, &RunOptions::default());
+        assert_eq!(report.status, RunStatus::Completed);
+        assert_eq!(report.trace[0].attempts, 3);
+        assert_eq!(report.trace[0].finished_at_ms, 200);
+    }
+
+    #[test]
+    fn test_timeout_cuts_retries_short() {
+        let pkg = resolved(json!([
+            {
+                "id": "s1",
+                "op": "wait",
+                "until": { "$ref": "#/selectors/dialog" },
+                "retry": { "times": 10, "intervalMs": 300, "timeoutMs": 1000 },
+                "on_fail": { "action": "abort", "reason": "dialog missing" }
+            }
+        ]));
+        let mut fake = desktop(json!([{}]));
+
+        let report = run(&pkg, &mut fake, &RunOptions::default());
+        assert_eq!(
+            report.status,
+            RunStatus::Aborted { step_id: "s1".to_string(), reason: "dialog missing".to_string() }
+        );
+        // Attempts start at 0, 300, 600 and 900 ms; a fifth would start past the timeout.
+        assert_eq!(report.trace[0].attempts, 4);
+        assert_eq!(report.trace[0].outcome, StepOutcome::Aborted);
+    }
+
+    #[test]
+    fn test_skip_and_fallback_step() {
+        let pkg = resolved(json!([
+            {
+                "id": "s1",
+                "op": "assert",
+                "expect": { "$ref": "#/selectors/dialog" },
+                "on_fail": { "action": "skip" }
+            },
+            {
+                "id": "s2",
+                "op": "click",
+                "target": { "$ref": "#/selectors/export_icon" },
+                "on_fail": { "action": "fallback_step_id", "stepId": "s3" }
+            },
+            { "id": "s3", "op": "hotkey", "keys": ["esc"] },
+            { "id": "s4", "op": "click", "target": { "$ref": "#/selectors/menu_file" } }
+        ]));
+        let mut fake = desktop(json!([{}, { "ocr": [ocr("文件", 0.0, 0.0)] }]));
+
+        let report = run(&pkg, &mut fake, &RunOptions::default());
+        assert_eq!(report.status, RunStatus::Completed);
+        let outcomes: Vec<_> = report.trace.iter().map(|t| (t.step_id.as_str(), t.outcome.clone())).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("s1", StepOutcome::Skipped),
+                ("s2", StepOutcome::Jumped { to: "s3".to_string() }),
+                ("s3", StepOutcome::Passed),
+                ("s4", StepOutcome::Passed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fallback_point_policies() {
+        let step = |policy: &str| {
+            json!([{
+                "id": "s1",
+                "op": "click",
+                "target": { "$ref": "#/selectors/export_icon" },
+                "retry": { "times": 2 },
+                "fallback": { "point": { "x": 0.5, "y": 0.25 }, "policy": policy }
+            }])
+        };
+
+        let mut fake = desktop(json!([{}]));
+        let report = run(&resolved(step("last_retry_only")), &mut fake, &RunOptions::default());
+        assert_eq!(report.trace[0].attempts, 3);
+        assert!(report.trace[0].used_fallback_point);
+        assert_eq!(
+            fake.actions,
+            vec![RecordedAction::Click { at: Point { x: 500.0, y: 200.0 }, button: "left".to_string(), count: 1 }]
+        );
+
+        let mut fake = desktop(json!([{}]));
+        let report = run(&resolved(step("always")), &mut fake, &RunOptions::default());
+        assert_eq!(report.trace[0].attempts, 1);
+        assert!(report.trace[0].used_fallback_point);
+    }
+
+    #[test]
+    fn test_fallback_loop_is_bounded() {
+        let pkg = resolved(json!([
+            {
+                "id": "s1",
+                "op": "assert",
+                "expect": { "$ref": "#/selectors/dialog" },
+                "on_fail": { "action": "fallback_step_id", "stepId": "s1" }
+            }
+        ]));
+        let mut fake = desktop(json!([{}]));
+
+        let report = run(&pkg, &mut fake, &RunOptions { max_step_runs: 5 });
+        assert!(matches!(report.status, RunStatus::Aborted { .. }));
+        assert_eq!(report.trace.len(), 5);
+    }
+}
@@ -0,0 +1,407 @@
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::domain::package::{
+    MultiSelector, OCRSelector, RelativeSelector, Scope, Selector, SelectorOrRef, TemplateSelector,
+};
+use crate::engine::backend::Match;
+use crate::engine::geometry::{Point, Rect, Region, Size};
+use crate::engine::scope::{resolve_scope, DialogInfo, ScopeContext, ScopeError, WindowInfo};
+
+/// Everything recognised on one screen frame.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Frame {
+    #[serde(default)]
+    pub ocr: Vec<OcrBox>,
+    #[serde(default)]
+    pub templates: Vec<TemplateHit>,
+    #[serde(default)]
+    pub windows: Vec<WindowInfo>,
+    /// Topmost first.
+    #[serde(default)]
+    pub dialogs: Vec<DialogInfo>,
+    #[serde(default)]
+    pub active_menu: Option<Rect>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrBox {
+    pub text: String,
+    pub rect: Rect,
+    pub confidence: f64,
+    /// Tesseract-style language the token was recognised with; `None` matches any.
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateHit {
+    pub template: String,
+    pub rect: Rect,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchError {
+    Scope(ScopeError),
+    InvalidRegex(String),
+    /// The selector still contains a `$ref`; resolve the package first.
+    UnresolvedRef(String),
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::Scope(e) => write!(f, "{}", e),
+            MatchError::InvalidRegex(e) => write!(f, "invalid regex: {}", e),
+            MatchError::UnresolvedRef(r) => write!(f, "unresolved reference '{}'", r),
+        }
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+/// Evaluates resolved selectors against a [`Frame`].
+pub struct SelectorMatcher<'a> {
+    frame: &'a Frame,
+    screen: Size,
+    elements: &'a HashMap<String, Rect>,
+}
+
+impl<'a> SelectorMatcher<'a> {
+    pub fn new(frame: &'a Frame, screen: Size, elements: &'a HashMap<String, Rect>) -> Self {
+        Self { frame, screen, elements }
+    }
+
+    /// All matches for `selector`, best first. `inherited` is the enclosing
+    /// (step or relative) scope, used when the selector has no scope of its own.
+    pub fn find(&self, selector: &Selector, inherited: Option<&Scope>) -> Result<Vec<Match>, MatchError> {
+        self.matches(selector, inherited, None)
+    }
+
+    fn matches(&self, selector: &Selector, inherited: Option<&Scope>, anchor: Option<Rect>) -> Result<Vec<Match>, MatchError> {
+        let mut found = match selector {
+            Selector::OCR(s) => self.match_ocr(s, inherited, anchor)?,
+            Selector::Template(s) => self.match_template(s, inherited, anchor)?,
+            Selector::Relative(s) => return self.match_relative(s, inherited),
+            Selector::Multi(s) => return self.match_multi(s, inherited, anchor),
+        };
+        rank(&mut found, selector_scope(selector).or(inherited), anchor);
+        Ok(found)
+    }
+
+    fn match_sor(&self, sor: &SelectorOrRef, inherited: Option<&Scope>, anchor: Option<Rect>) -> Result<Vec<Match>, MatchError> {
+        match sor {
+            SelectorOrRef::Inline(selector) => self.matches(selector, inherited, anchor),
+            SelectorOrRef::Ref(r) => Err(MatchError::UnresolvedRef(r.reference.clone())),
+        }
+    }
+
+    /// The area to search, or `None` when the scope's window/dialog/element is
+    /// not on this frame, which simply means nothing matches.
+    fn region(&self, scope: Option<&Scope>, anchor: Option<Rect>) -> Result<Option<Region>, MatchError> {
+        let screen = Region::from_rect(Rect::new(0.0, 0.0, self.screen.width, self.screen.height));
+        let Some(scope) = scope else {
+            return Ok(Some(screen));
+        };
+        let ctx = ScopeContext {
+            screen: self.screen,
+            windows: &self.frame.windows,
+            dialogs: &self.frame.dialogs,
+            active_menu: self.frame.active_menu,
+            elements: self.elements,
+            anchor,
+        };
+        let mut locate = |sor: &SelectorOrRef| {
+            self.match_sor(sor, None, None).ok().and_then(|m| m.first().map(|m| m.rect))
+        };
+        match resolve_scope(scope, &ctx, &mut locate) {
+            Ok(region) => Ok(Some(region)),
+            Err(ScopeError::Unavailable(_) | ScopeError::UnknownElement { .. } | ScopeError::AnchorNotFound) => Ok(None),
+            Err(e) => Err(MatchError::Scope(e)),
+        }
+    }
+
+    fn match_ocr(&self, s: &OCRSelector, inherited: Option<&Scope>, anchor: Option<Rect>) -> Result<Vec<Match>, MatchError> {
+        let Some(region) = self.region(s.scope.as_ref().or(inherited), anchor)? else {
+            return Ok(Vec::new());
+        };
+        let options = s.match_options.as_ref();
+        let mode = options.map(|m| m.mode.as_str()).unwrap_or("contains");
+        let case_sensitive = options.is_some_and(|m| m.case_sensitive);
+        let lang = options.map(|m| m.lang.as_str());
+
+        let fold = |t: &str| if case_sensitive { t.to_string() } else { t.to_lowercase() };
+        let wanted = fold(&s.text);
+        let regex = if mode == "regex" {
+            let pattern = options.and_then(|m| m.regex.as_deref()).unwrap_or(&s.text);
+            let re = RegexBuilder::new(pattern)
+                .case_insensitive(!case_sensitive)
+                .build()
+                .map_err(|e| MatchError::InvalidRegex(e.to_string()))?;
+            Some(re)
+        } else {
+            None
+        };
+
+        Ok(self
+            .frame
+            .ocr
+            .iter()
+            .filter(|b| match (&b.lang, lang) {
+                (Some(have), Some(want)) => have == want,
+                _ => true,
+            })
+            .filter(|b| region.contains(b.rect.center()))
+            .filter(|b| match (mode, &regex) {
+                (_, Some(re)) => re.is_match(&b.text),
+                ("equals", _) => fold(&b.text) == wanted,
+                ("contains", _) => fold(&b.text).contains(&wanted),
+                _ => false,
+            })
+            .map(|b| Match { rect: b.rect, confidence: b.confidence })
+            .collect())
+    }
+
+    fn match_template(&self, s: &TemplateSelector, inherited: Option<&Scope>, anchor: Option<Rect>) -> Result<Vec<Match>, MatchError> {
+        let Some(region) = self.region(s.scope.as_ref().or(inherited), anchor)? else {
+            return Ok(Vec::new());
+        };
+        let threshold = s.match_options.as_ref().map(|m| m.threshold).unwrap_or(0.8);
+        Ok(self
+            .frame
+            .templates
+            .iter()
+            .filter(|h| h.template == s.template && h.score >= threshold)
+            .filter(|h| region.contains(h.rect.center()))
+            .map(|h| Match { rect: h.rect, confidence: h.score })
+            .collect())
+    }
+
+    /// Targets in the given relation to any anchor, within `maxDistancePx`
+    /// (centre to centre). Confidence is the weaker of anchor and target; a
+    /// `nearest` target scope ranks by distance rather than confidence.
+    fn match_relative(&self, s: &RelativeSelector, inherited: Option<&Scope>) -> Result<Vec<Match>, MatchError> {
+        let scope = s.scope.as_ref().or(inherited);
+        let anchors = self.match_sor(&s.anchor, scope, None)?;
+        let max_distance = f64::from(s.relation.max_distance_px);
+
+        let mut results: Vec<(Match, f64)> = Vec::new();
+        for a in &anchors {
+            for t in self.match_sor(&s.target, scope, Some(a.rect))? {
+                let distance = distance(a.rect.center(), t.rect.center());
+                if distance > max_distance || !in_relation(&s.relation.rel_type, &a.rect, &t.rect) {
+                    continue;
+                }
+                let m = Match { rect: t.rect, confidence: t.confidence.min(a.confidence) };
+                match results.iter_mut().find(|(r, _)| r.rect == m.rect) {
+                    Some(existing) if existing.0.confidence >= m.confidence => {}
+                    Some(existing) => *existing = (m, distance),
+                    None => results.push((m, distance)),
+                }
+            }
+        }
+        let nearest = matches!(&s.target, SelectorOrRef::Inline(t) if matches!(selector_scope(t), Some(Scope::Nearest(_))));
+        if nearest {
+            results.sort_by(|(_, da), (_, db)| da.total_cmp(db));
+        } else {
+            results.sort_by(|(a, da), (b, db)| b.confidence.total_cmp(&a.confidence).then(da.total_cmp(db)));
+        }
+        Ok(results.into_iter().map(|(m, _)| m).collect())
+    }
+
+    fn match_multi(&self, s: &MultiSelector, inherited: Option<&Scope>, anchor: Option<Rect>) -> Result<Vec<Match>, MatchError> {
+        let scope = s.scope.as_ref().or(inherited);
+        let first_match = s.pick.as_ref().is_some_and(|p| p.policy == "firstMatch");
+
+        let mut all = Vec::new();
+        for candidate in &s.candidates {
+            let found = self.match_sor(candidate, scope, anchor)?;
+            if first_match && !found.is_empty() {
+                return Ok(found);
+            }
+            all.extend(found);
+        }
+        all.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        Ok(all)
+    }
+}
+
+fn selector_scope(selector: &Selector) -> Option<&Scope> {
+    match selector {
+        Selector::OCR(s) => s.scope.as_ref(),
+        Selector::Template(s) => s.scope.as_ref(),
+        Selector::Relative(s) => s.scope.as_ref(),
+        Selector::Multi(s) => s.scope.as_ref(),
+    }
+}
+
+/// Best confidence first; a `nearest` scope ranks by distance to the anchor instead.
+fn rank(found: &mut [Match], scope: Option<&Scope>, anchor: Option<Rect>) {
+    match (scope, anchor) {
+        (Some(Scope::Nearest(_)), Some(anchor)) => {
+            let c = anchor.center();
+            found.sort_by(|a, b| distance(c, a.rect.center()).total_cmp(&distance(c, b.rect.center())));
+        }
+        _ => found.sort_by(|a, b| b.confidence.total_cmp(&a.confidence)),
+    }
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// Whether the target's centre lies past the anchor's edge in the given direction.
+fn in_relation(rel_type: &str, anchor: &Rect, target: &Rect) -> bool {
+    let c = target.center();
+    match rel_type {
+        "below" => c.y >= anchor.bottom(),
+        "above" => c.y <= anchor.y,
+        "rightOf" => c.x >= anchor.right(),
+        "leftOf" => c.x <= anchor.x,
+        "near" => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCREEN: Size = Size { width: 1000.0, height: 800.0 };
+
+    fn selector(value: serde_json::Value) -> Selector {
+        serde_json::from_value(value).expect("selector should parse")
+    }
+
+    fn frame() -> Frame {
+        serde_json::from_value(json!({
+            "ocr": [
+                { "text": "文件", "rect": { "x": 10.0, "y": 5.0, "w": 30.0, "h": 15.0 }, "confidence": 0.95, "lang": "chi_sim" },
+                { "text": "File", "rect": { "x": 60.0, "y": 5.0, "w": 30.0, "h": 15.0 }, "confidence": 0.9, "lang": "eng" },
+                { "text": "Export As...", "rect": { "x": 320.0, "y": 120.0, "w": 90.0, "h": 20.0 }, "confidence": 0.7, "lang": "eng" },
+                { "text": "图层", "rect": { "x": 850.0, "y": 100.0, "w": 40.0, "h": 20.0 }, "confidence": 0.9 },
+                { "text": "图层 1", "rect": { "x": 850.0, "y": 200.0, "w": 60.0, "h": 20.0 }, "confidence": 0.8 },
+                { "text": "图层 2", "rect": { "x": 850.0, "y": 700.0, "w": 60.0, "h": 20.0 }, "confidence": 0.85 }
+            ],
+            "templates": [
+                { "template": "assets/templates/export_icon.png", "rect": { "x": 300.0, "y": 118.0, "w": 16.0, "h": 16.0 }, "score": 0.84 },
+                { "template": "assets/templates/export_icon.png", "rect": { "x": 600.0, "y": 600.0, "w": 16.0, "h": 16.0 }, "score": 0.75 }
+            ],
+            "dialogs": [{ "title": "导出", "rect": { "x": 280.0, "y": 100.0, "w": 400.0, "h": 300.0 } }]
+        }))
+        .expect("frame should parse")
+    }
+
+    fn find(frame: &Frame, value: serde_json::Value) -> Result<Vec<Match>, MatchError> {
+        let elements = HashMap::new();
+        SelectorMatcher::new(frame, SCREEN, &elements).find(&selector(value), None)
+    }
+
+    #[test]
+    fn test_ocr_modes() {
+        let frame = frame();
+        let equals = find(&frame, json!({ "strategy": "ocr", "text": "file", "match": { "mode": "equals", "lang": "eng" } }));
+        assert_eq!(equals.unwrap().len(), 1);
+
+        let case_sensitive = find(
+            &frame,
+            json!({ "strategy": "ocr", "text": "file", "match": { "mode": "equals", "lang": "eng", "caseSensitive": true } }),
+        );
+        assert!(case_sensitive.unwrap().is_empty());
+
+        let contains = find(&frame, json!({ "strategy": "ocr", "text": "EXPORT" }));
+        assert_eq!(contains.unwrap().len(), 1);
+        // The default match lang is chi_sim, so the English token is skipped.
+        let chinese = find(&frame, json!({ "strategy": "ocr", "text": "EXPORT", "match": { "mode": "contains" } }));
+        assert!(chinese.unwrap().is_empty());
+
+        let regex = find(
+            &frame,
+            json!({ "strategy": "ocr", "text": "export", "match": { "mode": "regex", "regex": "^export\\s+as", "lang": "eng" } }),
+        );
+        assert_eq!(regex.unwrap()[0].rect.x, 320.0);
+
+        let bad = find(&frame, json!({ "strategy": "ocr", "text": "x", "match": { "mode": "regex", "regex": "(" } }));
+        assert!(matches!(bad, Err(MatchError::InvalidRegex(_))));
+    }
+
+    #[test]
+    fn test_template_threshold_and_scope() {
+        let frame = frame();
+        let loose = find(
+            &frame,
+            json!({ "strategy": "template", "template": "assets/templates/export_icon.png", "match": { "threshold": 0.7 } }),
+        );
+        let loose = loose.unwrap();
+        assert_eq!(loose.len(), 2);
+        assert_eq!(loose[0].confidence, 0.84);
+
+        let strict = find(&frame, json!({ "strategy": "template", "template": "assets/templates/export_icon.png" }));
+        assert_eq!(strict.unwrap().len(), 1);
+
+        let scoped = find(
+            &frame,
+            json!({
+                "strategy": "template",
+                "template": "assets/templates/export_icon.png",
+                "match": { "threshold": 0.7 },
+                "scope": { "type": "band", "edge": "bottom", "ratio": 0.5 }
+            }),
+        );
+        assert_eq!(scoped.unwrap()[0].rect.x, 600.0);
+
+        let no_menu = find(
+            &frame,
+            json!({ "strategy": "template", "template": "assets/templates/export_icon.png", "scope": { "type": "activeMenu" } }),
+        );
+        assert!(no_menu.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_relative_relations_and_distance() {
+        let frame = frame();
+        let layer = |rel: &str, max: u32| {
+            json!({
+                "strategy": "relative",
+                "anchor": { "strategy": "ocr", "text": "图层", "match": { "mode": "equals" } },
+                "relation": { "type": rel, "maxDistancePx": max },
+                "target": { "strategy": "ocr", "text": "图层 ", "scope": { "type": "nearest", "to": "anchor" } }
+            })
+        };
+
+        let below = find(&frame, layer("below", 650)).unwrap();
+        assert_eq!(below.iter().map(|m| m.rect.y).collect::<Vec<_>>(), vec![200.0, 700.0]);
+        assert_eq!(below[0].confidence, 0.8);
+
+        let close = find(&frame, layer("below", 400)).unwrap();
+        assert_eq!(close.len(), 1);
+        assert!(find(&frame, layer("above", 650)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_multi_pick_policies() {
+        let frame = frame();
+        let multi = |policy: &str| {
+            json!({
+                "strategy": "multi",
+                "candidates": [
+                    { "strategy": "ocr", "text": "Export", "match": { "lang": "eng" }, "scope": { "type": "dialog", "role": "topmost" } },
+                    { "strategy": "template", "template": "assets/templates/export_icon.png" }
+                ],
+                "pick": { "policy": policy }
+            })
+        };
+
+        let best = find(&frame, multi("bestConfidence")).unwrap();
+        assert_eq!(best[0].confidence, 0.84);
+        assert_eq!(best.len(), 2);
+
+        let first = find(&frame, multi("firstMatch")).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].confidence, 0.7);
+    }
+}
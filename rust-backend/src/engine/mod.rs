@@ -0,0 +1,6 @@
+pub mod backend;
+pub mod fake;
+pub mod geometry;
+pub mod interpreter;
+pub mod matcher;
+pub mod scope;
@@ -0,0 +1,299 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::domain::package::{Scope, ScopeRect, SelectorOrRef};
+use crate::engine::geometry::{Rect, Region, Size};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub title: String,
+    pub rect: Rect,
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default)]
+    pub main: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogInfo {
+    pub title: String,
+    pub rect: Rect,
+    #[serde(default)]
+    pub modal: bool,
+}
+
+/// The desktop state a scope is evaluated against.
+pub struct ScopeContext<'a> {
+    pub screen: Size,
+    pub windows: &'a [WindowInfo],
+    /// Open dialogs, topmost first.
+    pub dialogs: &'a [DialogInfo],
+    pub active_menu: Option<Rect>,
+    /// Elements matched by earlier steps, keyed by step id.
+    pub elements: &'a HashMap<String, Rect>,
+    /// The relative selector's anchor, for `nearest` scopes.
+    pub anchor: Option<Rect>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScopeError {
+    /// The window, dialog or menu the scope names is not on screen.
+    Unavailable(String),
+    UnknownElement { step_id: String },
+    AnchorNotFound,
+    InvalidPattern(String),
+    InvalidValue(String),
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScopeError::Unavailable(what) => write!(f, "{} is not available", what),
+            ScopeError::UnknownElement { step_id } => write!(f, "step '{}' has no matched element", step_id),
+            ScopeError::AnchorNotFound => write!(f, "scope anchor not found"),
+            ScopeError::InvalidPattern(e) => write!(f, "invalid title pattern: {}", e),
+            ScopeError::InvalidValue(e) => write!(f, "invalid scope: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
+
+/// Computes the screen area a scope describes, clipped to the screen.
+///
+/// `locate` finds the anchor of an `around` scope; it is only called for those.
+pub fn resolve_scope(
+    scope: &Scope,
+    ctx: &ScopeContext<'_>,
+    locate: &mut dyn FnMut(&SelectorOrRef) -> Option<Rect>,
+) -> Result<Region, ScopeError> {
+    let screen = Rect::new(0.0, 0.0, ctx.screen.width, ctx.screen.height);
+    let region = match scope {
+        Scope::Rect(rect) => Region::from_rect(scope_rect(rect, ctx.screen)),
+        Scope::Band(band) => {
+            if !(0.0..=1.0).contains(&band.ratio) {
+                return Err(ScopeError::InvalidValue(format!("band ratio {} outside 0..1", band.ratio)));
+            }
+            let (w, h) = (screen.w, screen.h);
+            let rect = match band.edge.as_str() {
+                "top" => Rect::new(0.0, 0.0, w, h * band.ratio),
+                "bottom" => Rect::new(0.0, h * (1.0 - band.ratio), w, h * band.ratio),
+                "left" => Rect::new(0.0, 0.0, w * band.ratio, h),
+                "right" => Rect::new(w * (1.0 - band.ratio), 0.0, w * band.ratio, h),
+                other => return Err(ScopeError::InvalidValue(format!("unknown band edge '{}'", other))),
+            };
+            Region::from_rect(rect)
+        }
+        Scope::Window(window) => {
+            let found = match window.mode.as_str() {
+                "active" => ctx.windows.iter().find(|w| w.active),
+                "main" => ctx.windows.iter().find(|w| w.main),
+                "byTitle" => {
+                    let title = window.title.as_deref().unwrap_or_default();
+                    let matcher = TitleMatcher::new(&window.match_mode, title)?;
+                    ctx.windows.iter().find(|w| matcher.matches(&w.title))
+                }
+                other => return Err(ScopeError::InvalidValue(format!("unknown window mode '{}'", other))),
+            };
+            let window = found.ok_or_else(|| ScopeError::Unavailable(format!("{} window", window.mode)))?;
+            Region::from_rect(window.rect)
+        }
+        Scope::Dialog(dialog) => {
+            let found = match dialog.role.as_str() {
+                "topmost" => ctx.dialogs.first(),
+                "modal" => ctx.dialogs.iter().find(|d| d.modal),
+                "byTitle" => {
+                    let title = dialog.title.as_deref().unwrap_or_default();
+                    let matcher = TitleMatcher::new(&dialog.match_mode, title)?;
+                    ctx.dialogs.iter().find(|d| matcher.matches(&d.title))
+                }
+                other => return Err(ScopeError::InvalidValue(format!("unknown dialog role '{}'", other))),
+            };
+            let dialog = found.ok_or_else(|| ScopeError::Unavailable(format!("{} dialog", dialog.role)))?;
+            Region::from_rect(dialog.rect)
+        }
+        Scope::ActiveMenu(_) => {
+            let menu = ctx.active_menu.ok_or_else(|| ScopeError::Unavailable("active menu".to_string()))?;
+            Region::from_rect(menu)
+        }
+        Scope::ElementRef(element) => {
+            let rect = ctx
+                .elements
+                .get(&element.ref_step_id)
+                .ok_or_else(|| ScopeError::UnknownElement { step_id: element.ref_step_id.clone() })?;
+            Region::from_rect(rect.inflate(f64::from(element.padding_px)))
+        }
+        Scope::Around(around) => {
+            let anchor = locate(&around.anchor).ok_or(ScopeError::AnchorNotFound)?;
+            Region::from_rect(anchor.inflate(f64::from(around.radius_px)))
+        }
+        // `nearest` does not restrict the area; it only requires an anchor to rank
+        // candidates by distance, which is the matcher's job.
+        Scope::Nearest(_) => {
+            ctx.anchor.ok_or(ScopeError::AnchorNotFound)?;
+            Region::from_rect(screen)
+        }
+        Scope::Union(union) => {
+            let mut region = Region::empty();
+            for s in &union.scopes {
+                region = region.union(&resolve_scope(s, ctx, locate)?);
+            }
+            region
+        }
+        Scope::Intersect(intersect) => {
+            let mut region = Region::from_rect(screen);
+            for s in &intersect.scopes {
+                region = region.intersect(&resolve_scope(s, ctx, locate)?);
+            }
+            region
+        }
+        Scope::Exclude(exclude) => {
+            let base = resolve_scope(&exclude.base, ctx, locate)?;
+            base.subtract(&resolve_scope(&exclude.exclude, ctx, locate)?)
+        }
+    };
+    Ok(region.intersect(&Region::from_rect(screen)))
+}
+
+fn scope_rect(rect: &ScopeRect, screen: Size) -> Rect {
+    if rect.normalized {
+        Rect::new(rect.x * screen.width, rect.y * screen.height, rect.w * screen.width, rect.h * screen.height)
+    } else {
+        Rect::new(rect.x, rect.y, rect.w, rect.h)
+    }
+}
+
+/// Title matching for `window`/`dialog` scopes (`equals`, `contains`, `regex`).
+enum TitleMatcher<'a> {
+    Equals(&'a str),
+    Contains(&'a str),
+    Regex(Regex),
+}
+
+impl<'a> TitleMatcher<'a> {
+    fn new(mode: &str, title: &'a str) -> Result<Self, ScopeError> {
+        match mode {
+            "equals" => Ok(TitleMatcher::Equals(title)),
+            "contains" => Ok(TitleMatcher::Contains(title)),
+            "regex" => Regex::new(title)
+                .map(TitleMatcher::Regex)
+                .map_err(|e| ScopeError::InvalidPattern(e.to_string())),
+            other => Err(ScopeError::InvalidValue(format!("unknown title match '{}'", other))),
+        }
+    }
+
+    fn matches(&self, candidate: &str) -> bool {
+        match self {
+            TitleMatcher::Equals(t) => candidate == *t,
+            TitleMatcher::Contains(t) => candidate.contains(t),
+            TitleMatcher::Regex(re) => re.is_match(candidate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCREEN: Size = Size { width: 1000.0, height: 500.0 };
+
+    fn scope(value: serde_json::Value) -> Scope {
+        serde_json::from_value(value).expect("scope should parse")
+    }
+
+    fn resolve(scope: &Scope, ctx: &ScopeContext<'_>) -> Result<Region, ScopeError> {
+        resolve_scope(scope, ctx, &mut |_| Some(Rect::new(100.0, 100.0, 10.0, 10.0)))
+    }
+
+    fn with_ctx<T>(f: impl FnOnce(&ScopeContext<'_>) -> T) -> T {
+        let windows = vec![
+            WindowInfo { title: "Untitled-1 @ 100%".into(), rect: Rect::new(0.0, 20.0, 800.0, 480.0), active: true, main: true },
+            WindowInfo { title: "Layers".into(), rect: Rect::new(800.0, 20.0, 200.0, 480.0), active: false, main: false },
+        ];
+        let dialogs = vec![
+            DialogInfo { title: "导出为".into(), rect: Rect::new(300.0, 100.0, 400.0, 300.0), modal: true },
+        ];
+        let elements = HashMap::from([("s1".to_string(), Rect::new(10.0, 10.0, 20.0, 10.0))]);
+        f(&ScopeContext {
+            screen: SCREEN,
+            windows: &windows,
+            dialogs: &dialogs,
+            active_menu: None,
+            elements: &elements,
+            anchor: None,
+        })
+    }
+
+    #[test]
+    fn test_rect_and_band() {
+        with_ctx(|ctx| {
+            let normalized = resolve(&scope(json!({ "type": "rect", "x": 0.5, "y": 0.5, "w": 0.25, "h": 0.5 })), ctx);
+            assert_eq!(normalized.unwrap().rects(), &[Rect::new(500.0, 250.0, 250.0, 250.0)]);
+
+            let absolute = resolve(
+                &scope(json!({ "type": "rect", "x": 900.0, "y": 0.0, "w": 300.0, "h": 50.0, "normalized": false })),
+                ctx,
+            );
+            assert_eq!(absolute.unwrap().rects(), &[Rect::new(900.0, 0.0, 100.0, 50.0)]);
+
+            let bottom = resolve(&scope(json!({ "type": "band", "edge": "bottom", "ratio": 0.2 })), ctx);
+            assert_eq!(bottom.unwrap().rects(), &[Rect::new(0.0, 400.0, 1000.0, 100.0)]);
+            let right = resolve(&scope(json!({ "type": "band", "edge": "right", "ratio": 0.45 })), ctx);
+            assert_eq!(right.unwrap().bounds().map(|r| r.x.round()), Some(550.0));
+        });
+    }
+
+    #[test]
+    fn test_windows_dialogs_and_elements() {
+        with_ctx(|ctx| {
+            let by_title = resolve(&scope(json!({ "type": "window", "mode": "byTitle", "title": "^Lay", "match": "regex" })), ctx);
+            assert_eq!(by_title.unwrap().rects(), &[Rect::new(800.0, 20.0, 200.0, 480.0)]);
+
+            let modal = resolve(&scope(json!({ "type": "dialog", "role": "modal" })), ctx);
+            assert_eq!(modal.unwrap().rects(), &[Rect::new(300.0, 100.0, 400.0, 300.0)]);
+
+            let element = resolve(&scope(json!({ "type": "elementRef", "refStepId": "s1", "paddingPx": 5 })), ctx);
+            assert_eq!(element.unwrap().rects(), &[Rect::new(5.0, 5.0, 30.0, 20.0)]);
+
+            let missing = resolve(&scope(json!({ "type": "elementRef", "refStepId": "s9" })), ctx);
+            assert_eq!(missing, Err(ScopeError::UnknownElement { step_id: "s9".to_string() }));
+            let menu = resolve(&scope(json!({ "type": "activeMenu" })), ctx);
+            assert_eq!(menu, Err(ScopeError::Unavailable("active menu".to_string())));
+        });
+    }
+
+    #[test]
+    fn test_around_and_set_operations() {
+        with_ctx(|ctx| {
+            let around = resolve(
+                &scope(json!({ "type": "around", "anchor": { "strategy": "ocr", "text": "x" }, "radiusPx": 50 })),
+                ctx,
+            );
+            assert_eq!(around.unwrap().rects(), &[Rect::new(50.0, 50.0, 110.0, 110.0)]);
+
+            let exclude = scope(json!({
+                "type": "exclude",
+                "base": { "type": "window", "mode": "active" },
+                "exclude": {
+                    "type": "union",
+                    "scopes": [
+                        { "type": "dialog", "role": "topmost" },
+                        { "type": "band", "edge": "top", "ratio": 0.2 }
+                    ]
+                }
+            }));
+            let region = resolve(&exclude, ctx).unwrap();
+            // Active window (800x480 at y=20) minus the top 100px band and the 400x300 dialog.
+            assert_eq!(region.area(), 800.0 * 400.0 - 400.0 * 300.0);
+
+            let intersect = scope(json!({
+                "type": "intersect",
+                "scopes": [{ "type": "band", "edge": "left", "ratio": 0.5 }, { "type": "dialog", "role": "topmost" }]
+            }));
+            assert_eq!(resolve(&intersect, ctx).unwrap().rects(), &[Rect::new(300.0, 100.0, 200.0, 300.0)]);
+        });
+    }
+}
@@ -1,21 +1,39 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    Json,
-    response::IntoResponse,
+    body::Bytes,
+    extract::{State, rejection::BytesRejection},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}},
 };
+use futures_util::stream;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
 use std::sync::Arc;
-use std::env;
+use tokio_util::sync::CancellationToken;
 use crate::{
-    domain::task::TaskStatus,
-    service::{task_service::MemTaskService, process},
+    api_error::ApiError,
+    config::Config,
+    extract::{Json, Path, Query},
+    domain::{bundle::{BUNDLE_EXTENSION, Bundle}, diff::PackageDiff, library::{SkillEntry, SkillQuery, SkillRevision}, package::Package, pipeline_error::TaskFailure, schema::{self, SchemaViolation}, task::{ProviderCall, StatusChange, TaskStatus}},
+    service::{
+        job_queue::{JobQueue, ParseJob},
+        process,
+        skill_repository::{SkillDocument, SkillError, SkillRepository},
+        task_service::{TaskError, TaskService, TaskStore},
+    },
 };
 
 #[derive(Clone)]
 pub struct AppState {
-    pub task_service: MemTaskService,
+    pub task_service: TaskService,
+    pub skills: Arc<dyn SkillRepository>,
+    pub jobs: Arc<JobQueue>,
+    pub pipeline: Arc<process::Pipeline>,
+    pub config: Arc<Config>,
+    /// Cancelled when the server starts shutting down; ends open event streams
+    /// so graceful shutdown does not wait on them.
+    pub shutdown: CancellationToken,
 }
 
 // Request/Response Structs
@@ -79,6 +97,21 @@ pub struct TaskStatusResponse {
     pub status: TaskStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Code, stage and retryability of `error` when a pipeline stage failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<TaskFailure>,
+    #[serde(rename = "validationErrors", skip_serializing_if = "Vec::is_empty")]
+    pub validation_errors: Vec<SchemaViolation>,
+    /// Required tracks still without an artifact.
+    #[serde(rename = "missingTracks")]
+    pub missing_tracks: Vec<&'static str>,
+    /// 1-based place among the waiting jobs, per track still queued.
+    #[serde(rename = "queuePositions", skip_serializing_if = "BTreeMap::is_empty")]
+    pub queue_positions: BTreeMap<&'static str, usize>,
+    /// Provider calls so far, with the attempts each one took.
+    #[serde(rename = "providerCalls", skip_serializing_if = "Vec::is_empty")]
+    pub provider_calls: Vec<ProviderCall>,
+    pub history: Vec<StatusChange>,
 }
 
 #[derive(Deserialize)]
@@ -109,13 +142,115 @@ pub struct TaskSummary {
     pub status: TaskStatus,
 }
 
+#[derive(Deserialize)]
+pub struct InstantiatePackageRequest {
+    pub package: Package,
+    /// Values for the package `vars`; omitted vars fall back to their defaults.
+    #[serde(default)]
+    pub vars: HashMap<String, Value>,
+}
+
+#[derive(Serialize)]
+pub struct InstantiatePackageResponse {
+    pub package: Package,
+}
+
+#[derive(Serialize)]
+pub struct ListSkillsResponse {
+    pub count: usize,
+    pub skills: Vec<SkillSummary>,
+}
+
+#[derive(Serialize)]
+pub struct SkillSummary {
+    pub id: String,
+    pub name: String,
+    pub app: String,
+    #[serde(rename = "minVersion", skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+    #[serde(rename = "maxVersion", skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    #[serde(rename = "totalSteps")]
+    pub total_steps: usize,
+    #[serde(rename = "sourceTask", skip_serializing_if = "Option::is_none")]
+    pub source_task: Option<String>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<SkillEntry> for SkillSummary {
+    fn from(entry: SkillEntry) -> Self {
+        let SkillEntry { id, package, source_task, updated_at, .. } = entry;
+        SkillSummary {
+            id,
+            name: package.package.name,
+            app: package.app.name,
+            min_version: package.app.min_version,
+            max_version: package.app.max_version,
+            description: package.package.description,
+            tags: package.package.tags,
+            total_steps: package.steps.len(),
+            source_task,
+            updated_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ListRevisionsResponse {
+    #[serde(rename = "skillId")]
+    pub skill_id: String,
+    pub count: usize,
+    pub revisions: Vec<RevisionSummary>,
+}
+
+#[derive(Serialize)]
+pub struct RevisionSummary {
+    pub number: u32,
+    pub hash: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct SkillDiffRequest {
+    /// Defaults to the revision before `to`.
+    pub from: Option<u32>,
+    /// Defaults to the latest revision.
+    pub to: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct SkillDiffResponse {
+    #[serde(rename = "skillId")]
+    pub skill_id: String,
+    pub from: u32,
+    pub to: u32,
+    #[serde(flatten)]
+    pub diff: PackageDiff,
+}
+
 // Handlers
 
-pub async fn health_check() -> impl IntoResponse {
-    // Check Parse module (depends on OpenRouter API Key)
-    let parse_status = match env::var("OPENROUTER_API_KEY") {
-        Ok(_) => ComponentStatus { status: "healthy".to_string(), message: None },
-        Err(_) => ComponentStatus { status: "degraded".to_string(), message: Some("OPENROUTER_API_KEY missing".to_string()) },
+pub async fn health_check(
+    State(state): State<Arc<AppState>>,
+) -> Json<HealthResponse> {
+    // Check Parse module (depends on the chat and transcription credentials)
+    let config = &state.config;
+    let missing: Vec<&str> = [
+        ("chat.api_key", config.chat.api_key.is_none()),
+        ("transcribe.api_key", config.transcribe.api_key.is_none()),
+    ]
+    .into_iter()
+    .filter_map(|(field, absent)| absent.then_some(field))
+    .collect();
+    let parse_status = if missing.is_empty() {
+        ComponentStatus { status: "healthy".to_string(), message: None }
+    } else {
+        ComponentStatus { status: "degraded".to_string(), message: Some(format!("{} missing", missing.join(", "))) }
     };
 
     // Check Compose module (Simulated/Ready)
@@ -140,139 +275,87 @@ pub async fn health_check() -> impl IntoResponse {
 
 pub async fn create_task(
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    let task = state.task_service.create_task("".to_string()); // No directory needed initially
-    Json(CreateTaskResponse {
+) -> Result<Json<CreateTaskResponse>, ApiError> {
+    let task = state.task_service.create_task("".to_string())?; // No directory needed initially
+    Ok(Json(CreateTaskResponse {
         entry_id: task.entry_id,
         status: "created".to_string(),
-    })
+    }))
 }
 
 pub async fn parse_audio(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<ParseAudioRequest>,
-) -> impl IntoResponse {
-    let entry_id = payload.entry_id.clone();
-    
-    // Check if task exists
-    if state.task_service.get_task(&entry_id).is_none() {
-        return (StatusCode::NOT_FOUND, "Task not found").into_response();
-    }
-
-    // Update status to processing (if not already)
-    let _ = state.task_service.set_status(&entry_id, TaskStatus::Processing);
-
-    // Spawn async task
-    let task_service = state.task_service.clone();
-    let audio_url = payload.audio_url.clone();
-    
-    tokio::spawn(async move {
-        match process::process_audio(audio_url).await {
-            Ok(result) => {
-                let _ = task_service.update_audio_result(&entry_id, result.original_text);
-            }
-            Err(e) => {
-                let _ = task_service.mark_as_failed(&entry_id, e.to_string());
-            }
-        }
-    });
-
-    (StatusCode::OK, Json(json!({"status": "processing"}))).into_response()
+) -> Result<Json<Value>, ApiError> {
+    let job = ParseJob::Audio { audio_url: payload.audio_url };
+    let position = state.jobs.submit(&payload.entry_id, job)?;
+    Ok(Json(json!({"status": "processing", "queuePosition": position})))
 }
 
 pub async fn parse_video(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<ParseVideoRequest>,
-) -> impl IntoResponse {
-    let entry_id = payload.entry_id.clone();
-
-    // Check if task exists
-    if state.task_service.get_task(&entry_id).is_none() {
-        return (StatusCode::NOT_FOUND, "Task not found").into_response();
-    }
-
-    // Update status to processing
-    let _ = state.task_service.set_status(&entry_id, TaskStatus::Processing);
-
-    // Spawn async task
-    let task_service = state.task_service.clone();
-    let video_url = payload.video_url.clone();
-    let prompt = payload.transcript_text.clone(); // Using transcript as prompt/context
-    
-    tokio::spawn(async move {
-        match process::process_video(video_url, prompt).await {
-            Ok(skill) => {
-                // Serialize skill to Value
-                let skill_value = serde_json::to_value(skill).unwrap_or(Value::Null);
-                let _ = task_service.update_video_result(&entry_id, skill_value);
-                
-                // For this pipeline, we assume Steps Engine is part of this or triggered here.
-                // The diagram shows "Steps Engine" as a separate participant, but also says:
-                // "Status may transition to finished when all tracks are ready."
-                // For now, let's mark it as Finished when video is done, assuming video analysis *is* the main output or triggers the next step.
-                // Or we can leave it at VideoDone and require a separate step.
-                // The diagram says: API -> STEPS : Generate steps.
-                // Since we don't have a separate STEPS engine, we can simulate it or just stop at VideoDone.
-                // Let's stop at VideoDone as per current code capabilities.
-            }
-            Err(e) => {
-                let _ = task_service.mark_as_failed(&entry_id, e.to_string());
-            }
-        }
-    });
-
-    (StatusCode::OK, Json(json!({"status": "processing"}))).into_response()
+) -> Result<Json<Value>, ApiError> {
+    // The transcript is the video model's context
+    let job = ParseJob::Video { video_url: payload.video_url, prompt: payload.transcript_text };
+    let position = state.jobs.submit(&payload.entry_id, job)?;
+    Ok(Json(json!({"status": "processing", "queuePosition": position})))
 }
 
 pub async fn get_task_status(
     State(state): State<Arc<AppState>>,
     Query(params): Query<TaskStatusRequest>,
-) -> impl IntoResponse {
-    match state.task_service.get_task(&params.entry_id) {
-        Some(task) => Json(TaskStatusResponse {
-            entry_id: task.entry_id,
-            status: task.status,
-            error: task.error,
-        }).into_response(),
-        None => (StatusCode::NOT_FOUND, "Task not found").into_response(),
-    }
+) -> Result<Json<TaskStatusResponse>, ApiError> {
+    let task = state.task_service.get_task(&params.entry_id).ok_or(TaskError::NotFound)?;
+    Ok(Json(TaskStatusResponse {
+        missing_tracks: task.missing_tracks(),
+        queue_positions: state.jobs.positions(&task.entry_id),
+        entry_id: task.entry_id,
+        status: task.status,
+        error: task.error,
+        failure: task.failure,
+        validation_errors: task.validation_errors,
+        provider_calls: task.provider_calls,
+        history: task.history,
+    }))
 }
 
 pub async fn get_artifact(
     State(state): State<Arc<AppState>>,
     Query(params): Query<ArtifactRequest>,
-) -> impl IntoResponse {
-    let task = match state.task_service.get_task(&params.entry_id) {
-        Some(t) => t,
-        None => return (StatusCode::NOT_FOUND, "Task not found").into_response(),
-    };
+) -> Result<Json<ArtifactResponse>, ApiError> {
+    let task = state.task_service.get_task(&params.entry_id).ok_or(TaskError::NotFound)?;
+    let not_ready = || ApiError::not_found("artifact_not_ready", format!("Artifact {} not ready", params.track));
 
     let data = match params.track.as_str() {
-        "audio" => match task.transcript_text {
-            Some(text) => json!(text),
-            None => return (StatusCode::NOT_FOUND, "Artifact not ready").into_response(),
-        },
-        "video" => match task.video_analysis {
-            Some(analysis) => analysis,
-            None => return (StatusCode::NOT_FOUND, "Artifact not ready").into_response(),
-        },
-        "steps" => match task.steps_package {
-            Some(steps) => steps,
-            None => return (StatusCode::NOT_FOUND, "Artifact not ready").into_response(),
-        },
-        _ => return (StatusCode::BAD_REQUEST, "Invalid track").into_response(),
+        "audio" => json!(task.transcript_text.ok_or_else(not_ready)?),
+        "video" => task.video_analysis.ok_or_else(not_ready)?,
+        "steps" => task.steps_package.ok_or_else(not_ready)?,
+        "alignment" => {
+            let alignment = task.step_alignment.ok_or_else(not_ready)?;
+            json!({
+                "segments": task.transcript_segments,
+                "steps": alignment,
+            })
+        }
+        "attempts" if !task.format_attempts.is_empty() => json!(task.format_attempts),
+        "attempts" => return Err(not_ready()),
+        other => {
+            return Err(ApiError::bad_request("invalid_track", format!("Invalid track `{}`", other))
+                .with_details(json!({ "tracks": ["audio", "video", "steps", "alignment", "attempts"] })));
+        }
     };
 
-    Json(ArtifactResponse {
+    Ok(Json(ArtifactResponse {
         entry_id: task.entry_id,
         track: params.track,
         data,
-    }).into_response()
+    }))
 }
 
 pub async fn list_tasks(
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
+) -> Json<ListTasksResponse> {
     let tasks = state.task_service.list_tasks();
     let summaries: Vec<TaskSummary> = tasks.into_iter().map(|t| TaskSummary {
         entry_id: t.entry_id,
@@ -284,3 +367,226 @@ pub async fn list_tasks(
         tasks: summaries,
     })
 }
+
+pub async fn instantiate_package(
+    Json(payload): Json<InstantiatePackageRequest>,
+) -> Result<Json<InstantiatePackageResponse>, ApiError> {
+    match payload.package.bind(&payload.vars) {
+        Ok(package) => Ok(Json(InstantiatePackageResponse { package })),
+        Err(errors) => {
+            let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_vars", "Package vars do not bind")
+                .with_details(json!({ "errors": errors })))
+        }
+    }
+}
+
+/// Streams a task's events as SSE, replaying retained events after
+/// `Last-Event-ID`. The stream ends after the task reaches a terminal status.
+pub async fn task_events(
+    State(state): State<Arc<AppState>>,
+    Path(entry_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+
+    // Subscribe before reading the task so no transition falls in between
+    let subscription = state.task_service.subscribe(&entry_id, last_event_id);
+    let already_terminal = state.task_service.get_task(&entry_id).ok_or(TaskError::NotFound)?.status.is_terminal();
+
+    let shutdown = state.shutdown.clone();
+    let events = stream::unfold((subscription, false), move |(mut subscription, done)| {
+        let shutdown = shutdown.clone();
+        async move {
+            if done {
+                return None;
+            }
+            let event = match subscription.next_buffered() {
+                Some(event) => event,
+                // Nothing left to replay and nothing more will happen
+                None if already_terminal => return None,
+                None => tokio::select! {
+                    event = subscription.next() => event?,
+                    // Lets graceful shutdown finish; clients resume with Last-Event-ID
+                    _ = shutdown.cancelled() => return None,
+                },
+            };
+            let done = event.is_terminal();
+            let sse = Event::default()
+                .id(event.id.to_string())
+                .event(event.kind.name())
+                .json_data(&event)
+                .unwrap_or_default();
+            Some((Ok::<_, Infallible>(sse), (subscription, done)))
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()).into_response())
+}
+
+pub async fn package_schema() -> Json<Value> {
+    Json(schema::package_schema().clone())
+}
+
+pub async fn list_skills(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SkillQuery>,
+) -> Json<ListSkillsResponse> {
+    let skills: Vec<SkillSummary> = state.skills.search(&query).into_iter().map(SkillSummary::from).collect();
+    Json(ListSkillsResponse { count: skills.len(), skills })
+}
+
+/// Body: `{"package": {...}}` or `{"skill": {...}}`.
+pub async fn create_skill(
+    State(state): State<Arc<AppState>>,
+    Json(document): Json<SkillDocument>,
+) -> Result<(StatusCode, Json<SkillEntry>), ApiError> {
+    let entry = state.skills.create(document)?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn get_skill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<SkillEntry>, ApiError> {
+    let entry = state.skills.get(&id).ok_or(SkillError::NotFound)?;
+    Ok(Json(entry))
+}
+
+pub async fn update_skill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(document): Json<SkillDocument>,
+) -> Result<Json<SkillEntry>, ApiError> {
+    Ok(Json(state.skills.replace(&id, document)?))
+}
+
+pub async fn delete_skill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.skills.delete(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stops a task: it becomes `cancelled` at once, and its running audio and
+/// video work is aborted.
+pub async fn cancel_task(
+    State(state): State<Arc<AppState>>,
+    Path(entry_id): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let task = state.task_service.cancel(&entry_id, "cancelled by user")?;
+    Ok(Json(json!({ "entryId": task.entry_id, "status": task.status })))
+}
+
+/// Adds the steps package of a finished task to the skill library.
+pub async fn publish_task(
+    State(state): State<Arc<AppState>>,
+    Path(entry_id): Path<String>,
+) -> Result<(StatusCode, Json<SkillEntry>), ApiError> {
+    let task = state.task_service.get_task(&entry_id).ok_or(TaskError::NotFound)?;
+    let package = match (task.status, task.steps_package) {
+        (TaskStatus::Finished, Some(package)) => package,
+        (status, _) => {
+            let message = format!("Task is {}, only finished tasks can be published", status);
+            return Err(ApiError::conflict("task_not_finished", message).with_details(json!({ "status": status })));
+        }
+    };
+
+    let entry = state.skills.publish(&entry_id, package)?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn list_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ListRevisionsResponse>, ApiError> {
+    let revisions = state.skills.revisions(&id).ok_or(SkillError::NotFound)?;
+    let revisions: Vec<RevisionSummary> = revisions
+        .into_iter()
+        .map(|r| RevisionSummary { number: r.number, hash: r.hash, created_at: r.created_at })
+        .collect();
+    Ok(Json(ListRevisionsResponse { skill_id: id, count: revisions.len(), revisions }))
+}
+
+pub async fn get_revision(
+    State(state): State<Arc<AppState>>,
+    Path((id, number)): Path<(String, u32)>,
+) -> Result<Json<SkillRevision>, ApiError> {
+    match state.skills.revision(&id, number) {
+        Some(revision) => Ok(Json(revision)),
+        None if state.skills.get(&id).is_none() => Err(SkillError::NotFound.into()),
+        None => Err(SkillError::RevisionNotFound(number).into()),
+    }
+}
+
+pub async fn diff_skill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<SkillDiffRequest>,
+) -> Result<Json<SkillDiffResponse>, ApiError> {
+    let entry = state.skills.get(&id).ok_or(SkillError::NotFound)?;
+    let to = params.to.unwrap_or(entry.revision);
+    let from = params.from.unwrap_or(to.saturating_sub(1).max(1));
+
+    let diff = state.skills.diff(&id, from, to)?;
+    Ok(Json(SkillDiffResponse { skill_id: id, from, to, diff }))
+}
+
+/// Body: a `.skillflow` archive.
+pub async fn import_skill(
+    State(state): State<Arc<AppState>>,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<(StatusCode, Json<SkillEntry>), ApiError> {
+    let body = body?;
+    let result = Bundle::from_archive(&body).map_err(SkillError::Bundle).and_then(|bundle| state.skills.import(bundle));
+    match result {
+        Ok(entry) => Ok((StatusCode::CREATED, Json(entry))),
+        Err(e) => {
+            tracing::warn!("skill import rejected: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Body: a `.skillflow` archive whose package becomes the skill's next
+/// revision and whose assets replace the stored ones.
+pub async fn reimport_skill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Json<SkillEntry>, ApiError> {
+    let body = body?;
+    let result =
+        Bundle::from_archive(&body).map_err(SkillError::Bundle).and_then(|bundle| state.skills.import_into(&id, bundle));
+    match result {
+        Ok(entry) => Ok(Json(entry)),
+        Err(e) => {
+            tracing::warn!("skill {}: import rejected: {}", id, e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn export_skill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let bundle = state.skills.export(&id)?;
+    let archive = bundle.to_archive().map_err(SkillError::Bundle)?;
+    let disposition = format!("attachment; filename=\"{}.{}\"", id, BUNDLE_EXTENSION);
+    Ok(([(header::CONTENT_TYPE, "application/x-tar".to_string()), (header::CONTENT_DISPOSITION, disposition)], archive)
+        .into_response())
+}
+
+/// Any path the router does not know.
+pub async fn route_not_found() -> ApiError {
+    ApiError::not_found("route_not_found", "No such route")
+}
+
+pub async fn method_not_allowed() -> ApiError {
+    ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", "Method not allowed for this route")
+}
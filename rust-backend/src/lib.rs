@@ -0,0 +1,8 @@
+pub mod api_error;
+pub mod config;
+pub mod domain;
+pub mod engine;
+pub mod extract;
+pub mod handlers;
+pub mod router;
+pub mod service;
@@ -1,13 +1,16 @@
-mod domain;
-mod handlers;
-mod router;
-mod service;
-
-use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::info;
-use service::task_service::MemTaskService;
-use handlers::AppState;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+use phantom_be::config::{Config, StoreBackend};
+use phantom_be::router;
+use phantom_be::service::skill_repository::{MemSkillRepository, SkillRepository};
+use phantom_be::service::sqlite_skill_repository::SqliteSkillRepository;
+use phantom_be::service::sqlite_task_store::SqliteTaskStore;
+use phantom_be::service::task_service::{MemTaskService, TaskService, TaskStore};
+use phantom_be::handlers::AppState;
+use phantom_be::service::job_queue::JobQueue;
+use phantom_be::service::process::Pipeline;
 
 #[tokio::main]
 async fn main() {
@@ -22,20 +25,103 @@ async fn main() {
     // Load environment variables (optional, assuming dotenvy usage for keys)
     let _ = dotenvy::dotenv();
 
+    // 加载配置
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("configuration: {:#?}", config);
+
     // Initialize State
-    let task_service = MemTaskService::new();
+    let task_store: Arc<dyn TaskStore> = match config.storage.backend {
+        StoreBackend::Sqlite => {
+            info!("using sqlite task store at {}", config.storage.sqlite_path);
+            match SqliteTaskStore::open(&config.storage.sqlite_path) {
+                Ok(store) => Arc::new(store),
+                Err(e) => {
+                    error!("failed to open task store: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        StoreBackend::Memory => Arc::new(MemTaskService::new()),
+    };
+    let task_service = TaskService::new(task_store);
+    let skills: Arc<dyn SkillRepository> = match config.storage.backend {
+        StoreBackend::Sqlite => {
+            info!("using sqlite skill library at {}", config.storage.library_path);
+            match SqliteSkillRepository::open(&config.storage.library_path) {
+                Ok(repo) => Arc::new(repo),
+                Err(e) => {
+                    error!("failed to open skill library: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        StoreBackend::Memory => Arc::new(MemSkillRepository::new()),
+    };
+    let addr = config.listen_addr();
+    let pipeline = Arc::new(Pipeline::from_config(&config));
+    let jobs = JobQueue::start(&config.queue, task_service.clone(), pipeline.clone());
+    let drain_timeout = Duration::from_secs(config.queue.drain_timeout_secs);
+    let shutdown = CancellationToken::new();
     let app_state = Arc::new(AppState {
         task_service,
+        skills,
+        jobs: jobs.clone(),
+        pipeline,
+        config: Arc::new(config),
+        shutdown: shutdown.clone(),
     });
 
     // 构建路由
     let app = router::create_router(app_state);
 
     // 定义监听地址
-    let addr = SocketAddr::from(([0, 0, 0, 0], 64808));
     info!("listening on {}", addr);
 
     // 启动服务
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.cancel();
+        }
+    });
+    // The queue drains alongside the server's own shutdown rather than after it
+    let drain = async {
+        shutdown.cancelled().await;
+        info!("draining job queue");
+        jobs.shutdown(drain_timeout).await;
+    };
+    let serve = axum::serve(listener, app).with_graceful_shutdown(shutdown.clone().cancelled_owned());
+    let (served, ()) = tokio::join!(serve.into_future(), drain);
+    served.unwrap();
+}
+
+/// Ctrl-C, or SIGTERM on unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
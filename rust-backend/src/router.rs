@@ -1,9 +1,11 @@
 use axum::{
     Router,
+    middleware,
     routing::{get, post},
 };
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
+use crate::api_error;
 use crate::handlers::{self, AppState};
 
 pub fn create_router(state: Arc<AppState>) -> Router {
@@ -14,8 +16,214 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/v1/tasks/status", get(handlers::get_task_status))
         .route("/v1/tasks/artifact", get(handlers::get_artifact))
         .route("/v1/tasks/list", get(handlers::list_tasks))
+        .route("/v1/tasks/{id}/events", get(handlers::task_events))
+        .route("/v1/tasks/{id}/cancel", post(handlers::cancel_task))
+        .route("/v1/tasks/{id}/publish", post(handlers::publish_task))
         .route("/v1/parse/audio", post(handlers::parse_audio))
         .route("/v1/parse/video", post(handlers::parse_video))
+        .route("/v1/packages/instantiate", post(handlers::instantiate_package))
+        .route("/v1/schema/package", get(handlers::package_schema))
+        .route("/v1/skills", get(handlers::list_skills).post(handlers::create_skill))
+        .route("/v1/skills/import", post(handlers::import_skill))
+        .route(
+            "/v1/skills/{id}",
+            get(handlers::get_skill).put(handlers::update_skill).delete(handlers::delete_skill),
+        )
+        .route("/v1/skills/{id}/revisions", get(handlers::list_revisions))
+        .route("/v1/skills/{id}/revisions/{number}", get(handlers::get_revision))
+        .route("/v1/skills/{id}/diff", get(handlers::diff_skill))
+        .route("/v1/skills/{id}/import", post(handlers::reimport_skill))
+        .route("/v1/skills/{id}/export", get(handlers::export_skill))
+        .fallback(handlers::route_not_found)
+        .method_not_allowed_fallback(handlers::method_not_allowed)
+        .layer(middleware::from_fn(api_error::request_id))
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::domain::fixtures;
+    use crate::service::job_queue::JobQueue;
+    use crate::service::llm::ScriptedChatProvider;
+    use crate::service::process::Pipeline;
+    use crate::service::skill_repository::MemSkillRepository;
+    use crate::service::task_service::{MemTaskService, TaskService, TaskStore};
+    use crate::service::transcribe::CannedTranscriber;
+    use reqwest::Method;
+    use serde_json::{Value, json};
+    use std::time::Duration;
+    use tokio::task::JoinHandle;
+    use tokio_util::sync::CancellationToken;
+
+    fn package_document() -> Value {
+        fixtures::package().name("Open file").app("Notepad").vars(json!({ "COUNT": { "type": "number" } })).json()
+    }
+
+    enum Body {
+        Empty,
+        Json(String),
+        /// Sent without a content type.
+        Raw(&'static str),
+    }
+
+    struct Api {
+        base: String,
+        client: reqwest::Client,
+        shutdown: CancellationToken,
+        server: JoinHandle<()>,
+    }
+
+    impl Api {
+        async fn start() -> (Self, TaskService) {
+            let config = Config::default();
+            let task_service = TaskService::new(Arc::new(MemTaskService::new()));
+            let shutdown = CancellationToken::new();
+            let pipeline = Arc::new(Pipeline {
+                chat: Arc::new(ScriptedChatProvider::new()),
+                transcriber: Arc::new(CannedTranscriber::new()),
+                models: config.chat.models.clone(),
+                format_attempts: 1,
+                retry: config.retry.clone(),
+            });
+            let state = Arc::new(AppState {
+                task_service: task_service.clone(),
+                skills: Arc::new(MemSkillRepository::new()),
+                jobs: JobQueue::start(&config.queue, task_service.clone(), pipeline.clone()),
+                pipeline,
+                config: Arc::new(config),
+                shutdown: shutdown.clone(),
+            });
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let serve = axum::serve(listener, create_router(state)).with_graceful_shutdown(shutdown.clone().cancelled_owned());
+            let server = tokio::spawn(async move { serve.await.unwrap() });
+            let api = Api { base: format!("http://{}", addr), client: reqwest::Client::new(), shutdown, server };
+            (api, task_service)
+        }
+
+        async fn send(&self, method: Method, path: &str, body: Body) -> (u16, String, Value) {
+            let mut request = self.client.request(method, format!("{}{}", self.base, path));
+            request = match body {
+                Body::Empty => request,
+                Body::Json(text) => request.header("content-type", "application/json").body(text),
+                Body::Raw(text) => request.body(text),
+            };
+            let response = request.send().await.unwrap();
+            let status = response.status().as_u16();
+            let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+            let body = response.json().await.unwrap_or(Value::Null);
+            (status, request_id, body)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_route_answers_errors_with_the_envelope() {
+        let (api, tasks) = Api::start().await;
+        let task = tasks.create_task(String::new()).unwrap().entry_id;
+        let (status, _, skill) = api.send(Method::POST, "/v1/skills", Body::Json(json!({ "package": package_document() }).to_string())).await;
+        assert_eq!(status, 201, "{}", skill);
+        let skill = skill["id"].as_str().unwrap().to_string();
+        let package = || Body::Json(json!({ "package": package_document() }).to_string());
+
+        let cases = [
+            (Method::GET, "/v1/nope".to_string(), Body::Empty, 404, "route_not_found"),
+            (Method::POST, "/v1/health".to_string(), Body::Empty, 405, "method_not_allowed"),
+            (Method::POST, "/v1/tasks/create".to_string(), Body::Empty, 405, "method_not_allowed"),
+            (Method::GET, "/v1/tasks/status".to_string(), Body::Empty, 400, "invalid_query"),
+            (Method::GET, "/v1/tasks/status?entryId=missing".to_string(), Body::Empty, 404, "task_not_found"),
+            (Method::GET, format!("/v1/tasks/artifact?entryId={}", task), Body::Empty, 400, "invalid_query"),
+            (Method::GET, format!("/v1/tasks/artifact?entryId={}&track=bogus", task), Body::Empty, 400, "invalid_track"),
+            (Method::GET, format!("/v1/tasks/artifact?entryId={}&track=audio", task), Body::Empty, 404, "artifact_not_ready"),
+            (Method::DELETE, "/v1/tasks/list".to_string(), Body::Empty, 405, "method_not_allowed"),
+            (Method::GET, "/v1/tasks/missing/events".to_string(), Body::Empty, 404, "task_not_found"),
+            (Method::POST, "/v1/tasks/missing/cancel".to_string(), Body::Empty, 404, "task_not_found"),
+            (Method::POST, format!("/v1/tasks/{}/publish", task), Body::Empty, 409, "task_not_finished"),
+            (Method::POST, "/v1/parse/audio".to_string(), Body::Json("{".to_string()), 400, "malformed_json"),
+            (Method::POST, "/v1/parse/audio".to_string(), Body::Json(format!(r#"{{"entryId": "{}"}}"#, task)), 422, "invalid_body"),
+            (Method::POST, "/v1/parse/video".to_string(), Body::Raw("{}"), 415, "unsupported_media_type"),
+            (
+                Method::POST,
+                "/v1/parse/video".to_string(),
+                Body::Json(r#"{"entryId": "missing", "videoUrl": "http://v/1.mp4", "transcriptText": ""}"#.to_string()),
+                404,
+                "task_not_found",
+            ),
+            (
+                Method::POST,
+                "/v1/packages/instantiate".to_string(),
+                Body::Json(json!({ "package": package_document(), "vars": { "COUNT": "many" } }).to_string()),
+                422,
+                "invalid_vars",
+            ),
+            (Method::PUT, "/v1/schema/package".to_string(), Body::Empty, 405, "method_not_allowed"),
+            (Method::POST, "/v1/skills".to_string(), Body::Json(r#"{"package": {"steps": []}}"#.to_string()), 422, "invalid_skill"),
+            (Method::POST, "/v1/skills/import".to_string(), Body::Raw("not a bundle"), 422, "invalid_bundle"),
+            (Method::GET, "/v1/skills/missing".to_string(), Body::Empty, 404, "skill_not_found"),
+            (Method::PUT, "/v1/skills/missing".to_string(), package(), 404, "skill_not_found"),
+            (Method::DELETE, "/v1/skills/missing".to_string(), Body::Empty, 404, "skill_not_found"),
+            (Method::GET, "/v1/skills/missing/revisions".to_string(), Body::Empty, 404, "skill_not_found"),
+            (Method::GET, format!("/v1/skills/{}/revisions/latest", skill), Body::Empty, 400, "invalid_path"),
+            (Method::GET, format!("/v1/skills/{}/revisions/9", skill), Body::Empty, 404, "revision_not_found"),
+            (Method::GET, format!("/v1/skills/{}/diff?from=first", skill), Body::Empty, 400, "invalid_query"),
+            (Method::POST, format!("/v1/skills/{}/import", skill), Body::Raw("not a bundle"), 422, "invalid_bundle"),
+            (Method::GET, "/v1/skills/missing/export".to_string(), Body::Empty, 404, "skill_not_found"),
+        ];
+        for (method, path, body, status, code) in cases {
+            let label = format!("{} {}", method, path);
+            let (got, request_id, body) = api.send(method, &path, body).await;
+            assert_eq!(got, status, "{}: {}", label, body);
+            let error = &body["error"];
+            assert_eq!(error["code"], code, "{}: {}", label, body);
+            assert!(error["message"].as_str().is_some_and(|m| !m.is_empty()), "{}: {}", label, body);
+            assert!(error.as_object().unwrap().contains_key("details"), "{}", label);
+            assert_eq!(error["requestId"], request_id.as_str(), "{}", label);
+        }
+
+        // The routes without an error case still answer
+        for path in ["/v1/health", "/v1/tasks/create", "/v1/tasks/list", "/v1/schema/package", "/v1/skills"] {
+            assert_eq!(api.send(Method::GET, path, Body::Empty).await.0, 200, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_request_id_is_echoed() {
+        let (api, _) = Api::start().await;
+        let response = api
+            .client
+            .get(format!("{}/v1/skills/missing", api.base))
+            .header("x-request-id", "client-trace-7")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-request-id"], "client-trace-7");
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, json!({
+            "error": { "code": "skill_not_found", "message": "Skill not found", "details": null, "requestId": "client-trace-7" }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_ends_open_event_streams() {
+        let (api, tasks) = Api::start().await;
+        let task = tasks.create_task(String::new()).unwrap().entry_id;
+        let response = api.client.get(format!("{}/v1/tasks/{}/events", api.base, task)).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        // The task never finishes; without the shutdown token this stream would hold the server open
+        api.shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(5), api.server).await.expect("server should stop").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), response.text()).await.expect("stream should end").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_served_schema_matches_the_client_copy() {
+        let (api, _) = Api::start().await;
+        let served = api.client.get(format!("{}/v1/schema/package", api.base)).send().await.unwrap();
+        let served: Value = served.json().await.unwrap();
+        let client_copy: Value = serde_json::from_str(include_str!("../../schema.json")).unwrap();
+        assert_eq!(served, client_copy);
+    }
+}
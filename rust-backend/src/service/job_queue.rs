@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::config::QueueConfig;
+use crate::domain::package::Package;
+use crate::domain::pipeline_error::{PipelineError, Stage};
+use crate::domain::task::{Task, TaskStatus};
+use crate::domain::transcript;
+use crate::service::process::{self, Pipeline};
+use crate::service::task_runs::RunGuard;
+use crate::service::task_service::{TaskError, TaskService, TaskStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Track {
+    Audio,
+    Video,
+}
+
+impl Track {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Track::Audio => "audio",
+            Track::Video => "video",
+        }
+    }
+
+    /// The provider stage the track is named after.
+    pub fn stage(&self) -> Stage {
+        match self {
+            Track::Audio => Stage::Transcribe,
+            Track::Video => Stage::VideoAnalysis,
+        }
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseJob {
+    Audio { audio_url: String },
+    /// `prompt` is the transcript, given to the video model as context.
+    Video { video_url: String, prompt: String },
+}
+
+impl ParseJob {
+    pub fn track(&self) -> Track {
+        match self {
+            ParseJob::Audio { .. } => Track::Audio,
+            ParseJob::Video { .. } => Track::Video,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueError {
+    /// The track already has `capacity` jobs waiting.
+    Full { track: Track, capacity: usize },
+    ShuttingDown,
+    /// The task could not be moved to `Processing`.
+    Task(TaskError),
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Full { track, capacity } => {
+                write!(f, "{} queue is full ({} jobs waiting), try again later", track, capacity)
+            }
+            QueueError::ShuttingDown => write!(f, "Service is shutting down"),
+            QueueError::Task(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+struct QueuedJob {
+    entry_id: String,
+    job: ParseJob,
+    run: RunGuard,
+}
+
+/// Waiting jobs of one track; `ready` holds a permit per pushed job.
+struct Lane {
+    pending: Mutex<VecDeque<QueuedJob>>,
+    ready: Semaphore,
+}
+
+impl Lane {
+    fn new() -> Self {
+        Self { pending: Mutex::new(VecDeque::new()), ready: Semaphore::new(0) }
+    }
+}
+
+/// Parse jobs waiting for a worker, FIFO per track, with a fixed number of
+/// workers per track so a burst of uploads cannot start an unbounded number
+/// of provider calls.
+pub struct JobQueue {
+    task_service: TaskService,
+    pipeline: Arc<Pipeline>,
+    audio: Lane,
+    video: Lane,
+    max_queued: usize,
+    closed: AtomicBool,
+    /// Jobs a worker is executing right now.
+    running: Mutex<Vec<(String, Track)>>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl JobQueue {
+    /// Creates the queue and spawns its workers on the current runtime.
+    pub fn start(config: &QueueConfig, task_service: TaskService, pipeline: Arc<Pipeline>) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            task_service,
+            pipeline,
+            audio: Lane::new(),
+            video: Lane::new(),
+            max_queued: config.max_queued,
+            closed: AtomicBool::new(false),
+            running: Mutex::new(Vec::new()),
+            workers: Mutex::new(Vec::new()),
+        });
+        let tracks = [(Track::Audio, config.audio_workers), (Track::Video, config.video_workers)];
+        let handles = tracks
+            .into_iter()
+            .flat_map(|(track, count)| (0..count).map(move |_| track))
+            .map(|track| tokio::spawn(queue.clone().work(track)))
+            .collect();
+        *queue.workers.lock().unwrap() = handles;
+        queue
+    }
+
+    fn lane(&self, track: Track) -> &Lane {
+        match track {
+            Track::Audio => &self.audio,
+            Track::Video => &self.video,
+        }
+    }
+
+    /// Moves the task to `Processing` and queues the job. Returns the job's
+    /// 1-based position among the waiting jobs of its track.
+    pub fn submit(&self, entry_id: &str, job: ParseJob) -> Result<usize, QueueError> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(QueueError::ShuttingDown);
+        }
+        let track = job.track();
+        let lane = self.lane(track);
+        // The status changes under the lane lock, so a full queue never leaves a task `Processing`
+        let mut pending = lane.pending.lock().unwrap();
+        pending.retain(|queued| !queued.run.token().is_cancelled());
+        if pending.len() >= self.max_queued {
+            return Err(QueueError::Full { track, capacity: self.max_queued });
+        }
+
+        // Registered first, so a cancel racing the status change still reaches the run
+        let run = self.task_service.start_run(entry_id);
+        self.task_service
+            .set_status(entry_id, TaskStatus::Processing, &format!("{} parse queued", track))
+            .map_err(QueueError::Task)?;
+        pending.push_back(QueuedJob { entry_id: entry_id.to_string(), job, run });
+        let position = pending.len();
+        drop(pending);
+        lane.ready.add_permits(1);
+        Ok(position)
+    }
+
+    /// Where the task's jobs are in their queues; tracks that are not
+    /// waiting are left out.
+    pub fn positions(&self, entry_id: &str) -> BTreeMap<&'static str, usize> {
+        let mut positions = BTreeMap::new();
+        for track in [Track::Audio, Track::Video] {
+            let pending = self.lane(track).pending.lock().unwrap();
+            let waiting = pending.iter().filter(|queued| !queued.run.token().is_cancelled());
+            if let Some(index) = waiting.into_iter().position(|queued| queued.entry_id == entry_id) {
+                positions.insert(track.as_str(), index + 1);
+            }
+        }
+        positions
+    }
+
+    async fn work(self: Arc<Self>, track: Track) {
+        let lane = self.lane(track);
+        // Fails once the queue is closed for shutdown
+        while let Ok(permit) = lane.ready.acquire().await {
+            permit.forget();
+            let Some(queued) = lane.pending.lock().unwrap().pop_front() else {
+                continue;
+            };
+            if queued.run.token().is_cancelled() {
+                continue;
+            }
+            self.running.lock().unwrap().push((queued.entry_id.clone(), track));
+            let entry_id = queued.entry_id.clone();
+            self.execute(queued).await;
+            let mut running = self.running.lock().unwrap();
+            if let Some(index) = running.iter().position(|(id, t)| *id == entry_id && *t == track) {
+                running.remove(index);
+            }
+        }
+    }
+
+    async fn execute(&self, queued: QueuedJob) {
+        let QueuedJob { entry_id, job, run } = queued;
+        let task_service = &self.task_service;
+        let record_call = |call| {
+            if let Err(e) = task_service.record_provider_call(&entry_id, call) {
+                tracing::warn!("task {}: record_provider_call rejected: {}", entry_id, e);
+            }
+        };
+        match job {
+            ParseJob::Audio { audio_url } => match process::process_audio(&self.pipeline, audio_url, &record_call, run.token()).await {
+                Err(PipelineError::Cancelled { .. }) => tracing::info!("task {}: audio parse cancelled", entry_id),
+                Ok(result) => match task_service.update_audio_result(&entry_id, result.original_text, result.segments) {
+                    Ok(task) => store_alignment(task_service, &task),
+                    Err(e) => tracing::warn!("task {}: update_audio_result rejected: {}", entry_id, e),
+                },
+                Err(e) => fail_pipeline(task_service, &entry_id, &e),
+            },
+            ParseJob::Video { video_url, prompt } => {
+                let record_attempt = |attempt| {
+                    if let Err(e) = task_service.record_format_attempt(&entry_id, attempt) {
+                        tracing::warn!("task {}: record_format_attempt rejected: {}", entry_id, e);
+                    }
+                };
+                match process::process_video(&self.pipeline, video_url, prompt, &record_attempt, &record_call, run.token()).await {
+                    Err(PipelineError::Cancelled { .. }) => tracing::info!("task {}: video parse cancelled", entry_id),
+                    Ok(result) => {
+                        let skill_value = serde_json::to_value(result.skill).unwrap_or(Value::Null);
+                        let package_value = serde_json::to_value(result.package).unwrap_or(Value::Null);
+                        match task_service.update_video_result(&entry_id, skill_value, package_value) {
+                            Ok(task) => store_alignment(task_service, &task),
+                            Err(e) => tracing::warn!("task {}: update_video_result rejected: {}", entry_id, e),
+                        }
+                    }
+                    Err(e) => {
+                        if !e.violations().is_empty()
+                            && let Err(e) = task_service.record_validation_errors(&entry_id, e.violations().to_vec())
+                        {
+                            tracing::warn!("task {}: record_validation_errors rejected: {}", entry_id, e);
+                        }
+                        fail_pipeline(task_service, &entry_id, &e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stops taking jobs, fails the ones still waiting, and gives running
+    /// jobs `grace` to finish before failing and aborting them too.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.closed.store(true, Ordering::SeqCst);
+        for track in [Track::Audio, Track::Video] {
+            let lane = self.lane(track);
+            lane.ready.close();
+            let waiting: Vec<QueuedJob> = lane.pending.lock().unwrap().drain(..).collect();
+            for queued in waiting.iter().filter(|queued| !queued.run.token().is_cancelled()) {
+                let error = PipelineError::Interrupted { stage: track.stage(), started: false };
+                fail_pipeline(&self.task_service, &queued.entry_id, &error);
+            }
+        }
+
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        let aborts: Vec<_> = workers.iter().map(JoinHandle::abort_handle).collect();
+        if tokio::time::timeout(grace, futures_util::future::join_all(workers)).await.is_err() {
+            let interrupted = std::mem::take(&mut *self.running.lock().unwrap());
+            for (entry_id, track) in interrupted {
+                tracing::warn!("task {}: {} parse interrupted by shutdown", entry_id, track);
+                fail_pipeline(&self.task_service, &entry_id, &PipelineError::Interrupted { stage: track.stage(), started: true });
+            }
+            aborts.iter().for_each(|abort| abort.abort());
+        }
+    }
+}
+
+fn fail_pipeline(task_service: &TaskService, entry_id: &str, error: &PipelineError) {
+    if let Err(e) = task_service.mark_pipeline_failed(entry_id, error) {
+        tracing::warn!("task {}: mark_pipeline_failed rejected: {}", entry_id, e);
+    }
+}
+
+/// Joins the generated steps with the narration once both tracks are in,
+/// whichever finished last.
+fn store_alignment(task_service: &TaskService, task: &Task) {
+    if task.transcript_text.is_none() {
+        return;
+    }
+    let Some(package) = task.steps_package.clone().and_then(|p| serde_json::from_value::<Package>(p).ok()) else {
+        return;
+    };
+    let alignment = transcript::align_steps(&package, &task.transcript_segments);
+    if let Err(e) = task_service.update_alignment(&task.entry_id, alignment) {
+        tracing::warn!("task {}: update_alignment rejected: {}", task.entry_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::llm::{ScriptedChatProvider, StageModels};
+    use crate::service::retry::RetryConfig;
+    use crate::service::task_service::MemTaskService;
+    use crate::service::transcribe::{CannedTranscriber, Transcription};
+
+    /// Serves `/fast.mp3` at once and never answers `/slow.mp3`.
+    async fn media_server() -> String {
+        let app = axum::Router::new()
+            .route("/fast.mp3", axum::routing::get(|| async { "ID3" }))
+            .route("/slow.mp3", axum::routing::get(std::future::pending::<&'static str>));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn queue(max_queued: usize) -> (Arc<JobQueue>, TaskService) {
+        let transcript = Transcription {
+            text: "hello".to_string(),
+            language: None,
+            duration: None,
+            segments: Vec::new(),
+            model: String::new(),
+        };
+        let pipeline = Arc::new(Pipeline {
+            chat: Arc::new(ScriptedChatProvider::new()),
+            transcriber: Arc::new(CannedTranscriber::new().with("fast.mp3", transcript)),
+            models: StageModels::default(),
+            format_attempts: 1,
+            retry: RetryConfig::default(),
+        });
+        let service = TaskService::new(Arc::new(MemTaskService::new()));
+        let config = QueueConfig { audio_workers: 1, video_workers: 1, max_queued, drain_timeout_secs: 0 };
+        (JobQueue::start(&config, service.clone(), pipeline), service)
+    }
+
+    async fn wait_until(mut done: impl FnMut() -> bool) {
+        for _ in 0..200 {
+            if done() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    fn audio(url: String) -> ParseJob {
+        ParseJob::Audio { audio_url: url }
+    }
+
+    #[tokio::test]
+    async fn test_jobs_run_in_order() {
+        let media = media_server().await;
+        let (queue, service) = queue(8);
+        let ids: Vec<String> = (0..3).map(|_| service.create_task(String::new()).unwrap().entry_id).collect();
+        for id in &ids {
+            queue.submit(id, audio(format!("{}/fast.mp3", media))).unwrap();
+        }
+
+        wait_until(|| ids.iter().all(|id| service.get_task(id).unwrap().status == TaskStatus::AudioDone)).await;
+        let finished: Vec<_> = ids.iter().map(|id| service.get_task(id).unwrap().updated_at).collect();
+        assert!(finished.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(service.get_task(&ids[0]).unwrap().history[0].reason, "audio parse queued");
+    }
+
+    #[tokio::test]
+    async fn test_backpressure_positions_and_drain() {
+        let media = media_server().await;
+        let (queue, service) = queue(2);
+        let ids: Vec<String> = (0..4).map(|_| service.create_task(String::new()).unwrap().entry_id).collect();
+        let slow = || audio(format!("{}/slow.mp3", media));
+
+        // The only worker picks up the first job and stalls on it
+        queue.submit(&ids[0], slow()).unwrap();
+        wait_until(|| queue.positions(&ids[0]).is_empty()).await;
+        assert_eq!(queue.submit(&ids[1], slow()), Ok(1));
+        assert_eq!(queue.submit(&ids[2], slow()), Ok(2));
+        assert_eq!(queue.submit(&ids[3], slow()), Err(QueueError::Full { track: Track::Audio, capacity: 2 }));
+        assert_eq!(service.get_task(&ids[3]).unwrap().status, TaskStatus::Created);
+
+        // Cancelled jobs give up their place
+        service.cancel(&ids[1], "cancelled by user").unwrap();
+        assert_eq!(queue.positions(&ids[2]), BTreeMap::from([("audio", 1)]));
+
+        queue.shutdown(Duration::from_millis(50)).await;
+        assert_eq!(queue.submit(&ids[3], slow()), Err(QueueError::ShuttingDown));
+        let status = |i: usize| service.get_task(&ids[i]).unwrap();
+        let interrupted = status(0).failure.unwrap();
+        assert_eq!((interrupted.code.as_str(), interrupted.stage, interrupted.retryable), ("interrupted", Stage::Transcribe, true));
+        assert_eq!(status(1).status, TaskStatus::Cancelled);
+        assert_eq!(status(2).failure, Some(PipelineError::Interrupted { stage: Stage::Transcribe, started: false }.failure()));
+    }
+}
@@ -0,0 +1,104 @@
+use serde_json::Value;
+
+/// Pulls the JSON object out of a model reply.
+///
+/// Models wrap JSON in markdown fences, lead with prose ("Here is the
+/// package:") or append commentary. Every balanced `{ ... }` span is tried in
+/// order, skipping braces inside strings, and the first that parses wins. When
+/// none parses, the error of the first candidate is returned since that is the
+/// one the model meant.
+pub fn extract_json(text: &str) -> Result<Value, String> {
+    let mut first_error = None;
+    let mut search_from = 0;
+
+    while let Some(offset) = text[search_from..].find('{') {
+        let start = search_from + offset;
+        match balanced_end(&text[start..]) {
+            Some(len) => {
+                let candidate = &text[start..start + len];
+                match serde_json::from_str(candidate) {
+                    Ok(value) => return Ok(value),
+                    Err(e) => {
+                        first_error.get_or_insert_with(|| e.to_string());
+                    }
+                }
+                // Skip the whole span: a valid object nested in a broken package
+                // would only produce misleading schema errors
+                search_from = start + len;
+            }
+            None => {
+                // Unterminated: let serde describe where it stops making sense
+                if let Err(e) = serde_json::from_str::<Value>(&text[start..]) {
+                    first_error.get_or_insert_with(|| e.to_string());
+                }
+                break;
+            }
+        }
+    }
+
+    Err(first_error.unwrap_or_else(|| "no JSON object found in response".to_string()))
+}
+
+/// Length of the balanced `{ ... }` at the start of `text`, if it closes.
+fn balanced_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + c.len_utf8());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extracts_from_fences_prose_and_commentary() {
+        let cases = [
+            "```json\n{\"a\": 1}\n```",
+            "Here is the package:\n\n{\"a\": 1}\n\nLet me know if you need changes {or not}.",
+            "```\n{\"a\": 1}```trailing",
+            "{\"a\": 1}",
+        ];
+        for text in cases {
+            assert_eq!(extract_json(text), Ok(json!({"a": 1})), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_braces_inside_strings_are_ignored() {
+        let text = r#"Result: {"text": "click } then {", "n": [1, {"b": 2}]} done"#;
+        assert_eq!(extract_json(text), Ok(json!({"text": "click } then {", "n": [1, {"b": 2}]})));
+    }
+
+    #[test]
+    fn test_reports_why_the_json_is_broken() {
+        assert!(extract_json("no json here").unwrap_err().contains("no JSON object"));
+        let err = extract_json("{\"a\": 1,}").unwrap_err();
+        assert!(err.contains("trailing comma"), "{}", err);
+        assert!(extract_json("{\"a\": [1, 2").is_err());
+    }
+}
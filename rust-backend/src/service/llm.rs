@@ -0,0 +1,350 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::ChatConfig;
+use crate::service::retry::{self, RetryHint, Retryable};
+
+// Request
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MediaUrl {
+    pub url: String,
+}
+
+/// One part of a multimodal message.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: MediaUrl },
+    VideoUrl { video_url: MediaUrl },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: MessageContent,
+}
+
+impl ChatMessage {
+    pub fn system(text: impl Into<String>) -> Self {
+        Self { role: Role::System, content: MessageContent::Text(text.into()) }
+    }
+
+    pub fn user(text: impl Into<String>) -> Self {
+        Self { role: Role::User, content: MessageContent::Text(text.into()) }
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self { role: Role::Assistant, content: MessageContent::Text(text.into()) }
+    }
+
+    pub fn user_parts(parts: Vec<ContentPart>) -> Self {
+        Self { role: Role::User, content: MessageContent::Parts(parts) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: Value },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+impl ChatRequest {
+    pub fn new(model: impl Into<String>, messages: Vec<ChatMessage>) -> Self {
+        Self { model: model.into(), messages, temperature: None, response_format: None }
+    }
+}
+
+// Response
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+    pub model: String,
+    pub content: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+impl ChatResponse {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self { model: String::new(), content: content.into(), finish_reason: None, usage: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatError {
+    /// The request never produced an HTTP response.
+    Transport(String),
+    /// The provider answered with a non-success status.
+    Status { status: u16, body: String, retry_after: Option<Duration> },
+    /// The body was not a chat completion.
+    Decode(String),
+    /// A completion without any message content.
+    EmptyResponse,
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatError::Transport(msg) => write!(f, "chat request failed: {}", msg),
+            ChatError::Status { status, body, .. } => write!(f, "chat provider returned {}: {}", status, body),
+            ChatError::Decode(msg) => write!(f, "invalid chat response: {}", msg),
+            ChatError::EmptyResponse => write!(f, "No content in response"),
+        }
+    }
+}
+
+impl std::error::Error for ChatError {}
+
+impl Retryable for ChatError {
+    fn retry_hint(&self) -> RetryHint {
+        match self {
+            ChatError::Transport(_) => RetryHint::Transient,
+            ChatError::Status { status, retry_after, .. } => RetryHint::for_status(*status, *retry_after),
+            ChatError::Decode(_) | ChatError::EmptyResponse => RetryHint::Permanent,
+        }
+    }
+
+    fn timed_out(after: Duration) -> Self {
+        ChatError::Transport(format!("no response within {:?}", after))
+    }
+}
+
+/// A chat-completion backend.
+pub trait ChatProvider: Send + Sync {
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> BoxFuture<'a, Result<ChatResponse, ChatError>>;
+}
+
+// Configuration
+
+pub const DEFAULT_CHAT_BASE_URL: &str = "https://openrouter.ai/api/v1";
+pub const DEFAULT_VIDEO_ANALYSIS_MODEL: &str = "bytedance-seed/seed-1.6";
+pub const DEFAULT_SKILL_FORMAT_MODEL: &str = "z-ai/glm-4.7";
+
+/// Which model each pipeline stage asks for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StageModels {
+    pub video_analysis: String,
+    pub skill_format: String,
+}
+
+impl Default for StageModels {
+    fn default() -> Self {
+        Self {
+            video_analysis: DEFAULT_VIDEO_ANALYSIS_MODEL.to_string(),
+            skill_format: DEFAULT_SKILL_FORMAT_MODEL.to_string(),
+        }
+    }
+}
+
+// OpenAI-compatible provider
+
+#[derive(Deserialize)]
+struct CompletionBody {
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    choices: Vec<CompletionChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CompletionMessage {
+    content: Option<String>,
+}
+
+/// Any `/chat/completions` endpoint speaking the OpenAI wire format
+/// (OpenRouter, SiliconFlow, vLLM, ...).
+pub struct OpenAiCompatible {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatible {
+    pub fn new(base_url: impl Into<String>, api_key: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    pub fn from_config(config: &ChatConfig) -> Self {
+        Self::new(&config.base_url, config.api_key.as_ref().map(|k| k.expose().to_string()))
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("{}/chat/completions", self.base_url)
+    }
+
+    async fn send(&self, request: &ChatRequest) -> Result<ChatResponse, ChatError> {
+        let mut builder = self.client.post(self.endpoint()).json(request);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        let response = builder.send().await.map_err(|e| ChatError::Transport(e.to_string()))?;
+
+        let status = response.status();
+        let retry_after = retry::retry_after(response.headers());
+        let body = response.text().await.map_err(|e| ChatError::Transport(e.to_string()))?;
+        if !status.is_success() {
+            return Err(ChatError::Status { status: status.as_u16(), body, retry_after });
+        }
+
+        let completion: CompletionBody =
+            serde_json::from_str(&body).map_err(|e| ChatError::Decode(e.to_string()))?;
+        let choice = completion.choices.into_iter().next().ok_or(ChatError::EmptyResponse)?;
+        let content = choice.message.content.ok_or(ChatError::EmptyResponse)?;
+        Ok(ChatResponse {
+            model: completion.model,
+            content,
+            finish_reason: choice.finish_reason,
+            usage: completion.usage,
+        })
+    }
+}
+
+impl ChatProvider for OpenAiCompatible {
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> BoxFuture<'a, Result<ChatResponse, ChatError>> {
+        Box::pin(self.send(request))
+    }
+}
+
+// Scripted provider
+
+/// Replays queued responses in order and records every request, for tests and
+/// offline runs. Runs out with `ChatError::EmptyResponse`.
+#[derive(Default)]
+pub struct ScriptedChatProvider {
+    responses: Mutex<VecDeque<Result<ChatResponse, ChatError>>>,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl ScriptedChatProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reply(self, content: impl Into<String>) -> Self {
+        self.responses.lock().unwrap().push_back(Ok(ChatResponse::text(content)));
+        self
+    }
+
+    pub fn fail(self, error: ChatError) -> Self {
+        self.responses.lock().unwrap().push_back(Err(error));
+        self
+    }
+
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl ChatProvider for ScriptedChatProvider {
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> BoxFuture<'a, Result<ChatResponse, ChatError>> {
+        self.requests.lock().unwrap().push(request.clone());
+        let next = self.responses.lock().unwrap().pop_front();
+        Box::pin(async move { next.unwrap_or(Err(ChatError::EmptyResponse)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_multimodal_request_wire_format() {
+        let mut request = ChatRequest::new(
+            "m",
+            vec![
+                ChatMessage::system("sys"),
+                ChatMessage::user_parts(vec![
+                    ContentPart::Text { text: "describe".to_string() },
+                    ContentPart::VideoUrl { video_url: MediaUrl { url: "https://v/1.mp4".to_string() } },
+                ]),
+            ],
+        );
+        request.response_format = Some(ResponseFormat::JsonObject);
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": "m",
+                "messages": [
+                    { "role": "system", "content": "sys" },
+                    { "role": "user", "content": [
+                        { "type": "text", "text": "describe" },
+                        { "type": "video_url", "video_url": { "url": "https://v/1.mp4" } }
+                    ]}
+                ],
+                "response_format": { "type": "json_object" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scripted_provider_replays_in_order() {
+        let provider = ScriptedChatProvider::new()
+            .reply("first")
+            .fail(ChatError::Status { status: 429, body: "slow down".to_string(), retry_after: None });
+        let request = ChatRequest::new("m", vec![ChatMessage::user("hi")]);
+
+        assert_eq!(provider.chat(&request).await.unwrap().content, "first");
+        assert!(matches!(provider.chat(&request).await, Err(ChatError::Status { status: 429, .. })));
+        assert_eq!(provider.chat(&request).await, Err(ChatError::EmptyResponse));
+        assert_eq!(provider.requests().len(), 3);
+    }
+}
@@ -1,2 +1,12 @@
 pub mod task_service;
+pub mod sqlite_task_store;
+pub mod skill_repository;
+pub mod sqlite_skill_repository;
+pub mod task_events;
+pub mod task_runs;
+pub mod job_queue;
+pub mod json_extract;
+pub mod llm;
 pub mod process;
+pub mod retry;
+pub mod transcribe;
@@ -1,157 +1,296 @@
+use crate::config::Config;
 use crate::domain::skill::Skill;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use crate::service::llm::{
+    ChatError, ChatMessage, ChatProvider, ChatRequest, ContentPart, MediaUrl, OpenAiCompatible, StageModels,
+};
+use crate::domain::package::Package;
+use crate::domain::pipeline_error::{PipelineError, Stage};
+use crate::domain::schema::{PACKAGE_SCHEMA, SchemaViolation, validate_package_json};
+use crate::domain::task::{FormatAttempt, ProviderCall};
+use crate::domain::transcript::TranscriptSegment;
+use crate::service::json_extract::extract_json;
+use crate::service::retry::{self, RetryConfig, RetryHint, Retryable};
+use crate::service::transcribe::{AudioClip, OpenAiTranscriber, TranscribeError, Transcriber};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::env;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AudioAnalysisResult {
     pub original_text: String,
     pub summary_info: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Audio length in seconds, when the provider reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<TranscriptSegment>,
 }
 
-const OPENROUTER_API_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+/// Providers and per-stage settings the pipeline runs with.
+pub struct Pipeline {
+    pub chat: Arc<dyn ChatProvider>,
+    pub transcriber: Arc<dyn Transcriber>,
+    pub models: StageModels,
+    /// Formatter calls per video, the first included.
+    pub format_attempts: u32,
+    /// Retries of each provider call, per stage.
+    pub retry: RetryConfig,
+}
 
-fn get_api_key() -> Result<String, String> {
-    env::var("OPENROUTER_API_KEY").map_err(|_| "OPENROUTER_API_KEY not set".to_string())
+impl Pipeline {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            chat: Arc::new(OpenAiCompatible::from_config(&config.chat)),
+            transcriber: Arc::new(OpenAiTranscriber::from_config(&config.transcribe)),
+            models: config.chat.models.clone(),
+            format_attempts: config.pipeline.format_attempts,
+            retry: config.retry.clone(),
+        }
+    }
 }
 
-async fn create_client(api_key: &str) -> reqwest::Client {
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", api_key)).expect("Invalid API Key");
-    auth_value.set_sensitive(true);
-    headers.insert(AUTHORIZATION, auth_value);
-
-    reqwest::Client::builder()
-        .default_headers(headers)
-        .build()
-        .expect("Failed to build client")
+/// Called with every provider call once it has succeeded or given up.
+pub type CallObserver<'a> = &'a (dyn Fn(ProviderCall) + Send + Sync);
+
+/// Provider errors, once retries gave up, in the terms of [`PipelineError`].
+trait ProviderError: Retryable {
+    fn into_pipeline_error(self, stage: Stage) -> PipelineError;
 }
 
-use reqwest::multipart;
+/// Maps an upstream status; the body stays in the log.
+fn status_error(stage: Stage, status: u16, retry_after: Option<Duration>) -> PipelineError {
+    match status {
+        401 | 403 => PipelineError::ProviderAuth { stage, status },
+        429 => PipelineError::RateLimited { stage, retry_after },
+        415 => PipelineError::UnsupportedMedia { stage, reason: format!("rejected by the {} provider", stage) },
+        _ => PipelineError::BadResponse { stage, status: Some(status), reason: String::new() },
+    }
+}
 
-pub async fn process_audio(audio_url: String) -> Result<AudioAnalysisResult, Box<dyn std::error::Error>> {
-    // SiliconFlow API Key
-    let api_key = "sk-hvmvjwljevimjtluwqjmxcbxkznkopthjjpyzotamnqcympy";
-    let client = reqwest::Client::new();
+impl ProviderError for ChatError {
+    fn into_pipeline_error(self, stage: Stage) -> PipelineError {
+        match self {
+            ChatError::Status { status, retry_after, .. } => status_error(stage, status, retry_after),
+            ChatError::Transport(reason) | ChatError::Decode(reason) => {
+                PipelineError::BadResponse { stage, status: None, reason }
+            }
+            ChatError::EmptyResponse => {
+                PipelineError::BadResponse { stage, status: None, reason: "the completion had no content".to_string() }
+            }
+        }
+    }
+}
 
-    // 1. Download Audio
-    println!("[Audio Process] Downloading audio from: {}", audio_url);
-    let audio_response = reqwest::get(&audio_url).await?;
-    if !audio_response.status().is_success() {
-         return Err(format!("Failed to download audio: {}", audio_response.status()).into());
+impl ProviderError for TranscribeError {
+    fn into_pipeline_error(self, stage: Stage) -> PipelineError {
+        match self {
+            TranscribeError::Status { status, retry_after, .. } => status_error(stage, status, retry_after),
+            TranscribeError::Transport(reason) | TranscribeError::Decode(reason) => {
+                PipelineError::BadResponse { stage, status: None, reason }
+            }
+        }
     }
-    let audio_bytes = audio_response.bytes().await?;
-    let filename = audio_url.split('/').last().unwrap_or("audio.mp3").to_string();
-
-    // 2. Prepare Multipart
-    // Note: Assuming MP3 or similar. MIME type guessing could be improved but simple one works for many APIs.
-    let part = multipart::Part::bytes(audio_bytes.to_vec())
-        .file_name(filename)
-        .mime_str("audio/mpeg")?; 
-    
-    let form = multipart::Form::new()
-        .text("model", "TeleAI/TeleSpeechASR")
-        .part("file", part);
-
-    // 3. Send Request
-    let api_url = "https://api.siliconflow.cn/v1/audio/transcriptions";
-    println!("[Audio Process] Sending request to SiliconFlow: {}", api_url);
-
-    let response = client.post(api_url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        println!("[Audio Process] API Error Response: {}", error_text);
-        return Err(format!("API request failed: {}", error_text).into());
+}
+
+/// Runs `call` under the retry policy of `stage` unless `cancel` fires first,
+/// and reports how many attempts it took. Dropping the call aborts any HTTP
+/// request it has in flight.
+async fn call_provider<T, E, F, Fut>(
+    pipeline: &Pipeline,
+    stage: Stage,
+    on_call: CallObserver<'_>,
+    cancel: &CancellationToken,
+    call: F,
+) -> Result<T, PipelineError>
+where
+    E: ProviderError,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let policy = pipeline.retry.policy(stage);
+    let Some((result, attempts)) = cancel.run_until_cancelled(policy.run(stage, call)).await else {
+        return Err(PipelineError::Cancelled { stage });
+    };
+    on_call(ProviderCall {
+        stage: stage.to_string(),
+        attempts,
+        error: result.as_ref().err().map(ToString::to_string),
+        at: chrono::Utc::now(),
+    });
+    result.map_err(|e| {
+        println!("[{}] API Error Response: {}", stage, e);
+        e.into_pipeline_error(stage)
+    })
+}
+
+/// Why fetching the source media failed.
+#[derive(Debug)]
+pub enum DownloadError {
+    Transport(String),
+    Status { status: u16, retry_after: Option<Duration> },
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Transport(msg) => write!(f, "Failed to download audio: {}", msg),
+            DownloadError::Status { status, .. } => write!(f, "Failed to download audio: HTTP {}", status),
+        }
     }
+}
 
-    let response_json: Value = response.json().await?;
-    println!("[Audio Process] Response JSON: {}", serde_json::to_string_pretty(&response_json).unwrap());
+impl std::error::Error for DownloadError {}
 
-    // 4. Parse Result
-    let text = response_json["text"].as_str().unwrap_or("").to_string();
+impl Retryable for DownloadError {
+    fn retry_hint(&self) -> RetryHint {
+        match self {
+            DownloadError::Transport(_) => RetryHint::Transient,
+            DownloadError::Status { status, retry_after } => RetryHint::for_status(*status, *retry_after),
+        }
+    }
 
-    Ok(AudioAnalysisResult {
-        original_text: text,
-        summary_info: "Transcribed by TeleSpeechASR".to_string(),
-    })
+    fn timed_out(after: Duration) -> Self {
+        DownloadError::Transport(format!("no response within {:?}", after))
+    }
+}
+
+impl ProviderError for DownloadError {
+    fn into_pipeline_error(self, _stage: Stage) -> PipelineError {
+        match self {
+            DownloadError::Transport(reason) => PipelineError::DownloadFailed { status: None, reason },
+            DownloadError::Status { status, .. } => PipelineError::DownloadFailed { status: Some(status), reason: String::new() },
+        }
+    }
+}
+
+async fn download(url: &str) -> Result<Vec<u8>, DownloadError> {
+    let response = reqwest::get(url).await.map_err(|e| DownloadError::Transport(e.to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(DownloadError::Status { status: status.as_u16(), retry_after: retry::retry_after(response.headers()) });
+    }
+    let bytes = response.bytes().await.map_err(|e| DownloadError::Transport(e.to_string()))?;
+    Ok(bytes.to_vec())
 }
 
-use std::path::Path;
+pub async fn process_audio(
+    pipeline: &Pipeline,
+    audio_url: String,
+    on_call: CallObserver<'_>,
+    cancel: &CancellationToken,
+) -> Result<AudioAnalysisResult, PipelineError> {
+    // 1. Download Audio
+    println!("[Audio Process] Downloading audio from: {}", audio_url);
+    let audio_bytes = call_provider(pipeline, Stage::Download, on_call, cancel, || download(&audio_url)).await?;
+    let filename = audio_url.split('/').next_back().unwrap_or("audio.mp3").to_string();
+    let clip = AudioClip { filename, bytes: audio_bytes };
+    if clip.bytes.is_empty() || clip.mime_type() == "application/octet-stream" {
+        return Err(PipelineError::UnsupportedMedia {
+            stage: Stage::Download,
+            reason: format!("{} is not a recognised audio file", clip.filename),
+        });
+    }
+
+    // 2. Transcribe
+    println!("[Audio Process] Transcribing {} ({})", clip.filename, clip.mime_type());
+    let transcribe = || pipeline.transcriber.transcribe(&clip);
+    let transcription = call_provider(pipeline, Stage::Transcribe, on_call, cancel, transcribe).await?;
+    println!("[Audio Process] Transcript: {}", transcription.text);
 
-async fn analyze_video_content(video_url: String, user_prompt: String) -> Result<String, Box<dyn std::error::Error>> {
-    let api_key = get_api_key()?;
-    let client = create_client(&api_key).await;
+    Ok(AudioAnalysisResult {
+        original_text: transcription.text,
+        summary_info: format!("Transcribed by {}", transcription.model),
+        language: transcription.language,
+        duration: transcription.duration,
+        segments: transcription.segments,
+    })
+}
 
+async fn analyze_video_content(
+    pipeline: &Pipeline,
+    video_url: String,
+    user_prompt: String,
+    on_call: CallObserver<'_>,
+    cancel: &CancellationToken,
+) -> Result<String, PipelineError> {
     let system_prompt = "You are a video analysis assistant. \
     Analyze the video to extract mouse movements, clicks, and element details. \
     Serialize the output strictly into a JSON object matching the 'Skill' data model. \
     Ensure all fields like 'skill_id', 'steps', 'target', 'locators' are populated correctly based on the visual evidence. \
     Return ONLY the valid JSON, no markdown.";
 
-    let payload = json!({
-        "model": "bytedance-seed/seed-1.6",
-        "messages": [
-            {
-                "role": "system",
-                "content": system_prompt
-            },
-            {
-                "role": "user",
-                "content": [
-                    {
-                        "type": "text",
-                        "text": user_prompt
-                    },
-                    {
-                        "type": "video_url",
-                        "video_url": {
-                            "url": video_url
-                        }
-                    }
-                ]
-            }
-        ]
-    });
-
-    println!("[Video Analysis] Request Payload: {}", serde_json::to_string_pretty(&payload).unwrap());
-
-    let response = client.post(OPENROUTER_API_URL)
-        .json(&payload)
-        .send()
-        .await?;
+    let request = ChatRequest::new(
+        pipeline.models.video_analysis.clone(),
+        vec![
+            ChatMessage::system(system_prompt),
+            ChatMessage::user_parts(vec![
+                ContentPart::Text { text: user_prompt },
+                ContentPart::VideoUrl { video_url: MediaUrl { url: video_url } },
+            ]),
+        ],
+    );
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        println!("[Video Analysis] API Error Response: {}", error_text);
-        return Err(format!("API request failed: {}", error_text).into());
-    }
+    println!("[Video Analysis] Request Payload: {}", serde_json::to_string_pretty(&request).unwrap_or_default());
 
-    let response_json: Value = response.json().await?;
-    println!("[Video Analysis] Response JSON: {}", serde_json::to_string_pretty(&response_json).unwrap());
-    
-    let content = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or("No content in response")?;
+    let chat = || pipeline.chat.chat(&request);
+    let response = call_provider(pipeline, Stage::VideoAnalysis, on_call, cancel, chat).await?;
+    println!("[Video Analysis] Response content: {}", response.content);
 
     // Return the raw content (which might be an escaped JSON string)
-    Ok(content.to_string())
+    Ok(response.content)
 }
 
-async fn format_skill_with_llm(raw_content: String) -> Result<Skill, Box<dyn std::error::Error>> {
-    let api_key = get_api_key()?;
-    let client = create_client(&api_key).await;
+/// Called with every formatter attempt as soon as it is judged.
+pub type AttemptObserver<'a> = &'a (dyn Fn(FormatAttempt) + Send + Sync);
 
-    // Read schema file
-    let schema_path = "/root/srcs/SkillFlow/rust-backend/schema.json";
-    let schema_content = tokio::fs::read_to_string(schema_path).await.unwrap_or_else(|_| "{}".to_string());
+/// Turns a formatter reply into a package, or says what is wrong with it in
+/// terms the formatter can act on.
+fn parse_package(content: &str) -> Result<Package, PipelineError> {
+    let unreadable = |message: String| PipelineError::SchemaInvalid {
+        violations: vec![SchemaViolation { path: String::new(), schema_path: None, message }],
+    };
+    let document: Value = extract_json(content).map_err(|e| unreadable(format!("Response is not valid JSON: {}", e)))?;
+    // Schema first: its pointers say exactly what is wrong, serde errors do not
+    validate_package_json(&document).map_err(|violations| PipelineError::SchemaInvalid { violations })?;
+    let package: Package = serde_json::from_value(document).map_err(|e| unreadable(e.to_string()))?;
+
+    let diagnostics = package.validate();
+    for d in diagnostics.iter().filter(|d| !d.is_error()) {
+        println!("[Skill Formatting] Package warning: {}", d);
+    }
+    let errors: Vec<SchemaViolation> = diagnostics.iter().filter(|d| d.is_error()).map(SchemaViolation::from).collect();
+    if !errors.is_empty() {
+        return Err(PipelineError::PackageInvalid { violations: errors });
+    }
+
+    Ok(package)
+}
 
+fn repair_prompt(error: &PipelineError) -> String {
+    let problems = match error.violations() {
+        [] => format!("- {}", error),
+        violations => violations.iter().map(|v| format!("- {}", v)).collect::<Vec<_>>().join("\n"),
+    };
+    format!(
+        "Your previous answer was rejected:\n{}\n\n\
+        Fix exactly these problems and keep everything else unchanged. \
+        Return the corrected JSON only, no markdown, no explanations.",
+        problems
+    )
+}
+
+async fn format_skill_with_llm(
+    pipeline: &Pipeline,
+    raw_content: String,
+    on_attempt: AttemptObserver<'_>,
+    on_call: CallObserver<'_>,
+    cancel: &CancellationToken,
+) -> Result<Package, PipelineError> {
     let system_prompt = format!(
         "You are a strict JSON formatter. \
         Your goal is to convert the input text (which contains a JSON representation of a Skill) into a perfectly formatted JSON object that adheres to the provided Schema. \
@@ -160,127 +299,277 @@ async fn format_skill_with_llm(raw_content: String) -> Result<Skill, Box<dyn std
         1. Fix any malformed JSON.\n \
         2. Ensure the structure matches the Schema (especially 'steps', 'selectors' etc. if applicable, though the input might use a slightly different 'Skill' model, try to map it to valid JSON).\n \
         3. Return ONLY the valid JSON string, no markdown, no explanations.",
-        schema_content
+        PACKAGE_SCHEMA
     );
-
-    let payload = json!({
-        "model": "z-ai/glm-4.7",
-        "messages": [
-            {
-                "role": "system",
-                "content": system_prompt
-            },
-            {
-                "role": "user",
-                "content": raw_content
+    let conversation = vec![ChatMessage::system(system_prompt), ChatMessage::user(raw_content)];
+    let mut messages = conversation.clone();
+
+    let mut attempt = 1;
+    loop {
+        let request = ChatRequest::new(pipeline.models.skill_format.clone(), messages);
+        println!("[Skill Formatting] Attempt {} sent to {}", attempt, request.model);
+
+        let chat = || pipeline.chat.chat(&request);
+        let response = call_provider(pipeline, Stage::SkillFormat, on_call, cancel, chat).await?;
+        println!("[Skill Formatting] Response content: {}", response.content);
+
+        let error = match parse_package(&response.content) {
+            Ok(package) => {
+                on_attempt(FormatAttempt { attempt, output: response.content, error: None, at: chrono::Utc::now() });
+                return Ok(package);
             }
-        ]
-    });
+            Err(e) => e,
+        };
+        on_attempt(FormatAttempt {
+            attempt,
+            output: response.content.clone(),
+            error: Some(error.to_string()),
+            at: chrono::Utc::now(),
+        });
+        if attempt >= pipeline.format_attempts {
+            return Err(error);
+        }
+        println!("[Skill Formatting] Attempt {} rejected, asking for a repair: {}", attempt, error);
+
+        // Only the latest answer is shown: older ones would just repeat fixed mistakes
+        messages = conversation.clone();
+        messages.push(ChatMessage::assistant(response.content));
+        messages.push(ChatMessage::user(repair_prompt(&error)));
+        attempt += 1;
+    }
+}
 
-    println!("[Skill Formatting] Request Payload sent to GLM-4.7");
+/// Output of the video track: the validated package and its Skill view.
+pub struct VideoResult {
+    pub package: Package,
+    pub skill: Skill,
+}
 
-    let response = client.post(OPENROUTER_API_URL)
-        .json(&payload)
-        .send()
-        .await?;
+pub async fn process_video(
+    pipeline: &Pipeline,
+    video_url: String,
+    user_prompt: String,
+    on_attempt: AttemptObserver<'_>,
+    on_call: CallObserver<'_>,
+    cancel: &CancellationToken,
+) -> Result<VideoResult, PipelineError> {
+    // 1. Analyze video with the video analysis model
+    let raw_analysis = analyze_video_content(pipeline, video_url, user_prompt, on_call, cancel).await?;
+    
+    // 2. Format output with the formatting model using Schema
+    let package = format_skill_with_llm(pipeline, raw_analysis, on_attempt, on_call, cancel).await?;
+    let skill = Skill::from(&package);
+    
+    Ok(VideoResult { package, skill })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::llm::{MessageContent, ScriptedChatProvider};
+    use crate::service::transcribe::{CannedTranscriber, Transcription};
+    use crate::domain::transcript::TranscriptSegment;
+
+    use crate::domain::fixtures;
+    use serde_json::json;
+
+    fn package() -> Value {
+        fixtures::package().name("Open file").app("Notepad").json()
+    }
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        println!("[Skill Formatting] API Error Response: {}", error_text);
-        return Err(format!("Formatting API request failed: {}", error_text).into());
+    /// The package in a fenced block, as formatter models tend to answer.
+    fn reply(document: Value) -> String {
+        format!("```json\n{}\n```", serde_json::to_string_pretty(&document).unwrap())
     }
 
-    let response_json: Value = response.json().await?;
-    println!("[Skill Formatting] Response JSON: {}", serde_json::to_string_pretty(&response_json).unwrap());
+    #[tokio::test]
+    async fn test_process_video_uses_stage_models() {
+        let chat = Arc::new(ScriptedChatProvider::new().reply("{\"raw\": true}").reply(reply(package())));
+        let pipeline = Pipeline {
+            chat: chat.clone(),
+            transcriber: Arc::new(CannedTranscriber::new()),
+            models: StageModels { video_analysis: "vision".to_string(), skill_format: "formatter".to_string() },
+            format_attempts: 1,
+            retry: RetryConfig::default(),
+        };
+
+        let result = process_video(&pipeline, "https://v/1.mp4".to_string(), "open a file".to_string(), &|_| {}, &|_| {}, &CancellationToken::new())
+            .await
+            .unwrap();
+        let skill = result.skill;
+        assert_eq!(result.package.steps[0].id, "s1");
+        assert_eq!(skill.software, "Notepad");
+        assert_eq!(skill.total_steps, 1);
+        assert_eq!(skill.steps[0].target.name, "menu_file");
+
+        let requests = chat.requests();
+        assert_eq!(requests[0].model, "vision");
+        assert_eq!(requests[1].model, "formatter");
+        assert_eq!(requests[1].messages[1].content, MessageContent::Text("{\"raw\": true}".to_string()));
+    }
 
-    let content = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or("No content in response")?;
+    #[tokio::test]
+    async fn test_process_audio_with_canned_transcript() {
+        let app = axum::Router::new().route("/media/demo.mp3", axum::routing::get(|| async { "ID3 audio" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let transcript = Transcription {
+            text: "click file".to_string(),
+            language: Some("en".to_string()),
+            duration: Some(1.5),
+            segments: vec![TranscriptSegment { start: 0.0, end: 1.5, text: "click file".to_string(), confidence: None }],
+            model: String::new(),
+        };
+        let pipeline = Pipeline {
+            chat: Arc::new(ScriptedChatProvider::new()),
+            transcriber: Arc::new(CannedTranscriber::new().with("demo.mp3", transcript)),
+            models: StageModels::default(),
+            format_attempts: 1,
+            retry: RetryConfig::default(),
+        };
+
+        let result = process_audio(&pipeline, format!("http://{}/media/demo.mp3", addr), &|_| {}, &CancellationToken::new()).await.unwrap();
+        assert_eq!(result.original_text, "click file");
+        assert_eq!(result.summary_info, "Transcribed by canned");
+        assert_eq!(result.language.as_deref(), Some("en"));
+        assert_eq!(result.segments.len(), 1);
+    }
 
-    let clean_content = content.trim()
-        .trim_start_matches("```json")
-        .trim_start_matches("```")
-        .trim_end_matches("```");
+    #[tokio::test]
+    async fn test_cancel_aborts_a_stalled_download() {
+        let app = axum::Router::new().route("/media/slow.mp3", axum::routing::get(std::future::pending::<&'static str>));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let pipeline = Arc::new(Pipeline {
+            chat: Arc::new(ScriptedChatProvider::new()),
+            transcriber: Arc::new(CannedTranscriber::new()),
+            models: StageModels::default(),
+            format_attempts: 1,
+            retry: RetryConfig::default(),
+        });
+        let cancel = CancellationToken::new();
+        let run = {
+            let (pipeline, cancel) = (pipeline.clone(), cancel.clone());
+            let url = format!("http://{}/media/slow.mp3", addr);
+            tokio::spawn(async move {
+                let result = process_audio(&pipeline, url, &|_| {}, &cancel).await;
+                matches!(result, Err(PipelineError::Cancelled { stage: Stage::Download }))
+            })
+        };
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        cancel.cancel();
+        let cancelled = tokio::time::timeout(std::time::Duration::from_secs(5), run).await.unwrap().unwrap();
+        assert!(cancelled);
+    }
 
-    let package: crate::domain::package::Package = serde_json::from_str(clean_content)?;
-    
-    // Map Package to Skill
-    // Note: Skill struct in domain/skill.rs is different from Package struct in domain/package.rs
-    // The previous implementation expected Skill directly.
-    // Since we are now using schema.json which defines Package, GLM returns Package.
-    // We need to convert Package to Skill or update Skill definition.
-    // Given the task is to fix "missing field `type`" error which likely comes from Skill vs Package mismatch,
-    // and the user input shows GLM outputting "Package" format.
-    // Let's assume we need to return a Skill object as per function signature.
-    
-    // Create a simplified Skill from Package
-    let skill = Skill {
-        skill_id: package.package.name.clone(), // Using name as ID for now or generate one
-        name: package.package.name,
-        software: package.app.name,
-        version: package.version,
-        description: package.package.description.unwrap_or_default(),
-        steps: package.steps.into_iter().enumerate().map(|(i, s)| {
-            use crate::domain::skill::{Step, Target, Locator, ActionType, TargetType, LocatorMethod};
-            use crate::domain::package::StepOperation;
-            
-            let empty_ref = crate::domain::package::SelectorOrRef::Ref(crate::domain::package::SelectorRef { reference: "".to_string() });
-            
-            let (action_type, target_val) = match &s.op {
-                StepOperation::Click(c) => (ActionType::Click, &c.target),
-                StepOperation::Type(t) => (ActionType::Input, t.target.as_ref().unwrap_or(&empty_ref)),
-                StepOperation::Drag(d) => (ActionType::Drag, &d.from),
-                StepOperation::Scroll(sc) => (ActionType::Drag, sc.target.as_ref().unwrap_or(&empty_ref)),
-                StepOperation::Hotkey(_) => (ActionType::Shortcut, &empty_ref),
-                StepOperation::Wait(w) => (ActionType::Menu, &w.until),
-                StepOperation::Assert(a) => (ActionType::Menu, &a.expect),
-            };
-
-            // Extract target name from ref or inline
-            let target_name = match target_val {
-                crate::domain::package::SelectorOrRef::Ref(r) => r.reference.trim_start_matches("#/selectors/").to_string(),
-                _ => "inline_target".to_string(),
-            };
-
-            Step {
-                step_id: (i + 1) as u32,
-                action_type,
-                target: Target {
-                    target_type: TargetType::Button, // Defaulting
-                    name: target_name,
-                    locators: vec![
-                        Locator {
-                            method: LocatorMethod::Text,
-                            value: json!(""),
-                            priority: 1,
-                        }
-                    ],
-                },
-                instruction: s.name.unwrap_or_else(|| format!("Step {}", i+1)),
-                wait_after: 0.0,
-                parameters: json!({}),
-                confidence: 1.0,
+    #[tokio::test]
+    async fn test_flaky_download_is_retried_and_recorded() {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let flaky = move || {
+            let first = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0;
+            async move {
+                if first { Err(axum::http::StatusCode::BAD_GATEWAY) } else { Ok("ID3 audio") }
             }
-        }).collect(),
-        total_steps: 0, // Will update
-        estimated_duration: 0,
-        tags: package.package.tags,
-        created_at: None,
-        source_type: crate::domain::skill::SourceType::VideoAnalysis,
-    };
-    
-    let mut final_skill = skill;
-    final_skill.total_steps = final_skill.steps.len() as u32;
+        };
+        let app = axum::Router::new().route("/media/demo.mp3", axum::routing::get(flaky));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let pipeline = Pipeline {
+            chat: Arc::new(ScriptedChatProvider::new()),
+            transcriber: Arc::new(CannedTranscriber::new()),
+            models: StageModels::default(),
+            format_attempts: 1,
+            retry: RetryConfig { base_delay_ms: 1, max_delay_ms: 1, ..RetryConfig::default() },
+        };
+        let calls = std::sync::Mutex::new(Vec::new());
+        let record = |call: ProviderCall| calls.lock().unwrap().push(call);
+
+        // No canned transcript: the provider's 404 is permanent
+        let url = format!("http://{}/media/demo.mp3", addr);
+        let err = process_audio(&pipeline, url, &record, &CancellationToken::new()).await.err().unwrap();
+        assert!(matches!(err, PipelineError::BadResponse { stage: Stage::Transcribe, status: Some(404), .. }), "{:?}", err);
+        assert!(!err.retryable());
+
+        let calls = calls.into_inner().unwrap();
+        assert_eq!(calls.iter().map(|c| (c.stage.as_str(), c.attempts)).collect::<Vec<_>>(), [("download", 2), ("transcribe", 1)]);
+        assert_eq!(calls[0].error, None);
+        assert!(calls[1].error.is_some());
+    }
 
-    Ok(final_skill)
-}
+    fn attempt_log() -> (Arc<std::sync::Mutex<Vec<FormatAttempt>>>, impl Fn(FormatAttempt) + Send + Sync) {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = log.clone();
+        (log, move |attempt| sink.lock().unwrap().push(attempt))
+    }
 
-pub async fn process_video(video_url: String, user_prompt: String) -> Result<Skill, Box<dyn std::error::Error>> {
-    // 1. Analyze video with Seed model
-    let raw_analysis = analyze_video_content(video_url, user_prompt).await?;
-    
-    // 2. Format output with GLM-4.7 using Schema
-    let formatted_skill = format_skill_with_llm(raw_analysis).await?;
-    
-    Ok(formatted_skill)
+    #[tokio::test]
+    async fn test_schema_violations_reject_the_package() {
+        let mut invalid = package();
+        invalid["app"] = json!({});
+        let invalid = reply(invalid);
+        let pipeline = Pipeline {
+            chat: Arc::new(ScriptedChatProvider::new().reply("{}").reply(invalid.clone()).reply(invalid)),
+            transcriber: Arc::new(CannedTranscriber::new()),
+            models: StageModels::default(),
+            format_attempts: 2,
+            retry: RetryConfig::default(),
+        };
+        let (log, observer) = attempt_log();
+
+        let err = process_video(&pipeline, "https://v/1.mp4".to_string(), String::new(), &observer, &|_| {}, &CancellationToken::new()).await.err().unwrap();
+        let PipelineError::SchemaInvalid { violations } = &err else { panic!("expected a schema rejection, got {:?}", err) };
+        assert_eq!(violations[0].path, "/app");
+        assert_eq!(err.failure().code, "schema_invalid");
+
+        let attempts = log.lock().unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts.iter().all(|a| a.error.as_deref().is_some_and(|e| e.contains("/app"))));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_output_is_repaired() {
+        let mut tapped = package();
+        tapped["steps"][0]["op"] = json!("tap");
+        let chat = Arc::new(
+            ScriptedChatProvider::new()
+                .reply("{}")
+                .reply("Sure! {\"version\": \"0.1\", oops}")
+                .reply(reply(tapped))
+                .reply(reply(package())),
+        );
+        let pipeline = Pipeline {
+            chat: chat.clone(),
+            transcriber: Arc::new(CannedTranscriber::new()),
+            models: StageModels::default(),
+            format_attempts: 3,
+            retry: RetryConfig::default(),
+        };
+        let (log, observer) = attempt_log();
+
+        let result = process_video(&pipeline, "https://v/1.mp4".to_string(), String::new(), &observer, &|_| {}, &CancellationToken::new()).await.unwrap();
+        assert_eq!(result.package.steps[0].id, "s1");
+
+        let attempts = log.lock().unwrap();
+        assert_eq!(attempts.iter().map(|a| a.attempt).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(attempts[0].error.as_deref().unwrap().contains("not valid JSON"));
+        assert!(attempts[1].error.as_deref().unwrap().contains("/steps/0"));
+        assert_eq!(attempts[2].error, None);
+
+        // Each repair shows the rejected answer and what was wrong with it
+        let repair = &chat.requests()[3].messages;
+        assert_eq!(repair.len(), 4);
+        assert_eq!(repair[2].role, crate::service::llm::Role::Assistant);
+        match &repair[3].content {
+            MessageContent::Text(text) => assert!(text.contains("- /steps/0"), "{}", text),
+            other => panic!("unexpected repair prompt {:?}", other),
+        }
+    }
 }
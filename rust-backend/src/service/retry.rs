@@ -0,0 +1,319 @@
+//! Retries for upstream provider calls.
+//!
+//! A failure is either transient (timeouts, dropped connections, 408, 429,
+//! 5xx) and worth another attempt, or permanent (bad credentials, rejected
+//! requests) and returned at once. Transient failures are retried with
+//! jittered exponential backoff, or after the provider's `Retry-After`, until
+//! the stage runs out of attempts or of its time budget.
+
+use std::fmt;
+use std::hash::{BuildHasher, RandomState};
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::pipeline_error::Stage;
+
+/// What a failed attempt says about the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetryHint {
+    Permanent,
+    /// Retry after the backoff delay.
+    Transient,
+    /// Retry after the delay the provider asked for.
+    After(Duration),
+}
+
+impl RetryHint {
+    /// Classifies a non-success HTTP status.
+    pub fn for_status(status: u16, retry_after: Option<Duration>) -> Self {
+        match (status, retry_after) {
+            (408 | 429 | 500..=599, Some(delay)) => RetryHint::After(delay),
+            (408 | 429 | 500..=599, None) => RetryHint::Transient,
+            _ => RetryHint::Permanent,
+        }
+    }
+}
+
+/// Errors a [`RetryPolicy`] knows how to judge.
+pub trait Retryable: fmt::Display {
+    fn retry_hint(&self) -> RetryHint;
+
+    /// The error reported for an attempt cut off after `after`.
+    fn timed_out(after: Duration) -> Self;
+}
+
+/// The `Retry-After` header, in either of its forms (seconds or HTTP date).
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    parse_retry_after(headers.get(RETRY_AFTER)?.to_str().ok()?)
+}
+
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // A date in the past means "now"
+    Some((at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Attempts and time one stage may spend on a call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StageRetry {
+    /// Calls per stage, the first included.
+    pub max_attempts: u32,
+    /// How long one attempt may take before it counts as a timeout.
+    pub timeout_secs: u64,
+    /// Wall time for all attempts and the waits between them.
+    pub budget_secs: u64,
+}
+
+impl StageRetry {
+    const fn new(max_attempts: u32, timeout_secs: u64, budget_secs: u64) -> Self {
+        Self { max_attempts, timeout_secs, budget_secs }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Delay before the first retry; doubled for every further one.
+    pub base_delay_ms: u64,
+    /// Upper bound of the backoff delay, before jitter.
+    pub max_delay_ms: u64,
+    pub download: StageRetry,
+    pub transcribe: StageRetry,
+    pub video_analysis: StageRetry,
+    pub skill_format: StageRetry,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            download: StageRetry::new(3, 120, 300),
+            transcribe: StageRetry::new(3, 300, 600),
+            video_analysis: StageRetry::new(3, 600, 1200),
+            skill_format: StageRetry::new(3, 300, 600),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn stage(&self, stage: Stage) -> &StageRetry {
+        match stage {
+            Stage::Download => &self.download,
+            Stage::Transcribe => &self.transcribe,
+            Stage::VideoAnalysis => &self.video_analysis,
+            Stage::SkillFormat => &self.skill_format,
+        }
+    }
+
+    pub fn policy(&self, stage: Stage) -> RetryPolicy {
+        let limits = self.stage(stage);
+        RetryPolicy {
+            max_attempts: limits.max_attempts,
+            attempt_timeout: Duration::from_secs(limits.timeout_secs),
+            budget: Duration::from_secs(limits.budget_secs),
+            base_delay: Duration::from_millis(self.base_delay_ms),
+            max_delay: Duration::from_millis(self.max_delay_ms),
+        }
+    }
+
+    /// Problems with the limits, phrased like [`crate::config::Config::validate`].
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.base_delay_ms > self.max_delay_ms {
+            problems.push("retry.base_delay_ms must not exceed retry.max_delay_ms".to_string());
+        }
+        for stage in [Stage::Download, Stage::Transcribe, Stage::VideoAnalysis, Stage::SkillFormat] {
+            let limits = self.stage(stage);
+            if !(1..=10).contains(&limits.max_attempts) {
+                problems.push(format!("retry.{}.max_attempts must be 1..=10, got {}", stage, limits.max_attempts));
+            }
+            if limits.timeout_secs == 0 || limits.budget_secs == 0 {
+                problems.push(format!("retry.{} timeout_secs and budget_secs must be at least 1", stage));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub attempt_timeout: Duration,
+    pub budget: Duration,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (1-based): the doubled base delay,
+    /// capped, then jittered down by up to half so concurrent tasks that
+    /// failed together do not come back together.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let doubled = self.base_delay.saturating_mul(1 << retry.saturating_sub(1).min(20));
+        let delay = doubled.min(self.max_delay);
+        let jitter = RandomState::new().hash_one(retry) as f64 / u64::MAX as f64;
+        delay.mul_f64(0.5 + jitter / 2.0)
+    }
+
+    /// Calls `call` until it succeeds, fails permanently, or the policy is
+    /// exhausted. Returns the last result and how many attempts were made.
+    pub async fn run<T, E, F, Fut>(&self, stage: Stage, mut call: F) -> (Result<T, E>, u32)
+    where
+        E: Retryable,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let timeout = self.attempt_timeout.min(self.budget.saturating_sub(started.elapsed()));
+            let error = match tokio::time::timeout(timeout, call()).await {
+                Ok(Ok(value)) => return (Ok(value), attempt),
+                Ok(Err(error)) => error,
+                Err(_) => E::timed_out(timeout),
+            };
+            let delay = match error.retry_hint() {
+                RetryHint::Permanent => return (Err(error), attempt),
+                RetryHint::Transient => self.backoff(attempt),
+                RetryHint::After(delay) => delay,
+            };
+            if attempt >= self.max_attempts || started.elapsed() + delay >= self.budget {
+                return (Err(error), attempt);
+            }
+            tracing::warn!("{} attempt {} failed, retrying in {:?}: {}", stage, attempt, delay, error);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::llm::{ChatError, ChatMessage, ChatProvider, ChatRequest, OpenAiCompatible};
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            attempt_timeout: Duration::from_millis(200),
+            budget: Duration::from_secs(10),
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    /// A chat endpoint that answers request `n` (0-based) with `script(n)`.
+    async fn flaky_provider(
+        script: fn(usize) -> Option<(StatusCode, &'static str)>,
+    ) -> (OpenAiCompatible, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let handler = move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match script(n) {
+                    Some((StatusCode::TOO_MANY_REQUESTS, after)) => {
+                        (StatusCode::TOO_MANY_REQUESTS, [("retry-after", after)], "slow down").into_response()
+                    }
+                    Some((status, body)) => (status, body.to_string()).into_response(),
+                    // Never answers: the attempt times out
+                    None => std::future::pending().await,
+                }
+            }
+        };
+        let app = axum::Router::new().route("/chat/completions", axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (OpenAiCompatible::new(format!("http://{}", addr), Some("sk-test".to_string())), calls)
+    }
+
+    const COMPLETION: &str = r#"{"model":"m","choices":[{"message":{"content":"ok"},"finish_reason":"stop"}]}"#;
+
+    fn request() -> ChatRequest {
+        ChatRequest::new("m", vec![ChatMessage::user("hi")])
+    }
+
+    #[test]
+    fn test_classifies_statuses() {
+        assert_eq!(RetryHint::for_status(503, None), RetryHint::Transient);
+        assert_eq!(RetryHint::for_status(429, Some(Duration::from_secs(2))), RetryHint::After(Duration::from_secs(2)));
+        assert_eq!(RetryHint::for_status(408, None), RetryHint::Transient);
+        for permanent in [400, 401, 403, 404, 422] {
+            assert_eq!(RetryHint::for_status(permanent, None), RetryHint::Permanent);
+        }
+        assert_eq!(ChatError::Transport("connection reset".to_string()).retry_hint(), RetryHint::Transient);
+        assert_eq!(ChatError::EmptyResponse.retry_hint(), RetryHint::Permanent);
+    }
+
+    #[test]
+    fn test_parses_retry_after() {
+        assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let delay = parse_retry_after(&later).unwrap();
+        assert!(delay > Duration::from_secs(85) && delay <= Duration::from_secs(90), "{:?}", delay);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_is_capped() {
+        let policy = RetryPolicy { base_delay: Duration::from_millis(100), max_delay: Duration::from_millis(1000), ..policy(5) };
+        for (retry, full) in [(1, 100), (2, 200), (3, 400), (5, 1000), (40, 1000)] {
+            let delay = policy.backoff(retry);
+            let full = Duration::from_millis(full);
+            assert!(delay >= full / 2 && delay <= full, "retry {}: {:?}", retry, delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let (provider, calls) = flaky_provider(|n| match n {
+            0 => Some((StatusCode::BAD_GATEWAY, "upstream hiccup")),
+            1 => None,
+            2 => Some((StatusCode::TOO_MANY_REQUESTS, "1")),
+            _ => Some((StatusCode::OK, COMPLETION)),
+        })
+        .await;
+        let request = request();
+
+        let started = Instant::now();
+        let (result, attempts) = policy(5).run(Stage::SkillFormat, || provider.chat(&request)).await;
+        assert_eq!(result.unwrap().content, "ok");
+        assert_eq!(attempts, 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert!(started.elapsed() >= Duration::from_secs(1), "Retry-After was not honoured");
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failures_and_exhausted_budgets_stop_at_once() {
+        let (provider, calls) = flaky_provider(|_| Some((StatusCode::UNAUTHORIZED, "bad key"))).await;
+        let request = request();
+        let (result, attempts) = policy(5).run(Stage::VideoAnalysis, || provider.chat(&request)).await;
+        assert!(matches!(result, Err(ChatError::Status { status: 401, .. })));
+        assert_eq!((attempts, calls.load(Ordering::SeqCst)), (1, 1));
+
+        // Waiting as long as the provider asks would overrun the budget
+        let (provider, _) = flaky_provider(|_| Some((StatusCode::TOO_MANY_REQUESTS, "3600"))).await;
+        let (result, attempts) = policy(5).run(Stage::VideoAnalysis, || provider.chat(&request)).await;
+        assert!(matches!(result, Err(ChatError::Status { status: 429, retry_after: Some(_), .. })));
+        assert_eq!(attempts, 1);
+
+        let (provider, calls) = flaky_provider(|_| Some((StatusCode::SERVICE_UNAVAILABLE, "down"))).await;
+        let (result, attempts) = policy(3).run(Stage::VideoAnalysis, || provider.chat(&request)).await;
+        assert!(result.is_err());
+        assert_eq!((attempts, calls.load(Ordering::SeqCst)), (3, 3));
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::bundle::{Assets, Bundle, BundleError};
+use crate::domain::diff::{PackageDiff, diff_packages};
+use crate::domain::library::{SkillEntry, SkillQuery, SkillRevision};
+use crate::domain::package::Package;
+use crate::domain::schema::{SchemaViolation, validate_package_json};
+use crate::domain::skill::{Skill, SourceType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkillError {
+    NotFound,
+    RevisionNotFound(u32),
+    /// The submitted document is not a valid package, or does not convert to one.
+    Invalid(Vec<SchemaViolation>),
+    /// A `.skillflow` bundle that cannot be read, or whose assets do not
+    /// match its templates.
+    Bundle(BundleError),
+    Storage(String),
+}
+
+impl fmt::Display for SkillError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkillError::NotFound => write!(f, "Skill not found"),
+            SkillError::RevisionNotFound(number) => write!(f, "Revision {} not found", number),
+            SkillError::Invalid(violations) => {
+                let parts: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                write!(f, "Invalid skill: {}", parts.join("; "))
+            }
+            SkillError::Bundle(e) => write!(f, "Invalid bundle: {}", e),
+            SkillError::Storage(msg) => write!(f, "Skill storage error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SkillError {}
+
+/// What a client submits to the library: a package, or a Skill to be
+/// converted into one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillDocument {
+    Package(Value),
+    Skill(Box<Skill>),
+}
+
+impl SkillDocument {
+    /// Validates the document and returns the package with its Skill view.
+    pub fn into_parts(self) -> Result<(Package, Skill), SkillError> {
+        match self {
+            SkillDocument::Package(document) => {
+                let package = checked_package(&document)?;
+                let skill = Skill { source_type: SourceType::Manual, ..Skill::from(&package) };
+                Ok((package, skill))
+            }
+            SkillDocument::Skill(skill) => {
+                let package = Package::try_from(skill.as_ref()).map_err(|e| {
+                    SkillError::Invalid(vec![SchemaViolation { path: "/skill".to_string(), schema_path: None, message: e.to_string() }])
+                })?;
+                let document = serde_json::to_value(&package).map_err(|e| SkillError::Storage(e.to_string()))?;
+                Ok((checked_package(&document)?, *skill))
+            }
+        }
+    }
+}
+
+/// Schema, then serde, then semantic checks, as for generated packages.
+fn checked_package(document: &Value) -> Result<Package, SkillError> {
+    validate_package_json(document).map_err(SkillError::Invalid)?;
+    let package: Package = serde_json::from_value(document.clone()).map_err(|e| {
+        SkillError::Invalid(vec![SchemaViolation { path: String::new(), schema_path: None, message: e.to_string() }])
+    })?;
+    let errors: Vec<SchemaViolation> =
+        package.validate().iter().filter(|d| d.is_error()).map(SchemaViolation::from).collect();
+    if errors.is_empty() { Ok(package) } else { Err(SkillError::Invalid(errors)) }
+}
+
+/// Persistence for the skill library.
+///
+/// Backends only store entries, revisions and assets. Document checks,
+/// bundle import/export and search ranking live in the default methods, the
+/// same split as [`TaskStore`](crate::service::task_service::TaskStore).
+/// Every write of an entry also stores its [`SkillEntry::current_revision`];
+/// revisions are never changed afterwards and go away only with the skill.
+pub trait SkillRepository: Send + Sync {
+    fn insert(&self, entry: SkillEntry) -> Result<SkillEntry, SkillError>;
+
+    fn get(&self, id: &str) -> Option<SkillEntry>;
+
+    fn list(&self) -> Vec<SkillEntry>;
+
+    /// Edits one entry; same contract as
+    /// [`TaskStore::update_task`](crate::service::task_service::TaskStore::update_task).
+    fn update(
+        &self,
+        id: &str,
+        apply: &mut dyn FnMut(&mut SkillEntry) -> Result<(), SkillError>,
+    ) -> Result<SkillEntry, SkillError>;
+
+    fn delete(&self, id: &str) -> Result<(), SkillError>;
+
+    /// All revisions of a skill, oldest first; `None` for an unknown skill.
+    fn revisions(&self, id: &str) -> Option<Vec<SkillRevision>>;
+
+    fn revision(&self, id: &str, number: u32) -> Option<SkillRevision>;
+
+    /// Template images of a skill, keyed by the path its selectors use. They
+    /// belong to the skill, not to a revision.
+    fn assets(&self, id: &str) -> Option<Assets>;
+
+    fn set_assets(&self, id: &str, assets: Assets) -> Result<(), SkillError>;
+
+    fn create(&self, document: SkillDocument) -> Result<SkillEntry, SkillError> {
+        let (package, skill) = document.into_parts()?;
+        self.insert(SkillEntry::new(Uuid::new_v4().to_string(), package, skill, None))
+    }
+
+    /// Adds the package of a finished task.
+    fn publish(&self, task_id: &str, package: Value) -> Result<SkillEntry, SkillError> {
+        let package = checked_package(&package)?;
+        let entry =
+            SkillEntry::from_package(Uuid::new_v4().to_string(), package, SourceType::VideoAnalysis, Some(task_id.to_string()));
+        self.insert(entry)
+    }
+
+    fn replace(&self, id: &str, document: SkillDocument) -> Result<SkillEntry, SkillError> {
+        let (package, skill) = document.into_parts()?;
+        self.update(id, &mut |entry| {
+            entry.revise(package.clone(), skill.clone());
+            Ok(())
+        })
+    }
+
+    /// Adds the package of a verified bundle, with its assets.
+    fn import(&self, bundle: Bundle) -> Result<SkillEntry, SkillError> {
+        bundle.verify().map_err(SkillError::Bundle)?;
+        let document = serde_json::to_value(&bundle.package).map_err(|e| SkillError::Storage(e.to_string()))?;
+        let entry = self.create(SkillDocument::Package(document))?;
+        if let Err(e) = self.set_assets(&entry.id, bundle.assets) {
+            let _ = self.delete(&entry.id);
+            return Err(e);
+        }
+        Ok(entry)
+    }
+
+    /// Makes the package of a verified bundle the next revision of an existing
+    /// skill and replaces its assets. This is how a skill whose templates were
+    /// changed by [`SkillRepository::replace`] becomes exportable again.
+    fn import_into(&self, id: &str, bundle: Bundle) -> Result<SkillEntry, SkillError> {
+        bundle.verify().map_err(SkillError::Bundle)?;
+        let document = serde_json::to_value(&bundle.package).map_err(|e| SkillError::Storage(e.to_string()))?;
+        let (package, skill) = SkillDocument::Package(document).into_parts()?;
+        self.set_assets(id, bundle.assets)?;
+        self.update(id, &mut |entry| {
+            entry.revise(package.clone(), skill.clone());
+            Ok(())
+        })
+    }
+
+    /// The current package with its assets; fails if they no longer match.
+    fn export(&self, id: &str) -> Result<Bundle, SkillError> {
+        let entry = self.get(id).ok_or(SkillError::NotFound)?;
+        let bundle = Bundle::new(entry.package, self.assets(id).unwrap_or_default());
+        bundle.verify().map_err(SkillError::Bundle)?;
+        Ok(bundle)
+    }
+
+    /// Changes from revision `from` to revision `to` of a skill.
+    fn diff(&self, id: &str, from: u32, to: u32) -> Result<PackageDiff, SkillError> {
+        let load = |number| match self.revision(id, number) {
+            Some(revision) => Ok(revision),
+            None if self.get(id).is_none() => Err(SkillError::NotFound),
+            None => Err(SkillError::RevisionNotFound(number)),
+        };
+        let (old, new) = (load(from)?, load(to)?);
+        Ok(diff_packages(&old.package, &new.package))
+    }
+
+    /// Matching entries, most relevant first, then most recently updated.
+    fn search(&self, query: &SkillQuery) -> Vec<SkillEntry> {
+        let mut scored: Vec<(u32, SkillEntry)> =
+            self.list().into_iter().filter_map(|entry| entry.score(query).map(|score| (score, entry))).collect();
+        scored.sort_by(|(a, x), (b, y)| b.cmp(a).then(y.updated_at.cmp(&x.updated_at)));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+/// In-memory library; everything is lost on restart.
+#[derive(Debug, Clone, Default)]
+pub struct MemSkillRepository {
+    entries: Arc<Mutex<HashMap<String, SkillEntry>>>,
+    revisions: Arc<Mutex<HashMap<String, Vec<SkillRevision>>>>,
+    assets: Arc<Mutex<HashMap<String, Assets>>>,
+}
+
+impl MemSkillRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record_revision(&self, entry: &SkillEntry) {
+        let mut revisions = self.revisions.lock().unwrap();
+        let history = revisions.entry(entry.id.clone()).or_default();
+        if history.last().is_none_or(|r| r.number < entry.revision) {
+            history.push(entry.current_revision());
+        }
+    }
+}
+
+impl SkillRepository for MemSkillRepository {
+    fn insert(&self, entry: SkillEntry) -> Result<SkillEntry, SkillError> {
+        self.entries.lock().unwrap().insert(entry.id.clone(), entry.clone());
+        self.record_revision(&entry);
+        Ok(entry)
+    }
+
+    fn get(&self, id: &str) -> Option<SkillEntry> {
+        self.entries.lock().unwrap().get(id).cloned()
+    }
+
+    fn list(&self) -> Vec<SkillEntry> {
+        self.entries.lock().unwrap().values().cloned().collect()
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        apply: &mut dyn FnMut(&mut SkillEntry) -> Result<(), SkillError>,
+    ) -> Result<SkillEntry, SkillError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(id).ok_or(SkillError::NotFound)?;
+        let mut updated = entry.clone();
+        apply(&mut updated)?;
+        *entry = updated.clone();
+        self.record_revision(&updated);
+        Ok(updated)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), SkillError> {
+        self.entries.lock().unwrap().remove(id).ok_or(SkillError::NotFound)?;
+        self.revisions.lock().unwrap().remove(id);
+        self.assets.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    fn revisions(&self, id: &str) -> Option<Vec<SkillRevision>> {
+        self.revisions.lock().unwrap().get(id).cloned()
+    }
+
+    fn revision(&self, id: &str, number: u32) -> Option<SkillRevision> {
+        let revisions = self.revisions.lock().unwrap();
+        revisions.get(id)?.iter().find(|r| r.number == number).cloned()
+    }
+
+    fn assets(&self, id: &str) -> Option<Assets> {
+        self.get(id)?;
+        Some(self.assets.lock().unwrap().get(id).cloned().unwrap_or_default())
+    }
+
+    fn set_assets(&self, id: &str, assets: Assets) -> Result<(), SkillError> {
+        self.get(id).ok_or(SkillError::NotFound)?;
+        self.assets.lock().unwrap().insert(id.to_string(), assets);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fixtures;
+    use serde_json::json;
+
+    fn package(name: &str, app: &str, tags: &[&str]) -> Value {
+        fixtures::package().name(name).app(app).app_versions(Some("2023"), None).tags(tags).json()
+    }
+
+    #[test]
+    fn test_crud_and_search() {
+        let repo = MemSkillRepository::new();
+        let export = repo.create(SkillDocument::Package(package("Export PNG", "Photoshop", &["export"]))).unwrap();
+        let crop = repo.publish("task-1", package("Crop", "Photoshop", &["image"])).unwrap();
+        repo.create(SkillDocument::Package(package("Save note", "Notepad", &["export"]))).unwrap();
+
+        assert_eq!(crop.source_task.as_deref(), Some("task-1"));
+        assert_eq!(crop.skill.source_type, SourceType::VideoAnalysis);
+        assert_eq!(repo.get(&export.id).unwrap().skill.name, "Export PNG");
+
+        let query = SkillQuery { q: Some("export".to_string()), app: Some("photoshop".to_string()), version: None };
+        let hits: Vec<String> = repo.search(&query).into_iter().map(|e| e.package.package.name).collect();
+        assert_eq!(hits, ["Export PNG"]);
+        let query = SkillQuery { version: Some("2022".to_string()), ..Default::default() };
+        assert!(repo.search(&query).is_empty());
+
+        let updated = repo.replace(&export.id, SkillDocument::Skill(Box::new(crop.skill.clone()))).unwrap();
+        assert_eq!(updated.package.package.name, "Crop");
+        assert_eq!(updated.skill.skill_id, export.id);
+        assert_eq!((updated.revision, updated.hash.clone()), (2, crop.hash.clone()));
+
+        let history = repo.revisions(&export.id).unwrap();
+        assert_eq!(history.iter().map(|r| r.number).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(repo.revision(&export.id, 1).unwrap().package.package.name, "Export PNG");
+        let diff = repo.diff(&export.id, 1, 2).unwrap();
+        assert!(diff.summary.contains(&"Changed package".to_string()));
+        assert_eq!(repo.diff(&export.id, 1, 3).unwrap_err(), SkillError::RevisionNotFound(3));
+
+        repo.delete(&export.id).unwrap();
+        assert_eq!(repo.delete(&export.id), Err(SkillError::NotFound));
+        assert!(repo.revisions(&export.id).is_none());
+        assert_eq!(repo.list().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_documents_are_rejected() {
+        let repo = MemSkillRepository::new();
+        let mut broken = package("Broken", "Notepad", &[]);
+        broken["steps"][0]["target"] = json!({ "$ref": "#/selectors/missing" });
+        match repo.create(SkillDocument::Package(broken)) {
+            Err(SkillError::Invalid(violations)) => assert_eq!(violations[0].path, "/steps/0/target/$ref"),
+            other => panic!("expected rejection, got {:?}", other),
+        }
+        assert!(repo.publish("t", json!({ "version": "0.1" })).is_err());
+        assert!(repo.list().is_empty());
+    }
+
+    #[test]
+    fn test_import_and_export_bundles() {
+        let repo = MemSkillRepository::new();
+        let package: Package = serde_json::from_value(package_value_with_template("assets/file.png")).unwrap();
+
+        let missing = Bundle::new(package.clone(), Assets::new());
+        assert!(matches!(repo.import(missing), Err(SkillError::Bundle(BundleError::Assets(_)))));
+        assert!(repo.list().is_empty());
+
+        let assets = Assets::from([("assets/file.png".to_string(), vec![1, 2, 3])]);
+        let entry = repo.import(Bundle::new(package, assets.clone())).unwrap();
+        let exported = repo.export(&entry.id).unwrap();
+        assert_eq!(exported.assets, assets);
+
+        // An edit that points at a new template is completed by importing
+        // a bundle that carries it
+        let retargeted = package_value_with_template("assets/other.png");
+        let edited = repo.replace(&entry.id, SkillDocument::Package(retargeted.clone())).unwrap();
+        let package: Package = serde_json::from_value(retargeted).unwrap();
+        let assets = Assets::from([("assets/other.png".to_string(), vec![4, 5])]);
+        let reimported = repo.import_into(&entry.id, Bundle::new(package, assets.clone())).unwrap();
+        assert_eq!(reimported.revision, edited.revision + 1);
+        assert_eq!(repo.export(&entry.id).unwrap().assets, assets);
+        assert_eq!(repo.list().len(), 1);
+
+        assert_eq!(repo.export("nope").unwrap_err(), SkillError::NotFound);
+        let orphan = Bundle::new(serde_json::from_value(package_value_with_template("assets/other.png")).unwrap(), assets);
+        assert_eq!(repo.import_into("nope", orphan).unwrap_err(), SkillError::NotFound);
+    }
+
+    fn package_value_with_template(path: &str) -> Value {
+        fixtures::package().selector("menu_file", json!({ "strategy": "template", "template": path })).json()
+    }
+}
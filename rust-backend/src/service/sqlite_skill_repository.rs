@@ -0,0 +1,339 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use crate::domain::bundle::Assets;
+use crate::domain::diff::content_hash;
+use crate::domain::library::{SkillEntry, SkillRevision};
+use crate::service::skill_repository::{SkillError, SkillRepository};
+use crate::service::sqlite_task_store::{conversion_error, json_column, migrate, time_from_sql, user_version};
+
+/// Schema migrations of the library database; append only, see
+/// [`crate::service::sqlite_task_store`].
+const MIGRATIONS: &[&str] = &[
+    // 1: skills
+    "CREATE TABLE skills (
+        id          TEXT PRIMARY KEY NOT NULL,
+        name        TEXT NOT NULL,
+        app         TEXT NOT NULL,
+        package     TEXT NOT NULL,
+        skill       TEXT NOT NULL,
+        source_task TEXT,
+        created_at  TEXT NOT NULL,
+        updated_at  TEXT NOT NULL
+    );
+    CREATE INDEX skills_app ON skills (app);",
+    // 2: immutable revisions; hashes of existing rows are filled in on open
+    "ALTER TABLE skills ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE skills ADD COLUMN hash TEXT NOT NULL DEFAULT '';
+    CREATE TABLE skill_revisions (
+        skill_id   TEXT NOT NULL,
+        number     INTEGER NOT NULL,
+        hash       TEXT NOT NULL,
+        package    TEXT NOT NULL,
+        skill      TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (skill_id, number)
+    );
+    INSERT INTO skill_revisions (skill_id, number, hash, package, skill, created_at)
+        SELECT id, 1, '', package, skill, updated_at FROM skills;",
+    // 3: bundled template images
+    "CREATE TABLE skill_assets (
+        skill_id TEXT NOT NULL,
+        path     TEXT NOT NULL,
+        data     BLOB NOT NULL,
+        PRIMARY KEY (skill_id, path)
+    );",
+];
+
+const SKILL_COLUMNS: &str = "id, package, skill, source_task, created_at, updated_at, revision, hash, name, app";
+const REVISION_COLUMNS: &str = "skill_id, number, hash, package, skill, created_at";
+
+/// Skill library backed by its own SQLite database file. Search runs over the
+/// loaded entries, which is fine for libraries of a few thousand skills.
+pub struct SqliteSkillRepository {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteSkillRepository {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        Self::with_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        Self::with_connection(conn)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, String> {
+        migrate(&mut conn, MIGRATIONS).map_err(|e| format!("Skill library migration failed: {}", e))?;
+        backfill_hashes(&mut conn).map_err(|e| format!("Skill library migration failed: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Number of migrations applied to the open database.
+    pub fn schema_version(&self) -> Result<usize, String> {
+        let conn = self.conn.lock().unwrap();
+        user_version(&conn).map_err(|e| e.to_string())
+    }
+}
+
+fn storage_error(e: rusqlite::Error) -> SkillError {
+    SkillError::Storage(e.to_string())
+}
+
+/// Hashes can't be computed in SQL, so rows migrated from before revisions
+/// get theirs here.
+fn backfill_hashes(conn: &mut Connection) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let rows: Vec<(String, u32, String)> = {
+        let mut stmt = tx.prepare("SELECT skill_id, number, package FROM skill_revisions WHERE hash = ''")?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?.collect::<Result<_, _>>()?
+    };
+    for (id, number, package) in rows {
+        let package = serde_json::from_str(&package).map_err(|e| conversion_error(2, e))?;
+        let hash = content_hash(&package);
+        tx.execute("UPDATE skill_revisions SET hash = ?1 WHERE skill_id = ?2 AND number = ?3", params![hash, id, number])?;
+        tx.execute("UPDATE skills SET hash = ?1 WHERE id = ?2 AND revision = ?3", params![hash, id, number])?;
+    }
+    tx.commit()
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<SkillEntry> {
+    Ok(SkillEntry {
+        id: row.get(0)?,
+        package: json_column(row, 1)?,
+        skill: json_column(row, 2)?,
+        source_task: row.get(3)?,
+        created_at: time_from_sql(row, 4)?,
+        updated_at: time_from_sql(row, 5)?,
+        revision: row.get(6)?,
+        hash: row.get(7)?,
+    })
+}
+
+fn revision_from_row(row: &Row<'_>) -> rusqlite::Result<SkillRevision> {
+    Ok(SkillRevision {
+        skill_id: row.get(0)?,
+        number: row.get(1)?,
+        hash: row.get(2)?,
+        package: json_column(row, 3)?,
+        skill: json_column(row, 4)?,
+        created_at: time_from_sql(row, 5)?,
+    })
+}
+
+fn select_entry(conn: &Connection, id: &str) -> rusqlite::Result<Option<SkillEntry>> {
+    let sql = format!("SELECT {} FROM skills WHERE id = ?1", SKILL_COLUMNS);
+    conn.query_row(&sql, params![id], entry_from_row).optional()
+}
+
+fn write_entry(conn: &Connection, entry: &SkillEntry) -> rusqlite::Result<()> {
+    let package = serde_json::to_string(&entry.package).unwrap_or_else(|_| "null".to_string());
+    let skill = serde_json::to_string(&entry.skill).unwrap_or_else(|_| "null".to_string());
+    let sql = format!("INSERT OR REPLACE INTO skills ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)", SKILL_COLUMNS);
+    conn.execute(
+        &sql,
+        params![
+            entry.id,
+            package,
+            skill,
+            entry.source_task,
+            entry.created_at.to_rfc3339(),
+            entry.updated_at.to_rfc3339(),
+            entry.revision,
+            entry.hash,
+            entry.package.package.name,
+            entry.package.app.name,
+        ],
+    )?;
+    // Existing revisions are immutable; rewriting the same number is a no-op.
+    let sql = format!("INSERT OR IGNORE INTO skill_revisions ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)", REVISION_COLUMNS);
+    conn.execute(&sql, params![entry.id, entry.revision, entry.hash, package, skill, entry.updated_at.to_rfc3339()])?;
+    Ok(())
+}
+
+impl SkillRepository for SqliteSkillRepository {
+    fn insert(&self, entry: SkillEntry) -> Result<SkillEntry, SkillError> {
+        let conn = self.conn.lock().unwrap();
+        write_entry(&conn, &entry).map_err(storage_error)?;
+        Ok(entry)
+    }
+
+    fn get(&self, id: &str) -> Option<SkillEntry> {
+        let conn = self.conn.lock().unwrap();
+        select_entry(&conn, id).unwrap_or_else(|e| {
+            tracing::error!("failed to load skill {}: {}", id, e);
+            None
+        })
+    }
+
+    fn list(&self) -> Vec<SkillEntry> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM skills ORDER BY created_at", SKILL_COLUMNS);
+        let result = conn
+            .prepare(&sql)
+            .and_then(|mut stmt| stmt.query_map([], entry_from_row)?.collect::<Result<Vec<_>, _>>());
+        result.unwrap_or_else(|e| {
+            tracing::error!("failed to list skills: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        apply: &mut dyn FnMut(&mut SkillEntry) -> Result<(), SkillError>,
+    ) -> Result<SkillEntry, SkillError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let mut entry = select_entry(&tx, id).map_err(storage_error)?.ok_or(SkillError::NotFound)?;
+        apply(&mut entry)?;
+        write_entry(&tx, &entry).map_err(storage_error)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(entry)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), SkillError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        if tx.execute("DELETE FROM skills WHERE id = ?1", params![id]).map_err(storage_error)? == 0 {
+            return Err(SkillError::NotFound);
+        }
+        tx.execute("DELETE FROM skill_revisions WHERE skill_id = ?1", params![id]).map_err(storage_error)?;
+        tx.execute("DELETE FROM skill_assets WHERE skill_id = ?1", params![id]).map_err(storage_error)?;
+        tx.commit().map_err(storage_error)
+    }
+
+    fn revisions(&self, id: &str) -> Option<Vec<SkillRevision>> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM skill_revisions WHERE skill_id = ?1 ORDER BY number", REVISION_COLUMNS);
+        let result = conn
+            .prepare(&sql)
+            .and_then(|mut stmt| stmt.query_map(params![id], revision_from_row)?.collect::<Result<Vec<_>, _>>());
+        match result {
+            Ok(revisions) if revisions.is_empty() => None,
+            Ok(revisions) => Some(revisions),
+            Err(e) => {
+                tracing::error!("failed to load revisions of skill {}: {}", id, e);
+                None
+            }
+        }
+    }
+
+    fn revision(&self, id: &str, number: u32) -> Option<SkillRevision> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM skill_revisions WHERE skill_id = ?1 AND number = ?2", REVISION_COLUMNS);
+        conn.query_row(&sql, params![id, number], revision_from_row).optional().unwrap_or_else(|e| {
+            tracing::error!("failed to load revision {} of skill {}: {}", number, id, e);
+            None
+        })
+    }
+
+    fn assets(&self, id: &str) -> Option<Assets> {
+        let conn = self.conn.lock().unwrap();
+        let result = select_entry(&conn, id).and_then(|entry| {
+            if entry.is_none() {
+                return Ok(None);
+            }
+            let mut stmt = conn.prepare("SELECT path, data FROM skill_assets WHERE skill_id = ?1")?;
+            let rows = stmt.query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<Assets, _>>().map(Some)
+        });
+        result.unwrap_or_else(|e| {
+            tracing::error!("failed to load assets of skill {}: {}", id, e);
+            None
+        })
+    }
+
+    fn set_assets(&self, id: &str, assets: Assets) -> Result<(), SkillError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        select_entry(&tx, id).map_err(storage_error)?.ok_or(SkillError::NotFound)?;
+        tx.execute("DELETE FROM skill_assets WHERE skill_id = ?1", params![id]).map_err(storage_error)?;
+        for (path, data) in &assets {
+            tx.execute("INSERT INTO skill_assets (skill_id, path, data) VALUES (?1, ?2, ?3)", params![id, path, data])
+                .map_err(storage_error)?;
+        }
+        tx.commit().map_err(storage_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::library::SkillQuery;
+    use crate::domain::fixtures;
+    use crate::service::skill_repository::SkillDocument;
+    use serde_json::json;
+
+    fn package() -> serde_json::Value {
+        fixtures::package()
+            .selectors(json!({}))
+            .steps(json!([{ "id": "s1", "op": "hotkey", "keys": ["ctrl", "shift", "s"] }]))
+            .json()
+    }
+
+    #[test]
+    fn test_library_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("skillflow-library-{}.db", uuid::Uuid::new_v4()));
+        let repo = SqliteSkillRepository::open(&path).unwrap();
+        let entry = repo.publish("task-1", package()).unwrap();
+        let mut edited = package();
+        edited["steps"][0]["keys"] = json!(["ctrl", "s"]);
+        repo.replace(&entry.id, SkillDocument::Package(edited)).unwrap();
+        let assets = Assets::from([("assets/unused.png".to_string(), vec![0u8, 1])]);
+        repo.set_assets(&entry.id, assets.clone()).unwrap();
+        drop(repo);
+
+        let reopened = SqliteSkillRepository::open(&path).unwrap();
+        assert_eq!(reopened.schema_version().unwrap(), MIGRATIONS.len());
+        let loaded = reopened.get(&entry.id).unwrap();
+        assert_eq!(loaded.source_task.as_deref(), Some("task-1"));
+        assert_eq!(loaded.skill.steps[0].parameters["keys"], json!(["ctrl", "s"]));
+        assert_eq!(loaded.created_at, entry.created_at);
+        assert_eq!(loaded.revision, 2);
+        let first = reopened.revision(&entry.id, 1).unwrap();
+        assert_eq!((first.hash, first.skill.steps[0].parameters["keys"].clone()), (entry.hash, json!(["ctrl", "shift", "s"])));
+        assert_eq!(reopened.diff(&entry.id, 1, 2).unwrap().summary, ["Changed step s1: keys"]);
+        assert_eq!(reopened.assets(&entry.id), Some(assets));
+
+        let query = SkillQuery { q: Some("png".to_string()), ..Default::default() };
+        assert_eq!(reopened.search(&query).len(), 1);
+        reopened.delete(&entry.id).unwrap();
+        assert_eq!(reopened.delete(&entry.id), Err(SkillError::NotFound));
+        assert!(reopened.revisions(&entry.id).is_none());
+        assert!(reopened.assets(&entry.id).is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_migration_backfills_revision_hashes() {
+        let path = std::env::temp_dir().join(format!("skillflow-library-{}.db", uuid::Uuid::new_v4()));
+        let entry = SqliteSkillRepository::open_in_memory().unwrap().publish("task-1", package()).unwrap();
+        {
+            let mut conn = Connection::open(&path).unwrap();
+            migrate(&mut conn, &MIGRATIONS[..1]).unwrap();
+            conn.execute(
+                "INSERT INTO skills (id, name, app, package, skill, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                params![
+                    entry.id,
+                    entry.package.package.name,
+                    entry.package.app.name,
+                    serde_json::to_string(&entry.package).unwrap(),
+                    serde_json::to_string(&entry.skill).unwrap(),
+                    entry.created_at.to_rfc3339(),
+                ],
+            )
+            .unwrap();
+        }
+
+        let repo = SqliteSkillRepository::open(&path).unwrap();
+        let loaded = repo.get(&entry.id).unwrap();
+        assert_eq!((loaded.revision, &loaded.hash), (1, &entry.hash));
+        assert_eq!(repo.revisions(&entry.id).unwrap()[0].hash, entry.hash);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,281 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use uuid::Uuid;
+
+use crate::domain::task::{Task, TaskStatus};
+use crate::service::task_service::{TaskError, TaskStore};
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many
+/// have run, so entries must only ever be appended.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial task table
+    "CREATE TABLE tasks (
+        entry_id        TEXT PRIMARY KEY NOT NULL,
+        dir_location    TEXT NOT NULL,
+        status          TEXT NOT NULL,
+        transcript_text TEXT,
+        video_analysis  TEXT,
+        steps_package   TEXT,
+        error           TEXT,
+        created_at      TEXT NOT NULL,
+        updated_at      TEXT NOT NULL
+    );
+    CREATE INDEX tasks_created_at ON tasks (created_at);",
+    // 2: status history
+    "ALTER TABLE tasks ADD COLUMN history TEXT NOT NULL DEFAULT '[]';",
+    // 3: transcript segments and step alignment
+    "ALTER TABLE tasks ADD COLUMN transcript_segments TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE tasks ADD COLUMN step_alignment TEXT;",
+    // 4: package validation errors
+    "ALTER TABLE tasks ADD COLUMN validation_errors TEXT NOT NULL DEFAULT '[]';",
+    // 5: formatter attempts
+    "ALTER TABLE tasks ADD COLUMN format_attempts TEXT NOT NULL DEFAULT '[]';",
+    // 6: provider calls and their retries
+    "ALTER TABLE tasks ADD COLUMN provider_calls TEXT NOT NULL DEFAULT '[]';",
+    // 7: structured pipeline failure
+    "ALTER TABLE tasks ADD COLUMN failure TEXT;",
+];
+
+const TASK_COLUMNS: &str = "entry_id, dir_location, status, transcript_text, video_analysis, \
+    steps_package, error, created_at, updated_at, history, transcript_segments, step_alignment, \
+    validation_errors, format_attempts, provider_calls, failure";
+
+/// Task store backed by a single SQLite database file.
+pub struct SqliteTaskStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteTaskStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        Self::with_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        Self::with_connection(conn)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, String> {
+        migrate(&mut conn, MIGRATIONS).map_err(|e| format!("Task store migration failed: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Number of migrations applied to the open database.
+    pub fn schema_version(&self) -> Result<usize, String> {
+        let conn = self.conn.lock().unwrap();
+        user_version(&conn).map_err(|e| e.to_string())
+    }
+}
+
+pub(crate) fn user_version(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+        .map(|v| v as usize)
+}
+
+/// Runs the `migrations` the database has not seen yet, each in its own
+/// transaction.
+pub(crate) fn migrate(conn: &mut Connection, migrations: &[&str]) -> rusqlite::Result<()> {
+    let current = user_version(conn)?;
+    for (index, sql) in migrations.iter().enumerate().skip(current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", (index + 1) as i64)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn storage_error(e: rusqlite::Error) -> TaskError {
+    TaskError::Storage(e.to_string())
+}
+
+fn json_to_sql(value: &Option<serde_json::Value>) -> Option<String> {
+    value.as_ref().map(|v| v.to_string())
+}
+
+pub(crate) fn conversion_error(index: usize, e: impl std::error::Error + Send + Sync + 'static) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+}
+
+fn json_from_sql(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<serde_json::Value>> {
+    match row.get::<_, Option<String>>(index)? {
+        Some(text) => serde_json::from_str(&text).map(Some).map_err(|e| conversion_error(index, e)),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn json_column<T: serde::de::DeserializeOwned>(row: &Row<'_>, index: usize) -> rusqlite::Result<T> {
+    let text: Option<String> = row.get(index)?;
+    serde_json::from_str(text.as_deref().unwrap_or("null")).map_err(|e| conversion_error(index, e))
+}
+
+pub(crate) fn time_from_sql(row: &Row<'_>, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let text: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| conversion_error(index, e))
+}
+
+fn task_from_row(row: &Row<'_>) -> rusqlite::Result<Task> {
+    let status: String = row.get(2)?;
+    let status: TaskStatus = serde_json::from_value(serde_json::Value::String(status))
+        .map_err(|e| conversion_error(2, e))?;
+    Ok(Task {
+        entry_id: row.get(0)?,
+        dir_location: row.get(1)?,
+        status,
+        transcript_text: row.get(3)?,
+        video_analysis: json_from_sql(row, 4)?,
+        steps_package: json_from_sql(row, 5)?,
+        error: row.get(6)?,
+        created_at: time_from_sql(row, 7)?,
+        updated_at: time_from_sql(row, 8)?,
+        history: json_column(row, 9)?,
+        transcript_segments: json_column(row, 10)?,
+        step_alignment: json_column(row, 11)?,
+        validation_errors: json_column(row, 12)?,
+        format_attempts: json_column(row, 13)?,
+        provider_calls: json_column(row, 14)?,
+        failure: json_column(row, 15)?,
+    })
+}
+
+fn select_task(conn: &Connection, entry_id: &str) -> rusqlite::Result<Option<Task>> {
+    let sql = format!("SELECT {} FROM tasks WHERE entry_id = ?1", TASK_COLUMNS);
+    conn.query_row(&sql, params![entry_id], task_from_row).optional()
+}
+
+fn write_task(conn: &Connection, task: &Task) -> rusqlite::Result<()> {
+    let sql = format!(
+        "INSERT OR REPLACE INTO tasks ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        TASK_COLUMNS
+    );
+    conn.execute(
+        &sql,
+        params![
+            task.entry_id,
+            task.dir_location,
+            task.status.as_str(),
+            task.transcript_text,
+            json_to_sql(&task.video_analysis),
+            json_to_sql(&task.steps_package),
+            task.error,
+            task.created_at.to_rfc3339(),
+            task.updated_at.to_rfc3339(),
+            serde_json::to_string(&task.history).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&task.transcript_segments).unwrap_or_else(|_| "[]".to_string()),
+            task.step_alignment.as_ref().and_then(|a| serde_json::to_string(a).ok()),
+            serde_json::to_string(&task.validation_errors).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&task.format_attempts).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&task.provider_calls).unwrap_or_else(|_| "[]".to_string()),
+            task.failure.as_ref().and_then(|f| serde_json::to_string(f).ok()),
+        ],
+    )?;
+    Ok(())
+}
+
+impl TaskStore for SqliteTaskStore {
+    fn create_task(&self, dir_location: String) -> Result<Task, TaskError> {
+        let task = Task::new(Uuid::new_v4().to_string(), dir_location);
+        let conn = self.conn.lock().unwrap();
+        write_task(&conn, &task).map_err(storage_error)?;
+        Ok(task)
+    }
+
+    fn get_task(&self, entry_id: &str) -> Option<Task> {
+        let conn = self.conn.lock().unwrap();
+        select_task(&conn, entry_id).unwrap_or_else(|e| {
+            tracing::error!("failed to load task {}: {}", entry_id, e);
+            None
+        })
+    }
+
+    fn list_tasks(&self) -> Vec<Task> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM tasks ORDER BY created_at", TASK_COLUMNS);
+        let result = conn
+            .prepare(&sql)
+            .and_then(|mut stmt| stmt.query_map([], task_from_row)?.collect::<Result<Vec<_>, _>>());
+        result.unwrap_or_else(|e| {
+            tracing::error!("failed to list tasks: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn update_task(
+        &self,
+        entry_id: &str,
+        apply: &mut dyn FnMut(&mut Task) -> Result<(), TaskError>,
+    ) -> Result<Task, TaskError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let mut task = select_task(&tx, entry_id)
+            .map_err(storage_error)?
+            .ok_or(TaskError::NotFound)?;
+        apply(&mut task)?;
+        write_task(&tx, &task).map_err(storage_error)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::task::ProviderCall;
+    use serde_json::json;
+
+    fn temp_db() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("skillflow-tasks-{}.db", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let path = temp_db();
+        let store = SqliteTaskStore::open(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+        drop(store);
+
+        let reopened = SqliteTaskStore::open(&path).unwrap();
+        assert_eq!(reopened.schema_version().unwrap(), MIGRATIONS.len());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_artifacts_survive_reopen() {
+        let path = temp_db();
+        let store = SqliteTaskStore::open(&path).unwrap();
+        let task = store.create_task("s3://persist".to_string()).unwrap();
+        store.set_status(&task.entry_id, TaskStatus::Processing, "audio parse started").unwrap();
+        store.update_audio_result(&task.entry_id, "hello".to_string(), Vec::new()).unwrap();
+        store.update_video_result(&task.entry_id, json!({"name": "skill"}), json!({"steps": []})).unwrap();
+        let call = ProviderCall { stage: "transcribe".to_string(), attempts: 2, error: None, at: Utc::now() };
+        store.record_provider_call(&task.entry_id, call.clone()).unwrap();
+        drop(store);
+
+        let reopened = SqliteTaskStore::open(&path).unwrap();
+        let loaded = reopened.get_task(&task.entry_id).unwrap();
+        assert_eq!(loaded.dir_location, "s3://persist");
+        assert_eq!(loaded.status, TaskStatus::Finished);
+        assert_eq!(loaded.transcript_text.as_deref(), Some("hello"));
+        assert_eq!(loaded.video_analysis, Some(json!({"name": "skill"})));
+        assert_eq!(loaded.steps_package, Some(json!({"steps": []})));
+        assert_eq!(loaded.created_at, task.created_at);
+        assert_eq!(loaded.history.len(), 3);
+        assert_eq!(loaded.history[2].reason, "video analysis ready, all tracks ready");
+        assert_eq!(loaded.provider_calls[0].attempts, call.attempts);
+        assert_eq!(reopened.list_tasks().len(), 1);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_update_unknown_task() {
+        let store = SqliteTaskStore::open_in_memory().unwrap();
+        assert!(store.get_task("missing").is_none());
+        assert!(store.mark_as_failed("missing", "boom".to_string()).is_err());
+    }
+}
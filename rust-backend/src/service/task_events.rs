@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::domain::task::TaskStatus;
+
+/// How many events are kept for `Last-Event-ID` resume, across all tasks.
+pub const EVENT_LOG_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskEventKind {
+    Status { from: TaskStatus, to: TaskStatus, reason: String },
+    /// `track` is one of `audio`, `video`, `steps`, `alignment`, matching the artifact endpoint.
+    ArtifactReady { track: String },
+    Failed { error: String },
+}
+
+impl TaskEventKind {
+    /// SSE `event:` name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskEventKind::Status { .. } => "status",
+            TaskEventKind::ArtifactReady { .. } => "artifact",
+            TaskEventKind::Failed { .. } => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TaskEvent {
+    /// Monotonic across all tasks; used as the SSE event id.
+    pub id: u64,
+    #[serde(rename = "entryId")]
+    pub entry_id: String,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: TaskEventKind,
+}
+
+impl TaskEvent {
+    /// Whether this is the last event the task will ever produce.
+    pub fn is_terminal(&self) -> bool {
+        matches!(&self.kind, TaskEventKind::Status { to, .. } if to.is_terminal())
+    }
+}
+
+struct EventLog {
+    events: VecDeque<TaskEvent>,
+    next_id: u64,
+}
+
+/// Broadcast hub for task events with a bounded replay log.
+#[derive(Clone)]
+pub struct TaskEvents {
+    log: Arc<Mutex<EventLog>>,
+    sender: broadcast::Sender<TaskEvent>,
+    capacity: usize,
+}
+
+impl TaskEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self {
+            log: Arc::new(Mutex::new(EventLog { events: VecDeque::new(), next_id: 1 })),
+            sender,
+            capacity,
+        }
+    }
+
+    pub fn publish(&self, entry_id: &str, kind: TaskEventKind) -> TaskEvent {
+        // Sending under the log lock keeps the log and the channel in the same order
+        let mut log = self.log.lock().unwrap();
+        let event = TaskEvent { id: log.next_id, entry_id: entry_id.to_string(), at: Utc::now(), kind };
+        log.next_id += 1;
+        log.events.push_back(event.clone());
+        while log.events.len() > self.capacity {
+            log.events.pop_front();
+        }
+        let _ = self.sender.send(event.clone());
+        event
+    }
+
+    /// Retained events for `entry_id` with an id greater than `after`.
+    pub fn since(&self, entry_id: &str, after: u64) -> Vec<TaskEvent> {
+        let log = self.log.lock().unwrap();
+        log.events.iter().filter(|e| e.entry_id == entry_id && e.id > after).cloned().collect()
+    }
+
+    /// Subscribes to `entry_id`, first replaying retained events after `last_event_id`.
+    pub fn subscribe(&self, entry_id: &str, last_event_id: Option<u64>) -> TaskSubscription {
+        let log = self.log.lock().unwrap();
+        let after = last_event_id.unwrap_or(0);
+        let backlog = log.events.iter().filter(|e| e.entry_id == entry_id && e.id > after).cloned().collect();
+        TaskSubscription {
+            events: self.clone(),
+            entry_id: entry_id.to_string(),
+            backlog,
+            receiver: self.sender.subscribe(),
+            last_id: after,
+        }
+    }
+}
+
+/// Event stream for a single task; never yields the same event twice.
+pub struct TaskSubscription {
+    events: TaskEvents,
+    entry_id: String,
+    backlog: VecDeque<TaskEvent>,
+    receiver: broadcast::Receiver<TaskEvent>,
+    last_id: u64,
+}
+
+impl TaskSubscription {
+    /// Next already-available event, without waiting. Covers both the replay
+    /// backlog and events published since subscribing.
+    pub fn next_buffered(&mut self) -> Option<TaskEvent> {
+        if self.backlog.is_empty() {
+            self.drain_received();
+        }
+        let event = self.backlog.pop_front()?;
+        self.last_id = event.id;
+        Some(event)
+    }
+
+    /// Moves events already sitting in the channel to the backlog.
+    fn drain_received(&mut self) {
+        loop {
+            let queued_up_to = self.backlog.back().map_or(self.last_id, |e| e.id);
+            match self.receiver.try_recv() {
+                Ok(event) if event.entry_id == self.entry_id && event.id > queued_up_to => self.backlog.push_back(event),
+                Ok(_) => continue,
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    self.backlog = self.events.since(&self.entry_id, self.last_id).into();
+                }
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Waits for the next event; `None` once the hub is gone.
+    pub async fn next(&mut self) -> Option<TaskEvent> {
+        loop {
+            if let Some(event) = self.next_buffered() {
+                return Some(event);
+            }
+            match self.receiver.recv().await {
+                Ok(event) if event.entry_id == self.entry_id && event.id > self.last_id => {
+                    self.last_id = event.id;
+                    return Some(event);
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    // Catch up from the log rather than dropping events
+                    self.backlog = self.events.since(&self.entry_id, self.last_id).into();
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(track: &str) -> TaskEventKind {
+        TaskEventKind::ArtifactReady { track: track.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_last_event_id() {
+        let events = TaskEvents::new(16);
+        let first = events.publish("a", artifact("audio"));
+        events.publish("b", artifact("audio"));
+        let third = events.publish("a", artifact("video"));
+
+        let mut sub = events.subscribe("a", Some(first.id));
+        assert_eq!(sub.next_buffered(), Some(third));
+        assert_eq!(sub.next_buffered(), None);
+
+        let live = events.publish("a", artifact("steps"));
+        assert_eq!(sub.next().await, Some(live));
+    }
+
+    #[test]
+    fn test_terminal_event_published_after_subscribing_is_buffered() {
+        let events = TaskEvents::new(16);
+        events.publish("a", artifact("audio"));
+        let mut sub = events.subscribe("a", None);
+        // The task turns terminal before the handler reads it
+        let finished = events.publish(
+            "a",
+            TaskEventKind::Status { from: TaskStatus::Processing, to: TaskStatus::Finished, reason: String::new() },
+        );
+
+        assert_eq!(sub.next_buffered().map(|e| e.kind), Some(artifact("audio")));
+        assert_eq!(sub.next_buffered(), Some(finished));
+        assert_eq!(sub.next_buffered(), None);
+    }
+
+    #[tokio::test]
+    async fn test_log_is_bounded() {
+        let events = TaskEvents::new(2);
+        for track in ["audio", "video", "steps"] {
+            events.publish("a", artifact(track));
+        }
+        let replayed: Vec<_> = events.since("a", 0).into_iter().map(|e| e.kind).collect();
+        assert_eq!(replayed, vec![artifact("video"), artifact("steps")]);
+    }
+}
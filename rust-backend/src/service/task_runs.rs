@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio_util::sync::CancellationToken;
+
+struct Run {
+    token: CancellationToken,
+    /// Tracks of the task currently running; audio and video share the token.
+    active: usize,
+}
+
+/// Cancellation tokens of the pipeline runs in flight, by task.
+#[derive(Clone, Default)]
+pub struct TaskRuns {
+    runs: Arc<Mutex<HashMap<String, Run>>>,
+}
+
+impl TaskRuns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a run of `entry_id`; it stays registered until the guard is dropped.
+    pub fn start(&self, entry_id: &str) -> RunGuard {
+        let mut runs = self.runs.lock().unwrap();
+        let run = runs
+            .entry(entry_id.to_string())
+            .or_insert_with(|| Run { token: CancellationToken::new(), active: 0 });
+        run.active += 1;
+        RunGuard { runs: self.clone(), entry_id: entry_id.to_string(), token: run.token.clone() }
+    }
+
+    /// Signals every run of `entry_id`. Returns whether any was in flight.
+    pub fn cancel(&self, entry_id: &str) -> bool {
+        match self.runs.lock().unwrap().get(entry_id) {
+            Some(run) => {
+                run.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self, entry_id: &str) -> bool {
+        self.runs.lock().unwrap().contains_key(entry_id)
+    }
+
+    fn finish(&self, entry_id: &str) {
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(run) = runs.get_mut(entry_id) {
+            run.active -= 1;
+            if run.active == 0 {
+                runs.remove(entry_id);
+            }
+        }
+    }
+}
+
+/// One registered run; moves into the spawned pipeline future.
+pub struct RunGuard {
+    runs: TaskRuns,
+    entry_id: String,
+    token: CancellationToken,
+}
+
+impl RunGuard {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        self.runs.finish(&self.entry_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_share_a_token_until_both_finish() {
+        let runs = TaskRuns::new();
+        let audio = runs.start("t1");
+        let video = runs.start("t1");
+        let other = runs.start("t2");
+
+        assert!(runs.cancel("t1"));
+        assert!(audio.token().is_cancelled() && video.token().is_cancelled());
+        assert!(!other.token().is_cancelled());
+
+        drop(audio);
+        assert!(runs.is_running("t1"));
+        drop(video);
+        assert!(!runs.is_running("t1"));
+        assert!(!runs.cancel("t1"));
+
+        // A new run after cancellation starts with a fresh token
+        assert!(!runs.start("t1").token().is_cancelled());
+    }
+}
@@ -1,9 +1,146 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
-use chrono::Utc;
-use crate::domain::task::{Task, TaskStatus};
+use std::fmt;
+use crate::domain::pipeline_error::PipelineError;
+use crate::domain::schema::SchemaViolation;
+use crate::domain::task::{FormatAttempt, ProviderCall, Task, TaskStatus, TransitionError};
+use crate::domain::transcript::{StepAlignment, TranscriptSegment};
+use crate::service::task_events::{EVENT_LOG_CAPACITY, TaskEventKind, TaskEvents, TaskSubscription};
+use crate::service::task_runs::{RunGuard, TaskRuns};
 
+/// Why a task store operation was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskError {
+    NotFound,
+    IllegalTransition(TransitionError),
+    Storage(String),
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::NotFound => write!(f, "Task not found"),
+            TaskError::IllegalTransition(e) => write!(f, "{}", e),
+            TaskError::Storage(msg) => write!(f, "Task storage error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+impl From<TransitionError> for TaskError {
+    fn from(e: TransitionError) -> Self {
+        TaskError::IllegalTransition(e)
+    }
+}
+
+/// Persistence for pipeline tasks.
+///
+/// Implementations provide the primitives; the pipeline transitions are
+/// default methods so every backend applies the same rules.
+pub trait TaskStore: Send + Sync {
+    fn create_task(&self, dir_location: String) -> Result<Task, TaskError>;
+
+    fn get_task(&self, entry_id: &str) -> Option<Task>;
+
+    fn list_tasks(&self) -> Vec<Task>;
+
+    /// Applies `apply` to the stored task and persists the result atomically.
+    /// Nothing is written if `apply` fails.
+    fn update_task(
+        &self,
+        entry_id: &str,
+        apply: &mut dyn FnMut(&mut Task) -> Result<(), TaskError>,
+    ) -> Result<Task, TaskError>;
+
+    fn set_status(&self, entry_id: &str, status: TaskStatus, reason: &str) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| Ok(task.transition(status.clone(), reason)?))
+    }
+
+    fn update_audio_result(
+        &self,
+        entry_id: &str,
+        transcript: String,
+        segments: Vec<TranscriptSegment>,
+    ) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.transcript_text = Some(transcript.clone());
+            task.transcript_segments = segments.clone();
+            Ok(task.artifacts_stored(TaskStatus::AudioDone, "audio transcript ready")?)
+        })
+    }
+
+    /// Stores both outputs of the video track: the Skill view as `video` and
+    /// the validated package as `steps`.
+    fn update_video_result(
+        &self,
+        entry_id: &str,
+        analysis: serde_json::Value,
+        package: serde_json::Value,
+    ) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.video_analysis = Some(analysis.clone());
+            task.steps_package = Some(package.clone());
+            Ok(task.artifacts_stored(TaskStatus::VideoDone, "video analysis ready")?)
+        })
+    }
+
+    /// Stores the step/transcript join; does not change the status.
+    fn update_alignment(&self, entry_id: &str, alignment: Vec<StepAlignment>) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.step_alignment = Some(alignment.clone());
+            task.updated_at = chrono::Utc::now();
+            Ok(())
+        })
+    }
+
+    /// Keeps the schema/semantic errors of a rejected package for inspection.
+    fn record_validation_errors(&self, entry_id: &str, errors: Vec<SchemaViolation>) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.validation_errors = errors.clone();
+            task.updated_at = chrono::Utc::now();
+            Ok(())
+        })
+    }
+
+    fn record_format_attempt(&self, entry_id: &str, attempt: FormatAttempt) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.format_attempts.push(attempt.clone());
+            task.updated_at = chrono::Utc::now();
+            Ok(())
+        })
+    }
+
+    fn record_provider_call(&self, entry_id: &str, call: ProviderCall) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.provider_calls.push(call.clone());
+            task.updated_at = chrono::Utc::now();
+            Ok(())
+        })
+    }
+
+    fn mark_as_failed(&self, entry_id: &str, error: String) -> Result<Task, TaskError> {
+        self.update_task(entry_id, &mut |task| {
+            task.transition(TaskStatus::Failed, &error)?;
+            task.error = Some(error.clone());
+            Ok(())
+        })
+    }
+
+    /// Fails the task with a pipeline error, keeping its code and stage.
+    fn mark_pipeline_failed(&self, entry_id: &str, error: &PipelineError) -> Result<Task, TaskError> {
+        let failure = error.failure();
+        self.update_task(entry_id, &mut |task| {
+            task.transition(TaskStatus::Failed, &failure.message)?;
+            task.error = Some(failure.message.clone());
+            task.failure = Some(failure.clone());
+            Ok(())
+        })
+    }
+}
+
+/// In-memory store; everything is lost on restart.
 #[derive(Debug, Clone)]
 pub struct MemTaskService {
     tasks: Arc<Mutex<HashMap<String, Task>>>,
@@ -15,89 +152,146 @@ impl MemTaskService {
             tasks: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+}
+
+impl Default for MemTaskService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    pub fn create_task(&self, dir_location: String) -> Task {
+impl TaskStore for MemTaskService {
+    fn create_task(&self, dir_location: String) -> Result<Task, TaskError> {
         let entry_id = Uuid::new_v4().to_string();
         let task = Task::new(entry_id.clone(), dir_location);
         
         let mut tasks = self.tasks.lock().unwrap();
         tasks.insert(entry_id.clone(), task.clone());
         
-        task
+        Ok(task)
     }
 
-    pub fn get_task(&self, entry_id: &str) -> Option<Task> {
+    fn get_task(&self, entry_id: &str) -> Option<Task> {
         let tasks = self.tasks.lock().unwrap();
         tasks.get(entry_id).cloned()
     }
 
-    pub fn set_status(&self, entry_id: &str, status: TaskStatus) -> Result<Task, String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        if let Some(task) = tasks.get_mut(entry_id) {
-            task.status = status;
-            task.updated_at = Utc::now();
-            Ok(task.clone())
-        } else {
-            Err("Task not found".to_string())
-        }
+    fn list_tasks(&self) -> Vec<Task> {
+        let tasks = self.tasks.lock().unwrap();
+        tasks.values().cloned().collect()
     }
 
-    pub fn update_audio_result(&self, entry_id: &str, transcript: String) -> Result<Task, String> {
+    fn update_task(
+        &self,
+        entry_id: &str,
+        apply: &mut dyn FnMut(&mut Task) -> Result<(), TaskError>,
+    ) -> Result<Task, TaskError> {
         let mut tasks = self.tasks.lock().unwrap();
-        if let Some(task) = tasks.get_mut(entry_id) {
-            task.transcript_text = Some(transcript);
-            task.status = TaskStatus::AudioDone;
-            task.updated_at = Utc::now();
-            Ok(task.clone())
-        } else {
-            Err("Task not found".to_string())
-        }
+        let task = tasks.get_mut(entry_id).ok_or(TaskError::NotFound)?;
+        let mut updated = task.clone();
+        apply(&mut updated)?;
+        *task = updated.clone();
+        Ok(updated)
     }
+}
 
-    pub fn update_video_result(&self, entry_id: &str, analysis: serde_json::Value) -> Result<Task, String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        if let Some(task) = tasks.get_mut(entry_id) {
-            // Business Rule: Should ideally check if AudioDone or Processing?
-            // The diagram implies Video follows Audio, but they could be independent in some architectures.
-            // For this specific pipeline, Client submits transcript to video parse, implying dependency.
-            // We'll allow transition from any non-terminal state for flexibility, but update status to VideoDone.
-            
-            task.video_analysis = Some(analysis);
-            task.status = TaskStatus::VideoDone;
-            task.updated_at = Utc::now();
-            Ok(task.clone())
-        } else {
-            Err("Task not found".to_string())
+/// A [`TaskStore`] that publishes a [`TaskEvents`] entry for every status
+/// change, newly stored artifact and failure, and keeps track of the
+/// pipeline runs in flight so they can be cancelled.
+#[derive(Clone)]
+pub struct TaskService {
+    store: Arc<dyn TaskStore>,
+    events: TaskEvents,
+    runs: TaskRuns,
+    /// Held across an update and its publishing, so events come out in the
+    /// order the store applied the changes.
+    sequence: Arc<Mutex<()>>,
+}
+
+impl TaskService {
+    pub fn new(store: Arc<dyn TaskStore>) -> Self {
+        Self {
+            store,
+            events: TaskEvents::new(EVENT_LOG_CAPACITY),
+            runs: TaskRuns::new(),
+            sequence: Arc::new(Mutex::new(())),
         }
     }
 
-    pub fn update_steps_result(&self, entry_id: &str, steps: serde_json::Value) -> Result<Task, String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        if let Some(task) = tasks.get_mut(entry_id) {
-            task.steps_package = Some(steps);
-            task.status = TaskStatus::Finished;
-            task.updated_at = Utc::now();
-            Ok(task.clone())
-        } else {
-            Err("Task not found".to_string())
-        }
+    /// Registers a pipeline run; hand its token to the pipeline.
+    pub fn start_run(&self, entry_id: &str) -> RunGuard {
+        self.runs.start(entry_id)
     }
 
-    pub fn mark_as_failed(&self, entry_id: &str, error: String) -> Result<Task, String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        if let Some(task) = tasks.get_mut(entry_id) {
-            task.error = Some(error);
-            task.status = TaskStatus::Failed;
-            task.updated_at = Utc::now();
-            Ok(task.clone())
-        } else {
-            Err("Task not found".to_string())
+    /// Moves the task to `Cancelled`, then signals its runs. The status goes
+    /// first: once it is terminal, results of runs that finish anyway are
+    /// rejected by the store.
+    pub fn cancel(&self, entry_id: &str, reason: &str) -> Result<Task, TaskError> {
+        let task = self.set_status(entry_id, TaskStatus::Cancelled, reason)?;
+        self.runs.cancel(entry_id);
+        Ok(task)
+    }
+
+    pub fn subscribe(&self, entry_id: &str, last_event_id: Option<u64>) -> TaskSubscription {
+        self.events.subscribe(entry_id, last_event_id)
+    }
+
+    fn publish_changes(&self, before: &Task, after: &Task) {
+        let artifacts = [
+            ("audio", before.transcript_text != after.transcript_text && after.transcript_text.is_some()),
+            ("video", before.video_analysis != after.video_analysis && after.video_analysis.is_some()),
+            ("steps", before.steps_package != after.steps_package && after.steps_package.is_some()),
+            ("alignment", before.step_alignment != after.step_alignment && after.step_alignment.is_some()),
+        ];
+        for (track, ready) in artifacts {
+            if ready {
+                self.events.publish(&after.entry_id, TaskEventKind::ArtifactReady { track: track.to_string() });
+            }
         }
+        if after.error != before.error
+            && let Some(error) = &after.error
+        {
+            self.events.publish(&after.entry_id, TaskEventKind::Failed { error: error.clone() });
+        }
+        // Status last, so a terminal status is always the final event of a task
+        for change in after.history.iter().skip(before.history.len()) {
+            self.events.publish(&after.entry_id, TaskEventKind::Status {
+                from: change.from.clone(),
+                to: change.to.clone(),
+                reason: change.reason.clone(),
+            });
+        }
+    }
+}
+
+impl TaskStore for TaskService {
+    fn create_task(&self, dir_location: String) -> Result<Task, TaskError> {
+        self.store.create_task(dir_location)
     }
 
-    pub fn list_tasks(&self) -> Vec<Task> {
-        let tasks = self.tasks.lock().unwrap();
-        tasks.values().cloned().collect()
+    fn get_task(&self, entry_id: &str) -> Option<Task> {
+        self.store.get_task(entry_id)
+    }
+
+    fn list_tasks(&self) -> Vec<Task> {
+        self.store.list_tasks()
+    }
+
+    fn update_task(
+        &self,
+        entry_id: &str,
+        apply: &mut dyn FnMut(&mut Task) -> Result<(), TaskError>,
+    ) -> Result<Task, TaskError> {
+        let _sequence = self.sequence.lock().unwrap();
+        let mut before = None;
+        let after = self.store.update_task(entry_id, &mut |task| {
+            before = Some(task.clone());
+            apply(task)
+        })?;
+        if let Some(before) = before {
+            self.publish_changes(&before, &after);
+        }
+        Ok(after)
     }
 }
 
@@ -109,7 +303,7 @@ mod tests {
     #[test]
     fn test_create_task() {
         let service = MemTaskService::new();
-        let task = service.create_task("s3://bucket/prefix/".to_string());
+        let task = service.create_task("s3://bucket/prefix/".to_string()).unwrap();
         
         assert_eq!(task.dir_location, "s3://bucket/prefix/");
         assert_eq!(task.status, TaskStatus::Created);
@@ -119,35 +313,179 @@ mod tests {
     #[test]
     fn test_full_flow() {
         let service = MemTaskService::new();
-        let task = service.create_task("s3://test".to_string());
+        let task = service.create_task("s3://test".to_string()).unwrap();
         let id = task.entry_id;
+        service.set_status(&id, TaskStatus::Processing, "audio parse started").unwrap();
 
         // 1. Audio Done
-        let task = service.update_audio_result(&id, "Hello World".to_string()).unwrap();
+        let task = service.update_audio_result(&id, "Hello World".to_string(), Vec::new()).unwrap();
         assert_eq!(task.status, TaskStatus::AudioDone);
         assert_eq!(task.transcript_text, Some("Hello World".to_string()));
 
-        // 2. Video Done
+        // 2. Video and steps complete the task
         let analysis = json!({"scenes": []});
-        let task = service.update_video_result(&id, analysis.clone()).unwrap();
-        assert_eq!(task.status, TaskStatus::VideoDone);
-        assert_eq!(task.video_analysis, Some(analysis));
-
-        // 3. Finished (Steps)
         let steps = json!({"steps": []});
-        let task = service.update_steps_result(&id, steps.clone()).unwrap();
+        let task = service.update_video_result(&id, analysis.clone(), steps.clone()).unwrap();
         assert_eq!(task.status, TaskStatus::Finished);
+        assert_eq!(task.video_analysis, Some(analysis));
         assert_eq!(task.steps_package, Some(steps));
     }
 
+    #[test]
+    fn test_video_first_waits_for_audio() {
+        let service = MemTaskService::new();
+        let id = service.create_task(String::new()).unwrap().entry_id;
+        service.set_status(&id, TaskStatus::Processing, "video parse started").unwrap();
+
+        let task = service.update_video_result(&id, json!({}), json!({"steps": []})).unwrap();
+        assert_eq!(task.status, TaskStatus::VideoDone);
+
+        service.set_status(&id, TaskStatus::Processing, "audio parse started").unwrap();
+        let task = service.update_audio_result(&id, "hi".to_string(), Vec::new()).unwrap();
+        assert_eq!(task.status, TaskStatus::Finished);
+    }
+
     #[test]
     fn test_failure_flow() {
         let service = MemTaskService::new();
-        let task = service.create_task("s3://fail".to_string());
+        let task = service.create_task("s3://fail".to_string()).unwrap();
         let id = task.entry_id;
 
         let task = service.mark_as_failed(&id, "Something went wrong".to_string()).unwrap();
         assert_eq!(task.status, TaskStatus::Failed);
         assert_eq!(task.error, Some("Something went wrong".to_string()));
+
+        // Late results for a failed task are rejected and leave it untouched
+        let err = service.update_video_result(&id, json!({"scenes": []}), json!({})).unwrap_err();
+        assert!(matches!(err, TaskError::IllegalTransition(_)));
+        let task = service.get_task(&id).unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        assert!(task.video_analysis.is_none());
+        assert_eq!(task.history.len(), 1);
+    }
+
+    #[test]
+    fn test_pipeline_failure_is_stored_structured() {
+        let service = MemTaskService::new();
+        let id = service.create_task("s3://auth".to_string()).unwrap().entry_id;
+        service.set_status(&id, TaskStatus::Processing, "video parse started").unwrap();
+
+        let error = PipelineError::ProviderAuth { stage: crate::domain::pipeline_error::Stage::VideoAnalysis, status: 401 };
+        let task = service.mark_pipeline_failed(&id, &error).unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        let failure = task.failure.unwrap();
+        assert_eq!((failure.code.as_str(), failure.retryable, failure.status), ("provider_auth", false, Some(401)));
+        assert_eq!(task.error.as_deref(), Some(failure.message.as_str()));
+        assert_eq!(task.history.last().unwrap().reason, failure.message);
+    }
+
+    #[tokio::test]
+    async fn test_service_publishes_events() {
+        let service = TaskService::new(Arc::new(MemTaskService::new()));
+        let id = service.create_task(String::new()).unwrap().entry_id;
+        let mut sub = service.subscribe(&id, None);
+
+        service.set_status(&id, TaskStatus::Processing, "audio parse started").unwrap();
+        service.update_audio_result(&id, "hi".to_string(), Vec::new()).unwrap();
+        service.set_status(&id, TaskStatus::Processing, "video parse started").unwrap();
+        service.update_video_result(&id, json!({}), json!({"steps": []})).unwrap();
+
+        let mut kinds = Vec::new();
+        while let Some(event) = sub.next().await {
+            let terminal = event.is_terminal();
+            kinds.push(event.kind.name());
+            if terminal {
+                break;
+            }
+        }
+        assert_eq!(kinds, vec!["status", "artifact", "status", "status", "artifact", "artifact", "status"]);
+
+        // A reconnecting client still sees the Finished transition
+        let mut resumed = service.subscribe(&id, Some(5));
+        assert_eq!(resumed.next_buffered().map(|e| e.kind.name()), Some("artifact"));
+        assert!(resumed.next_buffered().unwrap().is_terminal());
+    }
+
+    /// Pauses after each commit, widening the gap before events are published.
+    struct SlowStore(MemTaskService);
+
+    impl TaskStore for SlowStore {
+        fn create_task(&self, dir_location: String) -> Result<Task, TaskError> {
+            self.0.create_task(dir_location)
+        }
+
+        fn get_task(&self, entry_id: &str) -> Option<Task> {
+            self.0.get_task(entry_id)
+        }
+
+        fn list_tasks(&self) -> Vec<Task> {
+            self.0.list_tasks()
+        }
+
+        fn update_task(
+            &self,
+            entry_id: &str,
+            apply: &mut dyn FnMut(&mut Task) -> Result<(), TaskError>,
+        ) -> Result<Task, TaskError> {
+            let task = self.0.update_task(entry_id, apply);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            task
+        }
+    }
+
+    #[test]
+    fn test_concurrent_tracks_publish_in_commit_order() {
+        for _ in 0..10 {
+            let service = TaskService::new(Arc::new(SlowStore(MemTaskService::new())));
+            let id = service.create_task(String::new()).unwrap().entry_id;
+            service.set_status(&id, TaskStatus::Processing, "parse started").unwrap();
+
+            let barrier = Arc::new(std::sync::Barrier::new(2));
+            let audio = {
+                let (service, id, barrier) = (service.clone(), id.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    service.update_audio_result(&id, "hi".to_string(), Vec::new()).unwrap();
+                })
+            };
+            barrier.wait();
+            service.update_video_result(&id, json!({}), json!({"steps": []})).unwrap();
+            audio.join().unwrap();
+
+            let mut sub = service.subscribe(&id, None);
+            let events: Vec<_> = std::iter::from_fn(|| sub.next_buffered()).collect();
+            assert!(events.last().unwrap().is_terminal(), "{:?}", events);
+            assert_eq!(events.iter().filter(|e| e.is_terminal()).count(), 1);
+            assert!(events.iter().any(|e| e.kind == TaskEventKind::ArtifactReady { track: "audio".to_string() }));
+
+            let published: Vec<_> = events
+                .iter()
+                .filter_map(|e| match &e.kind {
+                    TaskEventKind::Status { to, .. } => Some(to.clone()),
+                    _ => None,
+                })
+                .collect();
+            let history: Vec<_> = service.get_task(&id).unwrap().history.into_iter().map(|c| c.to).collect();
+            assert_eq!(published, history);
+        }
+    }
+
+    #[test]
+    fn test_cancel_discards_late_results() {
+        let service = TaskService::new(Arc::new(MemTaskService::new()));
+        let id = service.create_task(String::new()).unwrap().entry_id;
+        service.set_status(&id, TaskStatus::Processing, "audio parse started").unwrap();
+        let run = service.start_run(&id);
+
+        let task = service.cancel(&id, "cancelled by user").unwrap();
+        assert_eq!(task.status, TaskStatus::Cancelled);
+        assert!(run.token().is_cancelled());
+
+        let err = service.update_audio_result(&id, "late".to_string(), Vec::new()).unwrap_err();
+        assert!(matches!(err, TaskError::IllegalTransition(_)));
+        assert!(service.mark_as_failed(&id, "late failure".to_string()).is_err());
+        let task = service.get_task(&id).unwrap();
+        assert!(task.transcript_text.is_none() && task.error.is_none());
+        assert!(service.cancel(&id, "again").is_err());
     }
 }
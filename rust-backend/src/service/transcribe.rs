@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use reqwest::multipart;
+use serde::{Deserialize, Serialize};
+
+use crate::config::TranscribeConfig;
+use crate::domain::transcript::TranscriptSegment;
+use crate::service::retry::{self, RetryHint, Retryable};
+
+/// Downloaded audio handed to a [`Transcriber`].
+#[derive(Debug, Clone)]
+pub struct AudioClip {
+    pub filename: String,
+    pub bytes: Vec<u8>,
+}
+
+impl AudioClip {
+    pub fn mime_type(&self) -> &'static str {
+        infer_audio_mime(&self.filename, &self.bytes)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Transcription {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Audio length in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<TranscriptSegment>,
+    /// Model that produced the transcript.
+    #[serde(default)]
+    pub model: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscribeError {
+    /// The request never produced an HTTP response.
+    Transport(String),
+    /// The provider answered with a non-success status.
+    Status { status: u16, body: String, retry_after: Option<Duration> },
+    /// The body was not a transcription.
+    Decode(String),
+}
+
+impl fmt::Display for TranscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscribeError::Transport(msg) => write!(f, "transcription request failed: {}", msg),
+            TranscribeError::Status { status, body, .. } => {
+                write!(f, "transcription provider returned {}: {}", status, body)
+            }
+            TranscribeError::Decode(msg) => write!(f, "invalid transcription response: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TranscribeError {}
+
+impl Retryable for TranscribeError {
+    fn retry_hint(&self) -> RetryHint {
+        match self {
+            TranscribeError::Transport(_) => RetryHint::Transient,
+            TranscribeError::Status { status, retry_after, .. } => RetryHint::for_status(*status, *retry_after),
+            TranscribeError::Decode(_) => RetryHint::Permanent,
+        }
+    }
+
+    fn timed_out(after: Duration) -> Self {
+        TranscribeError::Transport(format!("no response within {:?}", after))
+    }
+}
+
+/// A speech-to-text backend.
+pub trait Transcriber: Send + Sync {
+    fn transcribe<'a>(&'a self, audio: &'a AudioClip) -> BoxFuture<'a, Result<Transcription, TranscribeError>>;
+}
+
+/// Sniffs the audio container from magic bytes, falling back to the file
+/// extension and finally to `application/octet-stream`.
+pub fn infer_audio_mime(filename: &str, bytes: &[u8]) -> &'static str {
+    let sniffed = match bytes {
+        [b'I', b'D', b'3', ..] => Some("audio/mpeg"),
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => Some("audio/mpeg"),
+        [0xFF, 0xF1 | 0xF9, ..] => Some("audio/aac"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some("audio/wav"),
+        [b'O', b'g', b'g', b'S', ..] => Some("audio/ogg"),
+        [b'f', b'L', b'a', b'C', ..] => Some("audio/flac"),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some("audio/webm"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("audio/mp4"),
+        _ => None,
+    };
+    if let Some(mime) = sniffed {
+        return mime;
+    }
+
+    let extension = filename
+        .split(['?', '#'])
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp3" | "mpga" | "mpeg") => "audio/mpeg",
+        Some("wav") => "audio/wav",
+        Some("ogg" | "oga" | "opus") => "audio/ogg",
+        Some("flac") => "audio/flac",
+        Some("webm") => "audio/webm",
+        Some("m4a" | "mp4") => "audio/mp4",
+        Some("aac") => "audio/aac",
+        _ => "application/octet-stream",
+    }
+}
+
+// OpenAI-compatible provider
+
+pub const DEFAULT_TRANSCRIBE_BASE_URL: &str = "https://api.siliconflow.cn/v1";
+pub const DEFAULT_TRANSCRIBE_MODEL: &str = "TeleAI/TeleSpeechASR";
+
+#[derive(Deserialize)]
+struct TranscriptionBody {
+    #[serde(default)]
+    text: String,
+    language: Option<String>,
+    duration: Option<f64>,
+    #[serde(default)]
+    segments: Vec<SegmentBody>,
+}
+
+#[derive(Deserialize)]
+struct SegmentBody {
+    start: f64,
+    end: f64,
+    text: String,
+    confidence: Option<f64>,
+    /// Whisper-style mean token log-probability.
+    avg_logprob: Option<f64>,
+}
+
+impl From<SegmentBody> for TranscriptSegment {
+    fn from(s: SegmentBody) -> Self {
+        let confidence = s.confidence.or(s.avg_logprob.map(|p| p.exp().clamp(0.0, 1.0)));
+        TranscriptSegment { start: s.start, end: s.end, text: s.text, confidence }
+    }
+}
+
+/// Any `/audio/transcriptions` endpoint speaking the OpenAI wire format
+/// (SiliconFlow, OpenAI Whisper, ...).
+pub struct OpenAiTranscriber {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    /// Sent as `response_format` when set, e.g. `verbose_json` for segments.
+    pub response_format: Option<String>,
+}
+
+impl OpenAiTranscriber {
+    pub fn new(base_url: impl Into<String>, api_key: Option<String>, model: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key,
+            model: model.into(),
+            response_format: None,
+        }
+    }
+
+    pub fn from_config(config: &TranscribeConfig) -> Self {
+        let api_key = config.api_key.as_ref().map(|k| k.expose().to_string());
+        let mut transcriber = Self::new(&config.base_url, api_key, &config.model);
+        transcriber.response_format = config.response_format.clone();
+        transcriber
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("{}/audio/transcriptions", self.base_url)
+    }
+
+    async fn send(&self, audio: &AudioClip) -> Result<Transcription, TranscribeError> {
+        let part = multipart::Part::bytes(audio.bytes.clone())
+            .file_name(audio.filename.clone())
+            .mime_str(audio.mime_type())
+            .map_err(|e| TranscribeError::Transport(e.to_string()))?;
+        let mut form = multipart::Form::new().text("model", self.model.clone()).part("file", part);
+        if let Some(format) = &self.response_format {
+            form = form.text("response_format", format.clone());
+        }
+
+        let mut builder = self.client.post(self.endpoint()).multipart(form);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        let response = builder.send().await.map_err(|e| TranscribeError::Transport(e.to_string()))?;
+
+        let status = response.status();
+        let retry_after = retry::retry_after(response.headers());
+        let body = response.text().await.map_err(|e| TranscribeError::Transport(e.to_string()))?;
+        if !status.is_success() {
+            return Err(TranscribeError::Status { status: status.as_u16(), body, retry_after });
+        }
+
+        let parsed: TranscriptionBody =
+            serde_json::from_str(&body).map_err(|e| TranscribeError::Decode(e.to_string()))?;
+        Ok(Transcription {
+            text: parsed.text,
+            language: parsed.language,
+            duration: parsed.duration,
+            segments: parsed.segments.into_iter().map(TranscriptSegment::from).collect(),
+            model: self.model.clone(),
+        })
+    }
+}
+
+impl Transcriber for OpenAiTranscriber {
+    fn transcribe<'a>(&'a self, audio: &'a AudioClip) -> BoxFuture<'a, Result<Transcription, TranscribeError>> {
+        Box::pin(self.send(audio))
+    }
+}
+
+// Canned provider
+
+/// Serves fixed transcripts keyed by file name, for tests and offline runs.
+#[derive(Default)]
+pub struct CannedTranscriber {
+    transcripts: HashMap<String, Transcription>,
+    fallback: Option<Transcription>,
+}
+
+impl CannedTranscriber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, filename: impl Into<String>, transcription: Transcription) -> Self {
+        self.transcripts.insert(filename.into(), transcription);
+        self
+    }
+
+    /// Served for any file without its own transcript.
+    pub fn otherwise(mut self, transcription: Transcription) -> Self {
+        self.fallback = Some(transcription);
+        self
+    }
+}
+
+impl Transcriber for CannedTranscriber {
+    fn transcribe<'a>(&'a self, audio: &'a AudioClip) -> BoxFuture<'a, Result<Transcription, TranscribeError>> {
+        let result = self
+            .transcripts
+            .get(&audio.filename)
+            .or(self.fallback.as_ref())
+            .cloned()
+            .map(|t| Transcription { model: "canned".to_string(), ..t })
+            .ok_or_else(|| TranscribeError::Status {
+                status: 404,
+                body: format!("no canned transcript for {}", audio.filename),
+                retry_after: None,
+            });
+        Box::pin(async move { result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, http::HeaderMap, routing::post};
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_audio_mime() {
+        assert_eq!(infer_audio_mime("clip.bin", b"ID3\x04\x00"), "audio/mpeg");
+        assert_eq!(infer_audio_mime("clip.mp3", b"RIFF\x24\x00\x00\x00WAVEfmt "), "audio/wav");
+        assert_eq!(infer_audio_mime("clip", b"OggS\x00"), "audio/ogg");
+        assert_eq!(infer_audio_mime("clip", b"\x00\x00\x00\x20ftypM4A "), "audio/mp4");
+        assert_eq!(infer_audio_mime("take.FLAC?sig=abc", b""), "audio/flac");
+        assert_eq!(infer_audio_mime("notes.txt", b"hello"), "application/octet-stream");
+    }
+
+    #[tokio::test]
+    async fn test_openai_transcriber_parses_verbose_json() {
+        let app = Router::new().route(
+            "/v1/audio/transcriptions",
+            post(|headers: HeaderMap| async move {
+                assert_eq!(headers["authorization"], "Bearer secret");
+                Json(json!({
+                    "text": "open the file menu",
+                    "language": "en",
+                    "duration": 2.5,
+                    "segments": [{ "id": 0, "start": 0.0, "end": 2.5, "text": "open the file menu", "avg_logprob": 0.0 }]
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let transcriber =
+            OpenAiTranscriber::new(format!("http://{}/v1/", addr), Some("secret".to_string()), "whisper-1");
+        let clip = AudioClip { filename: "a.mp3".to_string(), bytes: b"ID3".to_vec() };
+        let transcription = transcriber.transcribe(&clip).await.unwrap();
+
+        assert_eq!(transcription.language.as_deref(), Some("en"));
+        assert_eq!(transcription.duration, Some(2.5));
+        assert_eq!(transcription.segments[0].confidence, Some(1.0));
+        assert_eq!(transcription.model, "whisper-1");
+    }
+}